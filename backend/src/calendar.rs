@@ -0,0 +1,127 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Calendar helpers for bucketing microsecond timestamps into days, weeks and months.
+
+/// Microseconds in one day
+pub const MICROS_PER_DAY: u64 = 86_400_000_000;
+
+/// Day number since the Unix epoch (UTC) of a timestamp in microseconds
+pub fn day_of(timestamp_micros: u64) -> i64 {
+    (timestamp_micros / MICROS_PER_DAY) as i64
+}
+
+/// Smallest and largest UTC offsets in use, in minutes
+pub const MIN_UTC_OFFSET_MINUTES: i32 = -12 * 60;
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Length of a slot in minutes. Every UTC offset in use is a whole number of slots,
+/// so counts kept per UTC slot can be grouped into days in any timezone.
+pub const SLOT_MINUTES: i32 = 15;
+
+/// Microseconds in one slot
+const MICROS_PER_SLOT: u64 = SLOT_MINUTES as u64 * 60_000_000;
+
+/// Slots in one day
+const SLOTS_PER_DAY: i64 = (MICROS_PER_DAY / MICROS_PER_SLOT) as i64;
+
+/// Slot number since the Unix epoch (UTC) of a timestamp in microseconds
+pub fn slot_of(timestamp_micros: u64) -> i64 {
+    (timestamp_micros / MICROS_PER_SLOT) as i64
+}
+
+/// Day number since the Unix epoch of a slot, as seen in a timezone
+/// `utc_offset_minutes` ahead of UTC
+pub fn local_day_of_slot(slot: i64, utc_offset_minutes: i32) -> i64 {
+    (slot + i64::from(utc_offset_minutes / SLOT_MINUTES)).div_euclid(SLOTS_PER_DAY)
+}
+
+/// Day number since the Unix epoch of a timestamp in microseconds,
+/// as seen in a timezone `utc_offset_minutes` ahead of UTC
+pub fn local_day_of(timestamp_micros: u64, utc_offset_minutes: i32) -> i64 {
+    let local_micros = timestamp_micros as i64 + i64::from(utc_offset_minutes) * 60_000_000;
+    local_micros.div_euclid(MICROS_PER_DAY as i64)
+}
+
+/// Whether `year` is a leap year
+pub fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Whether a day falls on the same calendar day as `original` in a later year.
+/// February 29th is remembered on February 28th in common years.
+pub fn is_anniversary(original: i64, day: i64) -> bool {
+    let (original_year, original_month, original_day) = civil_from_days(original);
+    let (year, month, day) = civil_from_days(day);
+    if year <= original_year {
+        return false;
+    }
+    (month, day) == (original_month, original_day)
+        || ((original_month, original_day) == (2, 29)
+            && (month, day) == (2, 28)
+            && !is_leap_year(year))
+}
+
+/// Convert a day number since the Unix epoch into `(year, month, day)`
+pub fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    // Howard Hinnant's `civil_from_days` algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+    (year, month, day)
+}
+
+/// Convert `(year, month, day)` into a day number since the Unix epoch
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Day of the week, with Monday as 0 and Sunday as 6
+pub fn weekday(days: i64) -> u32 {
+    // 1970-01-01 was a Thursday
+    (days + 3).rem_euclid(7) as u32
+}
+
+/// Day number of the Monday starting the week that contains `days`
+pub fn week_start(days: i64) -> i64 {
+    days - i64::from(weekday(days))
+}
+
+/// Format a day number as `YYYY-MM-DD`
+pub fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Format the month containing a day number as `YYYY-MM`
+pub fn format_month(days: i64) -> String {
+    let (year, month, _) = civil_from_days(days);
+    format!("{:04}-{:02}", year, month)
+}
+
+/// Parse a `YYYY-MM-DD` date into a day number since the Unix epoch
+pub fn parse_date(value: &str) -> Option<i64> {
+    let mut parts = value.split('-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    // Reject dates such as February 30th that roll over into the next month
+    (civil_from_days(days) == (year, month, day)).then_some(days)
+}
@@ -3,13 +3,26 @@
 mod state;
 
 use linera_sdk::{
-    linera_base_types::WithContractAbi,
+    bcs,
+    linera_base_types::{ApplicationId, WithContractAbi},
     views::{RootView, View},
     Contract, ContractRuntime,
 };
 
-use diary_backend::Operation;
-use self::state::{DiaryEntry, DiaryState};
+use diary_backend::{
+    calendar, template,
+    patch::{self, ContentEdit, EditSpan},
+    upgrade::{ExportBatch, ExportProgress, EXPORT_BATCH_SIZE},
+    DiaryAbi, DiaryParameters, EntryMetadata, Message, Operation, OwnershipAction, Visibility,
+    OWNER_TRANSFER_EXPIRY_MICROS,
+};
+use self::state::{
+    Attachment, DiaryEntry, DiaryState, Draft, EntryHeader, Notebook, OwnerTransfer,
+    OwnershipEvent, Template, DEFAULT_NOTEBOOK_ID,
+};
+
+/// Maximum number of stored values the state migration moves per block
+const MIGRATION_BATCH_SIZE: usize = 100;
 
 pub struct DiaryContract {
     state: DiaryState,
@@ -23,21 +36,34 @@ impl WithContractAbi for DiaryContract {
 }
 
 impl Contract for DiaryContract {
-    type Message = ();
-    type Parameters = ();
+    type Message = Message;
+    type Parameters = DiaryParameters;
     type InstantiationArgument = ();
     type EventValue = ();
 
-    async fn load(runtime: ContractRuntime<Self>) -> Self {
-        let state = DiaryState::load(runtime.root_view_storage_context())
+    async fn load(mut runtime: ContractRuntime<Self>) -> Self {
+        let mut state = DiaryState::load(runtime.root_view_storage_context())
             .await
             .expect("Failed to load state");
+        // Upgrade state written by earlier versions of the contract, a batch per block
+        let now = runtime.system_time().micros();
+        state.migrate(MIGRATION_BATCH_SIZE, now)
+            .await
+            .expect("Failed to migrate state");
         DiaryContract { state, runtime }
     }
 
     async fn instantiate(&mut self, _argument: Self::InstantiationArgument) {
         // Validate that the application parameters were configured correctly
-        self.runtime.application_parameters();
+        if let Some(limits) = self.runtime.application_parameters() {
+            if let Err(error) = limits.validate() {
+                panic!("Invalid query limits: {}", error);
+            }
+        }
+
+        // Remember who created the diary, so that only they can register a predecessor
+        let creator = self.runtime.authenticated_signer().map(|signer| signer.to_string());
+        self.state.creator.set(creator);
     }
 
     async fn execute_operation(&mut self, operation: Self::Operation) -> Self::Response {
@@ -45,120 +71,1061 @@ impl Contract for DiaryContract {
             Operation::Initialize { secret_phrase } => {
                 // Check if already initialized
                 assert!(!self.state.is_initialized(), "Diary already initialized");
-                
+
                 // Hash and store the secret phrase
                 let hash = diary_backend::hash_secret_phrase(&secret_phrase);
                 self.state.secret_phrase_hash.set(hash);
-                
+
                 // Set the owner
                 let owner = self.runtime.authenticated_signer()
                     .expect("Owner must be authenticated")
                     .to_string();
                 self.state.owner.set(owner);
-                
+
                 // Initialize entry count
                 self.state.entry_count.set(0);
+
+                // Create the default notebook
+                let created_at = self.runtime.system_time().micros();
+                self.state.create_default_notebook(created_at)
+                    .expect("Failed to insert notebook");
             }
-            
-            Operation::AddEntry { secret_phrase, title, content } => {
-                // Verify diary is initialized
-                assert!(self.state.is_initialized(), "Diary not initialized");
-                
-                // Verify secret phrase
-                let provided_hash = diary_backend::hash_secret_phrase(&secret_phrase);
-                let stored_hash = self.state.secret_phrase_hash.get();
-                assert_eq!(&provided_hash, stored_hash, "Invalid secret phrase");
-                
-                // Verify caller is the owner
-                let owner = self.state.owner.get();
-                let caller = self.runtime.authenticated_signer()
-                    .expect("Caller must be authenticated")
-                    .to_string();
-                assert_eq!(owner, &caller, "Only the owner can add entries");
-                
-                // Get next entry ID
-                let entry_id = *self.state.entry_count.get();
-                
-                // Create new entry
-                let entry = DiaryEntry {
-                    id: entry_id,
+
+            Operation::AddEntry {
+                secret_phrase,
+                title,
+                content,
+                notebook_id,
+                metadata,
+                visibility,
+            } => {
+                let notebook_id = notebook_id.unwrap_or(DEFAULT_NOTEBOOK_ID);
+                self.verify_notebook_writer(&secret_phrase, notebook_id, "add entries")
+                    .await;
+
+                self.create_entry(
                     title,
                     content,
-                    timestamp: self.runtime.system_time().micros(),
-                };
-                
-                // Store entry
-                self.state.entries.insert(&entry_id, entry)
-                    .expect("Failed to insert entry");
-                
-                // Increment counter
-                self.state.entry_count.set(entry_id + 1);
-            }
-            
-            Operation::UpdateEntry { secret_phrase, entry_id, title, content } => {
-                // Verify diary is initialized
-                assert!(self.state.is_initialized(), "Diary not initialized");
-                
-                // Verify secret phrase
-                let provided_hash = diary_backend::hash_secret_phrase(&secret_phrase);
-                let stored_hash = self.state.secret_phrase_hash.get();
-                assert_eq!(&provided_hash, stored_hash, "Invalid secret phrase");
-                
-                // Verify caller is the owner
-                let owner = self.state.owner.get();
-                let caller = self.runtime.authenticated_signer()
-                    .expect("Caller must be authenticated")
-                    .to_string();
-                assert_eq!(owner, &caller, "Only the owner can update entries");
-                
+                    notebook_id,
+                    metadata.unwrap_or_default(),
+                    visibility.unwrap_or_default(),
+                )
+                .await;
+            }
+
+            Operation::UpdateEntry {
+                secret_phrase,
+                entry_id,
+                title,
+                content,
+                metadata,
+                visibility,
+                expected_revision,
+            } => {
                 // Get existing entry
-                let mut entry = self.state.entries.get(&entry_id)
+                let mut entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "update entries")
+                    .await;
+                // A stale revision leaves the entry as it is and is reported to the caller
+                entry.check_revision(expected_revision)?;
+                self.state.index_entry(&entry, false)
                     .await
-                    .expect("Failed to read entry")
-                    .expect("Entry not found");
-                
+                    .expect("Failed to update indexes");
+
                 // Update fields if provided
                 if let Some(new_title) = title {
                     entry.title = new_title;
                 }
+                let mut spans = Vec::new();
                 if let Some(new_content) = content {
+                    let link_targets = self.parse_links(entry_id, &new_content).await;
+                    self.state.set_links(entry_id, Some(link_targets), None)
+                        .await
+                        .expect("Failed to update links");
+                    spans.push(EditSpan {
+                        offset: 0,
+                        len: entry.content.len() as u64,
+                        new_len: new_content.len() as u64,
+                    });
                     entry.content = new_content;
                 }
-                
+                if let Some(new_metadata) = metadata {
+                    if let Err(error) = new_metadata.validate() {
+                        panic!("Invalid metadata: {}", error);
+                    }
+                    entry.metadata = new_metadata;
+                }
+                if let Some(new_visibility) = visibility {
+                    entry.visibility = new_visibility;
+                }
+                if let Err(error) =
+                    diary_backend::validate_visibility(entry.visibility, &entry.title, &entry.content)
+                {
+                    panic!("{}", error);
+                }
+
                 // Update timestamp
                 entry.timestamp = self.runtime.system_time().micros();
-                
+                self.bump_revision(entry.id, &mut entry.revision, spans).await;
+
+                self.state.index_entry(&entry, true)
+                    .await
+                    .expect("Failed to update indexes");
+
                 // Store updated entry
-                self.state.entries.insert(&entry_id, entry)
+                self.state.write_entry(entry)
+                    .await
                     .expect("Failed to update entry");
             }
-            
-            Operation::DeleteEntry { secret_phrase, entry_id } => {
-                // Verify diary is initialized
-                assert!(self.state.is_initialized(), "Diary not initialized");
-                
-                // Verify secret phrase
+
+            Operation::DeleteEntry { secret_phrase, entry_id, expected_revision } => {
+                let entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "delete entries")
+                    .await;
+                entry.check_revision(expected_revision)?;
+                self.state.index_entry(&entry, false)
+                    .await
+                    .expect("Failed to update indexes");
+
+                self.state.remove_links(entry_id)
+                    .await
+                    .expect("Failed to update links");
+
+                self.state.pinned.get_mut().retain(|id| *id != entry_id);
+                self.state.favorites.remove(&entry_id)
+                    .expect("Failed to update favorites");
+
+                // Remove the entry
+                self.state.remove_entry(entry_id)
+                    .await
+                    .expect("Failed to delete entry");
+            }
+
+            Operation::CreateNotebook { secret_phrase, name, notebook_secret_phrase } => {
+                self.verify_owner(&secret_phrase, "create notebooks");
+                assert!(!name.trim().is_empty(), "Notebook name cannot be empty");
+
+                let notebook_id = *self.state.notebook_count.get();
+                let notebook = Notebook {
+                    id: notebook_id,
+                    name,
+                    archived: false,
+                    secret_phrase_hash: notebook_secret_phrase
+                        .as_deref()
+                        .map(diary_backend::hash_secret_phrase),
+                    created_at: self.runtime.system_time().micros(),
+                };
+
+                self.state.notebooks.insert(&notebook_id, notebook)
+                    .expect("Failed to insert notebook");
+                self.state.notebook_count.set(notebook_id + 1);
+            }
+
+            Operation::RenameNotebook { secret_phrase, notebook_id, name } => {
+                self.verify_owner(&secret_phrase, "rename notebooks");
+                assert!(!name.trim().is_empty(), "Notebook name cannot be empty");
+
+                let mut notebook = self.load_notebook(notebook_id).await;
+                notebook.name = name;
+                self.state.notebooks.insert(&notebook_id, notebook)
+                    .expect("Failed to update notebook");
+            }
+
+            Operation::ArchiveNotebook { secret_phrase, notebook_id } => {
+                self.verify_owner(&secret_phrase, "archive notebooks");
+                assert_ne!(
+                    notebook_id, DEFAULT_NOTEBOOK_ID,
+                    "The default notebook cannot be archived"
+                );
+
+                let mut notebook = self.load_notebook(notebook_id).await;
+                notebook.archived = true;
+                self.state.notebooks.insert(&notebook_id, notebook)
+                    .expect("Failed to update notebook");
+            }
+
+            Operation::UnarchiveNotebook { secret_phrase, notebook_id } => {
+                self.verify_owner(&secret_phrase, "unarchive notebooks");
+
+                let mut notebook = self.load_notebook(notebook_id).await;
+                assert!(notebook.archived, "Notebook is not archived");
+                notebook.archived = false;
+                self.state.notebooks.insert(&notebook_id, notebook)
+                    .expect("Failed to update notebook");
+            }
+
+            Operation::MoveEntry { secret_phrase, entry_id, notebook_id, target_secret_phrase } => {
+                let mut entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "move entries")
+                    .await;
+
+                let target_secret_phrase = target_secret_phrase.unwrap_or(secret_phrase);
+                self.verify_notebook_writer(&target_secret_phrase, notebook_id, "move entries")
+                    .await;
+
+                // The statistics are kept per notebook
+                self.state.index_entry(&entry, false)
+                    .await
+                    .expect("Failed to update statistics");
+                entry.notebook_id = notebook_id;
+                self.state.index_entry(&entry, true)
+                    .await
+                    .expect("Failed to update statistics");
+                self.bump_revision(entry.id, &mut entry.revision, Vec::new()).await;
+                self.state.write_entry(entry)
+                    .await
+                    .expect("Failed to move entry");
+            }
+
+            Operation::SetTimezone { secret_phrase, utc_offset_minutes } => {
+                self.verify_owner(&secret_phrase, "change settings");
+                assert!(
+                    (calendar::MIN_UTC_OFFSET_MINUTES..=calendar::MAX_UTC_OFFSET_MINUTES)
+                        .contains(&utc_offset_minutes),
+                    "UTC offset out of range"
+                );
+                assert_eq!(
+                    utc_offset_minutes % calendar::SLOT_MINUTES,
+                    0,
+                    "UTC offset must be a whole number of quarter hours"
+                );
+
+                self.state.utc_offset_minutes.set(utc_offset_minutes);
+            }
+
+            Operation::AttachFile { secret_phrase, entry_id, blob_hash, mime_type, filename } => {
+                let mut entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "attach files")
+                    .await;
+
+                if let Err(error) = diary_backend::validate_attachment(&mime_type, &filename) {
+                    panic!("{}", error);
+                }
+                assert!(
+                    entry.attachments.len() < diary_backend::MAX_ATTACHMENTS_PER_ENTRY,
+                    "Too many attachments on this entry"
+                );
+                assert!(
+                    entry.attachments.iter().all(|a| a.blob_hash != blob_hash),
+                    "File is already attached to this entry"
+                );
+
+                // Reading the blob checks that it was published and gives its real size
+                let size = self.runtime.read_data_blob(blob_hash).len() as u64;
+                assert!(
+                    size <= diary_backend::MAX_ATTACHMENT_SIZE,
+                    "Attachment exceeds the maximum size"
+                );
+
+                entry.attachments.push(Attachment {
+                    blob_hash,
+                    mime_type,
+                    size,
+                    filename,
+                    attached_at: self.runtime.system_time().micros(),
+                });
+                self.bump_revision(entry.id, &mut entry.revision, Vec::new()).await;
+                self.state.write_entry(entry)
+                    .await
+                    .expect("Failed to update entry");
+            }
+
+            Operation::SaveDraft { secret_phrase, title, content, notebook_id, metadata } => {
+                let notebook_id = notebook_id.unwrap_or(DEFAULT_NOTEBOOK_ID);
+                self.verify_notebook_writer(&secret_phrase, notebook_id, "save drafts")
+                    .await;
+
+                let metadata = metadata.unwrap_or_default();
+                if let Err(error) = metadata.validate() {
+                    panic!("Invalid metadata: {}", error);
+                }
+
+                let draft_id = *self.state.draft_count.get();
+                let timestamp = self.runtime.system_time().micros();
+                let draft = Draft {
+                    id: draft_id,
+                    title,
+                    content,
+                    notebook_id,
+                    metadata,
+                    created_at: timestamp,
+                    updated_at: timestamp,
+                };
+
+                self.state.drafts.insert(&draft_id, draft)
+                    .expect("Failed to insert draft");
+                self.state.draft_count.set(draft_id + 1);
+            }
+
+            Operation::UpdateDraft { secret_phrase, draft_id, title, content, metadata } => {
+                let mut draft = self.load_draft(draft_id).await;
+                self.verify_notebook_writer(&secret_phrase, draft.notebook_id, "update drafts")
+                    .await;
+
+                let metadata = metadata.unwrap_or_default();
+                if let Err(error) = metadata.validate() {
+                    panic!("Invalid metadata: {}", error);
+                }
+
+                // Autosave overwrites the whole draft
+                draft.title = title;
+                draft.content = content;
+                draft.metadata = metadata;
+                draft.updated_at = self.runtime.system_time().micros();
+
+                self.state.drafts.insert(&draft_id, draft)
+                    .expect("Failed to update draft");
+            }
+
+            Operation::PublishDraft { secret_phrase, draft_id } => {
+                let draft = self.load_draft(draft_id).await;
+                self.verify_notebook_writer(&secret_phrase, draft.notebook_id, "publish drafts")
+                    .await;
+                assert!(!draft.title.is_empty(), "Title cannot be empty");
+                assert!(!draft.content.is_empty(), "Content cannot be empty");
+
+                self.state.drafts.remove(&draft_id)
+                    .expect("Failed to remove draft");
+                self.create_entry(
+                    draft.title,
+                    draft.content,
+                    draft.notebook_id,
+                    draft.metadata,
+                    Visibility::default(),
+                )
+                .await;
+            }
+
+            Operation::DiscardDraft { secret_phrase, draft_id } => {
+                let draft = self.load_draft(draft_id).await;
+                self.verify_notebook_writer(&secret_phrase, draft.notebook_id, "discard drafts")
+                    .await;
+
+                self.state.drafts.remove(&draft_id)
+                    .expect("Failed to remove draft");
+            }
+
+            Operation::CreateTemplate { secret_phrase, name, title_pattern, content_skeleton } => {
+                self.verify_owner(&secret_phrase, "create templates");
+                assert!(!name.trim().is_empty(), "Template name cannot be empty");
+                Self::validate_template(&title_pattern, &content_skeleton);
+
+                let template_id = *self.state.template_count.get();
+                let template = Template {
+                    id: template_id,
+                    name,
+                    title_pattern,
+                    content_skeleton,
+                    created_at: self.runtime.system_time().micros(),
+                };
+
+                self.state.templates.insert(&template_id, template)
+                    .expect("Failed to insert template");
+                self.state.template_count.set(template_id + 1);
+            }
+
+            Operation::UpdateTemplate {
+                secret_phrase,
+                template_id,
+                name,
+                title_pattern,
+                content_skeleton,
+            } => {
+                self.verify_owner(&secret_phrase, "update templates");
+
+                let mut template = self.load_template(template_id).await;
+                if let Some(new_name) = name {
+                    assert!(!new_name.trim().is_empty(), "Template name cannot be empty");
+                    template.name = new_name;
+                }
+                if let Some(new_title_pattern) = title_pattern {
+                    template.title_pattern = new_title_pattern;
+                }
+                if let Some(new_content_skeleton) = content_skeleton {
+                    template.content_skeleton = new_content_skeleton;
+                }
+                Self::validate_template(&template.title_pattern, &template.content_skeleton);
+
+                self.state.templates.insert(&template_id, template)
+                    .expect("Failed to update template");
+            }
+
+            Operation::DeleteTemplate { secret_phrase, template_id } => {
+                self.verify_owner(&secret_phrase, "delete templates");
+                self.load_template(template_id).await;
+
+                self.state.templates.remove(&template_id)
+                    .expect("Failed to delete template");
+            }
+
+            Operation::AddEntryFromTemplate { secret_phrase, template_id, values, notebook_id } => {
+                let notebook_id = notebook_id.unwrap_or(DEFAULT_NOTEBOOK_ID);
+                self.verify_notebook_writer(&secret_phrase, notebook_id, "add entries")
+                    .await;
+
+                let template = self.load_template(template_id).await;
+
+                let values = template::values_with_builtins(
+                    values.into_iter().map(|v| (v.key, v.value)).collect(),
+                    self.runtime.system_time().micros(),
+                    *self.state.utc_offset_minutes.get(),
+                )
+                .unwrap_or_else(|error| panic!("{}", error));
+
+                let title = template::render(&template.title_pattern, &values)
+                    .unwrap_or_else(|error| panic!("{}", error));
+                let content = template::render(&template.content_skeleton, &values)
+                    .unwrap_or_else(|error| panic!("{}", error));
+                assert!(!title.is_empty(), "Title cannot be empty");
+                assert!(!content.is_empty(), "Content cannot be empty");
+
+                self.create_entry(
+                    title,
+                    content,
+                    notebook_id,
+                    EntryMetadata::default(),
+                    Visibility::default(),
+                )
+                .await;
+            }
+
+            Operation::LinkEntries { secret_phrase, from_entry_id, to_entry_id } => {
+                let entry = self.load_entry(from_entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "link entries")
+                    .await;
+                assert_ne!(from_entry_id, to_entry_id, "An entry cannot link to itself");
+                self.load_entry(to_entry_id).await;
+
+                let mut targets = self.state.explicit_links.get(&from_entry_id)
+                    .await
+                    .expect("Failed to read links")
+                    .unwrap_or_default();
+                assert!(!targets.contains(&to_entry_id), "Entries are already linked");
+                targets.push(to_entry_id);
+
+                self.state.set_links(from_entry_id, None, Some(targets))
+                    .await
+                    .expect("Failed to update links");
+            }
+
+            Operation::UnlinkEntries { secret_phrase, from_entry_id, to_entry_id } => {
+                let entry = self.load_entry(from_entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "unlink entries")
+                    .await;
+
+                let mut targets = self.state.explicit_links.get(&from_entry_id)
+                    .await
+                    .expect("Failed to read links")
+                    .unwrap_or_default();
+                assert!(targets.contains(&to_entry_id), "Link not found");
+                targets.retain(|t| *t != to_entry_id);
+
+                self.state.set_links(from_entry_id, None, Some(targets))
+                    .await
+                    .expect("Failed to update links");
+            }
+
+            Operation::PinEntry { secret_phrase, entry_id, position } => {
+                let entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "pin entries")
+                    .await;
+
+                let pinned = self.state.pinned.get_mut();
+                pinned.retain(|id| *id != entry_id);
+                let position = position.map_or(pinned.len(), |p| (p as usize).min(pinned.len()));
+                pinned.insert(position, entry_id);
+            }
+
+            Operation::UnpinEntry { secret_phrase, entry_id } => {
+                let entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "unpin entries")
+                    .await;
+
+                let pinned = self.state.pinned.get_mut();
+                assert!(pinned.contains(&entry_id), "Entry is not pinned");
+                pinned.retain(|id| *id != entry_id);
+            }
+
+            Operation::Favorite { secret_phrase, entry_id } => {
+                let entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "favorite entries")
+                    .await;
+
+                self.state.favorites.insert(&entry_id)
+                    .expect("Failed to update favorites");
+            }
+
+            Operation::Unfavorite { secret_phrase, entry_id } => {
+                let entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "unfavorite entries")
+                    .await;
+
+                self.state.favorites.remove(&entry_id)
+                    .expect("Failed to update favorites");
+            }
+
+            Operation::DetachFile { secret_phrase, entry_id, blob_hash } => {
+                let mut entry = self.load_entry(entry_id).await;
+                self.verify_notebook_writer(&secret_phrase, entry.notebook_id, "detach files")
+                    .await;
+
+                let count = entry.attachments.len();
+                entry.attachments.retain(|a| a.blob_hash != blob_hash);
+                assert_ne!(count, entry.attachments.len(), "Attachment not found");
+
+                self.bump_revision(entry.id, &mut entry.revision, Vec::new()).await;
+                self.state.write_entry(entry)
+                    .await
+                    .expect("Failed to update entry");
+            }
+
+            Operation::SetMembersPhrase { secret_phrase, members_phrase } => {
+                self.verify_owner(&secret_phrase, "change settings");
+
+                let hash = members_phrase.map(|phrase| {
+                    assert!(!phrase.is_empty(), "Members phrase cannot be empty");
+                    assert_ne!(phrase, secret_phrase, "Members phrase must differ from the secret phrase");
+                    diary_backend::hash_secret_phrase(&phrase)
+                });
+                self.state.members_phrase_hash.set(hash);
+            }
+
+            Operation::ExportToSuccessor { secret_phrase, successor } => {
+                self.verify_signer("export the diary");
                 let provided_hash = diary_backend::hash_secret_phrase(&secret_phrase);
-                let stored_hash = self.state.secret_phrase_hash.get();
-                assert_eq!(&provided_hash, stored_hash, "Invalid secret phrase");
-                
-                // Verify caller is the owner
-                let owner = self.state.owner.get();
+                assert_eq!(&provided_hash, self.state.secret_phrase_hash.get(), "Invalid secret phrase");
+
+                self.export_next_batch(successor).await;
+            }
+
+            Operation::RegisterPredecessor { predecessor } => {
+                assert!(!self.state.is_initialized(), "Diary already initialized");
                 let caller = self.runtime.authenticated_signer()
-                    .expect("Caller must be authenticated")
+                    .expect("Owner must be authenticated")
                     .to_string();
-                assert_eq!(owner, &caller, "Only the owner can delete entries");
-                
-                // Remove the entry
-                self.state.entries.remove(&entry_id)
-                    .expect("Failed to delete entry");
+                assert_eq!(
+                    self.state.creator.get().as_ref(),
+                    Some(&caller),
+                    "Only the creator of the diary can register a predecessor"
+                );
+                assert_ne!(
+                    predecessor,
+                    self.runtime.application_id().forget_abi(),
+                    "A diary cannot be its own predecessor"
+                );
+
+                self.state.importing_from.set(Some(predecessor));
+            }
+
+            Operation::ImportFromPredecessor { batch } => {
+                let caller = self.runtime.authenticated_caller_id();
+                assert!(
+                    caller.is_some() && caller == *self.state.importing_from.get(),
+                    "Imports are only accepted from the registered predecessor application"
+                );
+                let batch = bcs::from_bytes(&batch).expect("Invalid export batch");
+
+                self.import_batch(batch).await;
+            }
+
+            Operation::AppendToEntry { secret_phrase, entry_id, text } => {
+                let header = self.load_header(entry_id).await;
+                let content_len = self.state.content_len(entry_id)
+                    .await
+                    .expect("Failed to read entry");
+                let edit = ContentEdit {
+                    offset: content_len as u64,
+                    len: 0,
+                    text,
+                };
+                self.patch_content(&secret_phrase, header, None, vec![edit]).await;
+            }
+
+            Operation::ReplaceRange {
+                secret_phrase,
+                entry_id,
+                offset,
+                len,
+                text,
+            } => {
+                let header = self.load_header(entry_id).await;
+                let edit = ContentEdit { offset, len, text };
+                self.patch_content(&secret_phrase, header, None, vec![edit]).await;
+            }
+
+            Operation::PatchEntry {
+                secret_phrase,
+                entry_id,
+                base_revision,
+                patch,
+            } => {
+                let header = self.load_header(entry_id).await;
+                self.patch_content(&secret_phrase, header, Some(base_revision), patch)
+                    .await;
+            }
+
+            Operation::ProposeOwnerTransfer { secret_phrase, new_owner } => {
+                self.verify_owner(&secret_phrase, "transfer the diary");
+                let new_owner = new_owner.to_string();
+                assert_ne!(
+                    self.state.owner.get(), &new_owner,
+                    "The diary already belongs to {}", new_owner
+                );
+
+                let now = self.runtime.system_time().micros();
+                self.state.pending_owner_transfer.set(Some(OwnerTransfer {
+                    new_owner: new_owner.clone(),
+                    proposed_at: now,
+                    expires_at: now + OWNER_TRANSFER_EXPIRY_MICROS,
+                }));
+                self.log_ownership(OwnershipAction::Proposed, new_owner);
+            }
+
+            Operation::AcceptOwnership => {
+                let diary_chain_id = self.runtime.application_creator_chain_id();
+                if self.runtime.chain_id() == diary_chain_id {
+                    self.accept_ownership();
+                } else {
+                    // The new owner signs on their own chain, so the signer goes along
+                    self.runtime.prepare_message(Message::AcceptOwnership)
+                        .with_authentication()
+                        .send_to(diary_chain_id);
+                }
+            }
+
+            Operation::CancelOwnerTransfer { secret_phrase } => {
+                self.verify_owner(&secret_phrase, "cancel owner transfers");
+
+                let transfer = self.state.pending_owner_transfer.get().clone()
+                    .expect("No owner transfer is pending");
+                self.state.pending_owner_transfer.set(None);
+                self.log_ownership(OwnershipAction::Cancelled, transfer.new_owner);
             }
         }
+
+        Ok(())
     }
 
-    async fn execute_message(&mut self, _message: Self::Message) {}
+    async fn execute_message(&mut self, message: Self::Message) {
+        match message {
+            Message::AcceptOwnership => self.accept_ownership(),
+        }
+    }
 
     async fn store(mut self) {
         self.state.save().await.expect("Failed to save state");
     }
 }
+
+impl DiaryContract {
+    /// Store a new entry and return its ID
+    async fn create_entry(
+        &mut self,
+        title: String,
+        content: String,
+        notebook_id: u64,
+        metadata: EntryMetadata,
+        visibility: Visibility,
+    ) -> u64 {
+        if let Err(error) = metadata.validate() {
+            panic!("Invalid metadata: {}", error);
+        }
+        if let Err(error) = diary_backend::validate_visibility(visibility, &title, &content) {
+            panic!("{}", error);
+        }
+
+        // Get next entry ID
+        let entry_id = *self.state.entry_count.get();
+
+        let link_targets = self.parse_links(entry_id, &content).await;
+
+        // Create new entry
+        let timestamp = self.runtime.system_time().micros();
+        let entry = DiaryEntry {
+            id: entry_id,
+            title,
+            content,
+            timestamp,
+            created_at: timestamp,
+            notebook_id,
+            metadata,
+            attachments: Vec::new(),
+            visibility,
+            revision: 0,
+        };
+
+        self.state.index_entry(&entry, true)
+            .await
+            .expect("Failed to update indexes");
+
+        // Store entry
+        self.state.write_entry(entry)
+            .await
+            .expect("Failed to insert entry");
+
+        self.state.set_links(entry_id, Some(link_targets), None)
+            .await
+            .expect("Failed to update links");
+
+        // Increment counter
+        self.state.entry_count.set(entry_id + 1);
+        entry_id
+    }
+
+    /// Apply a patch to an entry's content, rebasing it first if it was made on an earlier
+    /// revision than `base_revision`. Only the chunks of the body that the edits touch are
+    /// read and rewritten, and the indexes are adjusted from the text they lose and gain.
+    async fn patch_content(
+        &mut self,
+        secret_phrase: &str,
+        mut header: EntryHeader,
+        base_revision: Option<u64>,
+        patch: Vec<ContentEdit>,
+    ) {
+        self.verify_notebook_writer(secret_phrase, header.notebook_id, "edit entries")
+            .await;
+        assert!(
+            header.visibility != Visibility::Private,
+            "Private entries are encrypted, so their content can only be replaced whole"
+        );
+
+        let patch = match base_revision {
+            Some(base_revision) if base_revision != header.revision => {
+                let history = self.state.get_edit_history(header.id).await;
+                patch::rebase(patch, base_revision, header.revision, &history)
+                    .unwrap_or_else(|error| panic!("Cannot merge patch: {}", error))
+            }
+            _ => patch,
+        };
+        let content_len = self.state.content_len(header.id)
+            .await
+            .expect("Failed to read entry");
+        if let Err(error) = patch::validate(&patch, content_len) {
+            panic!("Invalid patch: {}", error);
+        }
+        let removed: u64 = patch.iter().map(|edit| edit.len).sum();
+        let inserted: u64 = patch.iter().map(|edit| edit.text.len() as u64).sum();
+        assert!(
+            content_len as u64 - removed + inserted > 0,
+            "Content cannot be empty"
+        );
+
+        // From the end, so that the offsets of the edits before stay valid
+        let mut links = Vec::new();
+        for edit in patch.iter().rev() {
+            let (start, end) = (edit.offset as usize, edit.end() as usize);
+            let span = self.state.read_span(header.id, start, end)
+                .await
+                .expect("Failed to read entry");
+            let (from, to) = (start - span.start, end - span.start);
+            assert!(
+                span.text.is_char_boundary(from) && span.text.is_char_boundary(to),
+                "Range must start and end on character boundaries"
+            );
+            let mut edited = span.text.clone();
+            edited.replace_range(from..to, &edit.text);
+            links = self.state.write_span(&mut header, span, edited)
+                .await
+                .expect("Failed to update entry");
+        }
+
+        let entry_id = header.id;
+        header.timestamp = self.runtime.system_time().micros();
+        self.bump_revision(entry_id, &mut header.revision, patch::spans(&patch))
+            .await;
+        self.state.write_header(header)
+            .expect("Failed to update entry");
+
+        let link_targets = self.existing_targets(entry_id, links).await;
+        self.state.set_links(entry_id, Some(link_targets), None)
+            .await
+            .expect("Failed to update links");
+    }
+
+    /// Count a change to an entry, recording the ranges of its content the change replaced
+    async fn bump_revision(&mut self, entry_id: u64, revision: &mut u64, spans: Vec<EditSpan>) {
+        *revision += 1;
+        self.state.record_revision(entry_id, *revision, spans)
+            .await
+            .expect("Failed to record revision");
+    }
+
+    /// Hand the diary over to the signer if it was offered to them and the offer is still open
+    fn accept_ownership(&mut self) {
+        assert!(self.state.is_initialized(), "Diary not initialized");
+        self.verify_writable();
+
+        let transfer = self.state.pending_owner_transfer.get().clone()
+            .expect("No owner transfer is pending");
+        let signer = self.runtime.authenticated_signer()
+            .expect("Caller must be authenticated")
+            .to_string();
+        assert_eq!(transfer.new_owner, signer, "The diary was not offered to {}", signer);
+        let now = self.runtime.system_time().micros();
+        assert!(!transfer.is_expired(now), "The owner transfer expired");
+
+        self.log_ownership(OwnershipAction::Accepted, signer.clone());
+        self.state.owner.set(signer);
+        self.state.pending_owner_transfer.set(None);
+    }
+
+    /// Record a step of an owner transfer to `new_owner` in the audit log
+    fn log_ownership(&mut self, action: OwnershipAction, new_owner: String) {
+        let event = OwnershipEvent {
+            action,
+            owner: self.state.owner.get().clone(),
+            new_owner,
+            timestamp: self.runtime.system_time().micros(),
+        };
+        self.state.ownership_log.get_mut().push(event);
+    }
+
+    /// Existing entries referenced as `[[entry:42]]` in the content of `source`
+    async fn parse_links(&self, source: u64, content: &str) -> Vec<u64> {
+        let links = diary_backend::text::extract_entry_links(content);
+        self.existing_targets(source, links).await
+    }
+
+    /// The entries of `targets` that exist, other than `source`
+    async fn existing_targets(&self, source: u64, targets: Vec<u64>) -> Vec<u64> {
+        let mut existing = Vec::new();
+        for target in targets {
+            if target != source && self.state.get_header(target).await.is_some() {
+                existing.push(target);
+            }
+        }
+        existing
+    }
+
+    /// Verify that the diary is initialized, the diary secret phrase matches
+    /// and the caller is the owner
+    fn verify_owner(&mut self, secret_phrase: &str, action: &str) {
+        self.verify_caller(action);
+
+        let provided_hash = diary_backend::hash_secret_phrase(secret_phrase);
+        let stored_hash = self.state.secret_phrase_hash.get();
+        assert_eq!(&provided_hash, stored_hash, "Invalid secret phrase");
+    }
+
+    /// Verify that the caller is the owner and may write to the given notebook.
+    /// Protected notebooks only accept their own secret phrase.
+    async fn verify_notebook_writer(&mut self, secret_phrase: &str, notebook_id: u64, action: &str) {
+        self.verify_caller(action);
+
+        let notebook = self.load_notebook(notebook_id).await;
+        assert!(!notebook.archived, "Notebook is archived");
+        assert!(
+            notebook.accepts_secret(secret_phrase, self.state.secret_phrase_hash.get()),
+            "Invalid secret phrase"
+        );
+    }
+
+    /// Verify that the caller is the owner and the diary accepts writes
+    fn verify_caller(&mut self, action: &str) {
+        self.verify_signer(action);
+        self.verify_writable();
+    }
+
+    /// Verify that the diary was neither exported nor is still being imported
+    fn verify_writable(&self) {
+        if let Some(export) = self.state.export.get() {
+            panic!("Diary moved to application {}", export.successor);
+        }
+        assert!(self.state.importing_from.get().is_none(), "Diary is still being imported");
+    }
+
+    /// Verify that the diary is initialized and the caller is the owner
+    fn verify_signer(&mut self, action: &str) {
+        assert!(self.state.is_initialized(), "Diary not initialized");
+
+        let caller = self.runtime.authenticated_signer()
+            .expect("Caller must be authenticated")
+            .to_string();
+        assert_eq!(self.state.owner.get(), &caller, "Only the owner can {}", action);
+    }
+
+    /// Send the next batch of the diary to `successor`, starting with the header
+    async fn export_next_batch(&mut self, successor: ApplicationId) {
+        let mut progress = match self.state.export.get().clone() {
+            Some(progress) => {
+                assert_eq!(
+                    progress.successor, successor,
+                    "Diary is being exported to {}", progress.successor
+                );
+                assert!(!progress.finished, "Diary was already exported to {}", successor);
+                progress
+            }
+            None => {
+                assert_ne!(
+                    successor,
+                    self.runtime.application_id().forget_abi(),
+                    "Cannot export the diary to itself"
+                );
+                assert!(self.state.importing_from.get().is_none(), "Diary is still being imported");
+                let header = self.state.export_header().await;
+                self.send_to_successor(successor, ExportBatch::Header(Box::new(header)));
+                ExportProgress {
+                    successor,
+                    next_entry_id: 0,
+                    finished: false,
+                }
+            }
+        };
+
+        let entry_count = *self.state.entry_count.get();
+        let end = entry_count.min(progress.next_entry_id + EXPORT_BATCH_SIZE);
+        let entries = self.state.export_entries(progress.next_entry_id..end)
+            .await
+            .expect("Failed to read entries");
+        if !entries.is_empty() {
+            self.send_to_successor(successor, ExportBatch::Entries(entries));
+        }
+        progress.next_entry_id = end;
+
+        if end == entry_count {
+            self.send_to_successor(successor, ExportBatch::Done);
+            progress.finished = true;
+        }
+        self.state.export.set(Some(progress));
+    }
+
+    /// Call the successor application with one export batch
+    fn send_to_successor(&mut self, successor: ApplicationId, batch: ExportBatch) {
+        let batch = bcs::to_bytes(&batch).expect("Failed to serialize export batch");
+        self.runtime.call_application(
+            true,
+            successor.with_abi::<DiaryAbi>(),
+            &Operation::ImportFromPredecessor { batch },
+        )
+        .expect("Imports never check revisions");
+    }
+
+    /// Apply one export batch received from the registered predecessor application
+    async fn import_batch(&mut self, batch: ExportBatch) {
+        if let ExportBatch::Header(header) = batch {
+            assert!(!self.state.is_initialized(), "Diary already initialized");
+            let signer = self.runtime.authenticated_signer()
+                .expect("Owner must be authenticated")
+                .to_string();
+            assert_eq!(header.owner, signer, "Only the owner can import the diary");
+
+            self.state.secret_phrase_hash.set(header.secret_phrase_hash);
+            self.state.owner.set(header.owner);
+            self.state.utc_offset_minutes.set(header.utc_offset_minutes);
+            self.state.pinned.set(header.pinned);
+            self.state.entry_count.set(header.entry_count);
+            for notebook in header.notebooks {
+                let id = notebook.id;
+                self.state.notebooks.insert(&id, notebook)
+                    .expect("Failed to insert notebook");
+            }
+            self.state.notebook_count.set(header.notebook_count);
+            for draft in header.drafts {
+                let id = draft.id;
+                self.state.drafts.insert(&id, draft)
+                    .expect("Failed to insert draft");
+            }
+            self.state.draft_count.set(header.draft_count);
+            for template in header.templates {
+                let id = template.id;
+                self.state.templates.insert(&id, template)
+                    .expect("Failed to insert template");
+            }
+            self.state.template_count.set(header.template_count);
+            self.state.members_phrase_hash.set(header.members_phrase_hash);
+            self.state.pending_owner_transfer.set(header.pending_owner_transfer);
+            self.state.ownership_log.set(header.ownership_log);
+            return;
+        }
+
+        assert!(self.state.is_initialized(), "The header of the diary was not imported");
+        match batch {
+            ExportBatch::Entries(entries) => {
+                for exported in entries {
+                    let entry_id = exported.entry.id;
+                    self.state.index_entry(&exported.entry, true)
+                        .await
+                        .expect("Failed to update indexes");
+                    self.state.write_entry(exported.entry)
+                        .await
+                        .expect("Failed to insert entry");
+                    self.state.set_links(
+                        entry_id,
+                        Some(exported.content_links),
+                        Some(exported.explicit_links),
+                    )
+                    .await
+                    .expect("Failed to update links");
+                    if exported.favorite {
+                        self.state.favorites.insert(&entry_id)
+                            .expect("Failed to update favorites");
+                    }
+                    if !exported.edit_history.is_empty() {
+                        self.state.edit_history.insert(&entry_id, exported.edit_history)
+                            .expect("Failed to update edit history");
+                    }
+                }
+            }
+            ExportBatch::Done => self.state.importing_from.set(None),
+            ExportBatch::Header(_) => unreachable!(),
+        }
+    }
+
+    /// Load an existing entry or abort. An entry still waiting for the migration is
+    /// migrated first, so that it is in the indexes before an edit takes it out of them.
+    async fn load_entry(&mut self, entry_id: u64) -> DiaryEntry {
+        self.state.migrate_entry(entry_id)
+            .await
+            .expect("Failed to migrate entry");
+        self.state.read_entry(entry_id)
+            .await
+            .expect("Failed to read entry")
+            .expect("Entry not found")
+    }
+
+    /// Load the header of an existing entry or abort, migrating the entry first like
+    /// [`Self::load_entry`]
+    async fn load_header(&mut self, entry_id: u64) -> EntryHeader {
+        self.state.migrate_entry(entry_id)
+            .await
+            .expect("Failed to migrate entry");
+        self.state.read_header(entry_id)
+            .await
+            .expect("Failed to read entry")
+            .expect("Entry not found")
+    }
+
+    /// Abort if a template pattern has malformed placeholders
+    fn validate_template(title_pattern: &str, content_skeleton: &str) {
+        assert!(!title_pattern.trim().is_empty(), "Title pattern cannot be empty");
+        if let Err(error) = template::placeholders(title_pattern)
+            .and_then(|_| template::placeholders(content_skeleton))
+        {
+            panic!("{}", error);
+        }
+    }
+
+    /// Load an existing template or abort
+    async fn load_template(&self, template_id: u64) -> Template {
+        self.state.templates.get(&template_id)
+            .await
+            .expect("Failed to read template")
+            .expect("Template not found")
+    }
+
+    /// Load an existing draft or abort
+    async fn load_draft(&self, draft_id: u64) -> Draft {
+        self.state.drafts.get(&draft_id)
+            .await
+            .expect("Failed to read draft")
+            .expect("Draft not found")
+    }
+
+    /// Load an existing notebook or abort
+    async fn load_notebook(&self, notebook_id: u64) -> Notebook {
+        self.state.notebooks.get(&notebook_id)
+            .await
+            .expect("Failed to read notebook")
+            .expect("Notebook not found")
+    }
+}
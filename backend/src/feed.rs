@@ -0,0 +1,172 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Atom 1.0 and RSS 2.0 rendering of public diary entries.
+
+use crate::calendar;
+
+/// Abbreviated weekday names used by RFC 822 dates, starting on Monday
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Abbreviated month names used by RFC 822 dates, starting in January
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Channel-level details of a feed
+pub struct FeedInfo {
+    /// ID of the chain the diary lives on, used for stable IDs
+    pub chain_id: String,
+    pub title: String,
+    /// Address of the page the diary is published on
+    pub link: String,
+    pub description: String,
+    pub author: String,
+}
+
+/// One entry of a feed
+pub struct FeedItem {
+    pub id: u64,
+    pub title: String,
+    pub content: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub tags: Vec<String>,
+}
+
+/// Stable ID of the whole feed
+pub fn feed_id(chain_id: &str) -> String {
+    format!("urn:linera-diary:{}", chain_id)
+}
+
+/// Stable ID of an entry, which never changes when the entry is edited
+pub fn entry_guid(chain_id: &str, entry_id: u64) -> String {
+    format!("urn:linera-diary:{}:entry:{}", chain_id, entry_id)
+}
+
+/// Render an Atom 1.0 document. Items are expected newest first.
+pub fn atom(info: &FeedInfo, items: &[FeedItem]) -> String {
+    let updated = items.iter().map(|item| item.updated_at).max().unwrap_or(0);
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    push_element(&mut xml, 1, "id", &feed_id(&info.chain_id));
+    push_element(&mut xml, 1, "title", &info.title);
+    push_element(&mut xml, 1, "subtitle", &info.description);
+    push_element(&mut xml, 1, "updated", &format_rfc3339(updated));
+    xml.push_str(&format!(
+        "  <link rel=\"alternate\" href=\"{}\"/>\n",
+        escape(&info.link)
+    ));
+    xml.push_str("  <author>\n");
+    push_element(&mut xml, 2, "name", &info.author);
+    xml.push_str("  </author>\n");
+
+    for item in items {
+        xml.push_str("  <entry>\n");
+        push_element(&mut xml, 2, "id", &entry_guid(&info.chain_id, item.id));
+        push_element(&mut xml, 2, "title", &item.title);
+        push_element(&mut xml, 2, "published", &format_rfc3339(item.created_at));
+        push_element(&mut xml, 2, "updated", &format_rfc3339(item.updated_at));
+        for tag in &item.tags {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape(tag)));
+        }
+        xml.push_str(&format!(
+            "    <content type=\"text\">{}</content>\n",
+            escape(&item.content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Render an RSS 2.0 document. Items are expected newest first.
+pub fn rss(info: &FeedInfo, items: &[FeedItem]) -> String {
+    let updated = items.iter().map(|item| item.updated_at).max().unwrap_or(0);
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\">\n");
+    xml.push_str("  <channel>\n");
+    push_element(&mut xml, 2, "title", &info.title);
+    push_element(&mut xml, 2, "link", &info.link);
+    push_element(&mut xml, 2, "description", &info.description);
+    push_element(&mut xml, 2, "lastBuildDate", &format_rfc822(updated));
+
+    for item in items {
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!(
+            "      <guid isPermaLink=\"false\">{}</guid>\n",
+            escape(&entry_guid(&info.chain_id, item.id))
+        ));
+        push_element(&mut xml, 3, "title", &item.title);
+        push_element(&mut xml, 3, "pubDate", &format_rfc822(item.created_at));
+        for tag in &item.tags {
+            push_element(&mut xml, 3, "category", tag);
+        }
+        push_element(&mut xml, 3, "description", &item.content);
+        xml.push_str("    </item>\n");
+    }
+
+    xml.push_str("  </channel>\n");
+    xml.push_str("</rss>\n");
+    xml
+}
+
+/// Format a timestamp in microseconds as an RFC 3339 date, e.g. `2024-03-05T14:30:00Z`
+pub fn format_rfc3339(timestamp_micros: u64) -> String {
+    let day = calendar::day_of(timestamp_micros);
+    let seconds = (timestamp_micros % calendar::MICROS_PER_DAY) / 1_000_000;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        calendar::format_date(day),
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Format a timestamp in microseconds as an RFC 822 date, e.g. `Tue, 05 Mar 2024 14:30:00 GMT`
+pub fn format_rfc822(timestamp_micros: u64) -> String {
+    let day = calendar::day_of(timestamp_micros);
+    let (year, month, day_of_month) = calendar::civil_from_days(day);
+    let seconds = (timestamp_micros % calendar::MICROS_PER_DAY) / 1_000_000;
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[calendar::weekday(day) as usize],
+        day_of_month,
+        MONTHS[month as usize - 1],
+        year,
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Escape text for XML content and attribute values.
+/// Control characters that XML 1.0 does not allow are dropped.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Append `<name>text</name>` on its own line, indented by `depth` levels
+fn push_element(xml: &mut String, depth: usize, name: &str, text: &str) {
+    xml.push_str(&"  ".repeat(depth));
+    xml.push_str(&format!("<{}>{}</{}>\n", name, escape(text), name));
+}
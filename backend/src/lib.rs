@@ -1,18 +1,31 @@
 // Copyright (c) Linera.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod calendar;
+pub mod feed;
+pub mod patch;
+pub mod schema;
+pub mod storage;
+pub mod template;
+pub mod text;
+pub mod upgrade;
+
 use async_graphql::{Request, Response};
 use linera_sdk::{
     graphql::GraphQLMutationRoot,
-    linera_base_types::{ContractAbi, ServiceAbi},
+    linera_base_types::{AccountOwner, ApplicationId, ContractAbi, DataBlobHash, ServiceAbi},
 };
 use serde::{Deserialize, Serialize};
 
+use crate::patch::ContentEdit;
+
 pub struct DiaryAbi;
 
 impl ContractAbi for DiaryAbi {
     type Operation = Operation;
-    type Response = ();
+    /// `UpdateEntry` and `DeleteEntry` respond with the conflict when the entry is no
+    /// longer at the expected revision, and leave it unchanged
+    type Response = Result<(), RevisionConflict>;
 }
 
 impl ServiceAbi for DiaryAbi {
@@ -26,8 +39,15 @@ pub enum Operation {
     /// Initialize the diary with a secret phrase
     Initialize { secret_phrase: String },
 
-    /// Add a new diary entry
-    AddEntry { secret_phrase: String, title: String, content: String },
+    /// Add a new diary entry, optionally into a specific notebook
+    AddEntry {
+        secret_phrase: String,
+        title: String,
+        content: String,
+        notebook_id: Option<u64>,
+        metadata: Option<EntryMetadata>,
+        visibility: Option<Visibility>,
+    },
 
     /// Update an entry
     UpdateEntry {
@@ -35,10 +55,848 @@ pub enum Operation {
         entry_id: u64,
         title: Option<String>,
         content: Option<String>,
+        metadata: Option<EntryMetadata>,
+        visibility: Option<Visibility>,
+        /// Respond with a `RevisionConflict` unless the entry is still at this revision
+        expected_revision: Option<u64>,
     },
 
     /// Delete an entry
-    DeleteEntry { secret_phrase: String, entry_id: u64 },
+    DeleteEntry {
+        secret_phrase: String,
+        entry_id: u64,
+        /// Respond with a `RevisionConflict` unless the entry is still at this revision
+        expected_revision: Option<u64>,
+    },
+
+    /// Create a named notebook, optionally protected by its own secret phrase
+    CreateNotebook {
+        secret_phrase: String,
+        name: String,
+        notebook_secret_phrase: Option<String>,
+    },
+
+    /// Rename a notebook
+    RenameNotebook {
+        secret_phrase: String,
+        notebook_id: u64,
+        name: String,
+    },
+
+    /// Archive a notebook so that no new entries can be written to it
+    ArchiveNotebook { secret_phrase: String, notebook_id: u64 },
+
+    /// Move an entry into another notebook.
+    /// `target_secret_phrase` is needed when the target notebook has a different secret.
+    MoveEntry {
+        secret_phrase: String,
+        entry_id: u64,
+        notebook_id: u64,
+        target_secret_phrase: Option<String>,
+    },
+
+    /// Set the owner's timezone as an offset from UTC in minutes
+    SetTimezone {
+        secret_phrase: String,
+        utc_offset_minutes: i32,
+    },
+
+    /// Attach a file, published beforehand as a data blob, to an entry
+    AttachFile {
+        secret_phrase: String,
+        entry_id: u64,
+        blob_hash: DataBlobHash,
+        mime_type: String,
+        filename: String,
+    },
+
+    /// Remove an attachment from an entry
+    DetachFile {
+        secret_phrase: String,
+        entry_id: u64,
+        blob_hash: DataBlobHash,
+    },
+
+    /// Save a new draft, kept apart from the published entries
+    SaveDraft {
+        secret_phrase: String,
+        title: String,
+        content: String,
+        notebook_id: Option<u64>,
+        metadata: Option<EntryMetadata>,
+    },
+
+    /// Overwrite a draft with its latest contents (e.g. on autosave)
+    UpdateDraft {
+        secret_phrase: String,
+        draft_id: u64,
+        title: String,
+        content: String,
+        metadata: Option<EntryMetadata>,
+    },
+
+    /// Turn a draft into a regular entry
+    PublishDraft { secret_phrase: String, draft_id: u64 },
+
+    /// Throw a draft away
+    DiscardDraft { secret_phrase: String, draft_id: u64 },
+
+    /// Create a template for a recurring entry format
+    CreateTemplate {
+        secret_phrase: String,
+        name: String,
+        title_pattern: String,
+        content_skeleton: String,
+    },
+
+    /// Update a template
+    UpdateTemplate {
+        secret_phrase: String,
+        template_id: u64,
+        name: Option<String>,
+        title_pattern: Option<String>,
+        content_skeleton: Option<String>,
+    },
+
+    /// Delete a template
+    DeleteTemplate { secret_phrase: String, template_id: u64 },
+
+    /// Add an entry rendered from a template, filling placeholders with `values`
+    /// and with the built-in date placeholders
+    AddEntryFromTemplate {
+        secret_phrase: String,
+        template_id: u64,
+        values: Vec<TemplateValue>,
+        notebook_id: Option<u64>,
+    },
+
+    /// Link one entry to another, in addition to `[[entry:42]]` links in the content
+    LinkEntries {
+        secret_phrase: String,
+        from_entry_id: u64,
+        to_entry_id: u64,
+    },
+
+    /// Remove an explicit link between two entries
+    UnlinkEntries {
+        secret_phrase: String,
+        from_entry_id: u64,
+        to_entry_id: u64,
+    },
+
+    /// Pin an entry, at the end of the pinned list or at `position`.
+    /// Pinning an already pinned entry moves it.
+    PinEntry {
+        secret_phrase: String,
+        entry_id: u64,
+        position: Option<u32>,
+    },
+
+    /// Unpin an entry
+    UnpinEntry { secret_phrase: String, entry_id: u64 },
+
+    /// Mark an entry as a favorite
+    Favorite { secret_phrase: String, entry_id: u64 },
+
+    /// Remove an entry from the favorites
+    Unfavorite { secret_phrase: String, entry_id: u64 },
+
+    /// Set the phrase readers give to see `Members` entries, or remove it to let
+    /// anyone see them
+    SetMembersPhrase {
+        secret_phrase: String,
+        members_phrase: Option<String>,
+    },
+
+    /// Copy the diary to a successor application on the same chain, see [`upgrade`].
+    /// Each operation sends the next batch; the diary is read-only from the first one on.
+    ExportToSuccessor {
+        secret_phrase: String,
+        successor: ApplicationId,
+    },
+
+    /// Accept the export of `predecessor` into this diary, before it is initialized.
+    /// Only the signer that created the application can register it.
+    RegisterPredecessor { predecessor: ApplicationId },
+
+    /// One batch of `ExportToSuccessor` as a BCS-encoded [`upgrade::ExportBatch`].
+    /// Only accepted from the registered predecessor, through an application call.
+    ImportFromPredecessor { batch: Vec<u8> },
+
+    /// Append text to the end of an entry's content. Private entries are encrypted whole,
+    /// so their content is only replaced with `UpdateEntry`.
+    AppendToEntry {
+        secret_phrase: String,
+        entry_id: u64,
+        text: String,
+    },
+
+    /// Replace `len` bytes of an entry's content starting at byte `offset` with `text`.
+    /// Both ends of the range must fall on character boundaries of the UTF-8 content.
+    ReplaceRange {
+        secret_phrase: String,
+        entry_id: u64,
+        offset: u64,
+        len: u64,
+        text: String,
+    },
+
+    /// Apply edits made on revision `base_revision` of an entry's content. When the entry
+    /// changed since, the edits are merged if they don't touch what changed, see [`patch`].
+    PatchEntry {
+        secret_phrase: String,
+        entry_id: u64,
+        base_revision: u64,
+        patch: Vec<ContentEdit>,
+    },
+
+    /// Offer the diary to `new_owner`, who has `OWNER_TRANSFER_EXPIRY_MICROS` to accept it.
+    /// A new proposal replaces a pending one.
+    ProposeOwnerTransfer {
+        secret_phrase: String,
+        new_owner: AccountOwner,
+    },
+
+    /// Take over the diary proposed to the signer of the block. On another chain than the
+    /// diary's, the acceptance is forwarded to the diary's chain with the signer.
+    AcceptOwnership,
+
+    /// Withdraw a pending owner transfer
+    CancelOwnerTransfer { secret_phrase: String },
+
+    /// Make an archived notebook writable again
+    UnarchiveNotebook { secret_phrase: String, notebook_id: u64 },
+}
+
+/// Messages sent to the chain the diary was created on
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Message {
+    /// Take over the diary proposed to the authenticated signer of the message
+    AcceptOwnership,
+}
+
+/// A value for a `{{key}}` placeholder in a template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, async_graphql::InputObject)]
+pub struct TemplateValue {
+    pub key: String,
+    pub value: String,
+}
+
+/// Largest file that can be attached to an entry (10 MiB)
+pub const MAX_ATTACHMENT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Maximum number of attachments per entry
+pub const MAX_ATTACHMENTS_PER_ENTRY: usize = 10;
+
+/// MIME types accepted for attachments: photos, audio notes and PDFs
+pub const ALLOWED_ATTACHMENT_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "audio/mpeg",
+    "audio/mp4",
+    "audio/ogg",
+    "audio/wav",
+    "audio/webm",
+    "application/pdf",
+];
+
+/// Check an attachment's MIME type and filename
+pub fn validate_attachment(mime_type: &str, filename: &str) -> Result<(), String> {
+    if !ALLOWED_ATTACHMENT_TYPES.contains(&mime_type) {
+        return Err(format!("Attachment type '{}' is not allowed", mime_type));
+    }
+    if filename.trim().is_empty() || filename.len() > 255 {
+        return Err("Filename must be between 1 and 255 characters".to_string());
+    }
+    if filename.contains(['/', '\\']) {
+        return Err("Filename cannot contain path separators".to_string());
+    }
+    Ok(())
+}
+
+/// Who can read an entry
+///
+/// Visibilities are ordered from the most to the least restricted, so a reader
+/// allowed to see one visibility also sees every later one.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    async_graphql::Enum,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Visibility {
+    /// Only readers with the diary's secret phrase: title and content must be
+    /// encrypted by the client
+    Private,
+    /// Readers with the members phrase or the diary's secret phrase, or anyone
+    /// while the diary has no members phrase
+    #[default]
+    Members,
+    /// Anyone, and listed on the public read API
+    Public,
+}
+
+/// Prefix of text encrypted by the client, followed by base64 data
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// Bytes of an encrypted envelope besides the ciphertext: a 12-byte AES-GCM nonce
+/// and a 16-byte authentication tag
+pub const ENCRYPTION_OVERHEAD: usize = 12 + 16;
+
+/// Whether a text is an encrypted envelope: the prefix followed by padded standard
+/// base64 of at least a nonce and an authentication tag
+pub fn is_encrypted(text: &str) -> bool {
+    let Some(data) = text.strip_prefix(ENCRYPTED_PREFIX) else {
+        return false;
+    };
+    let padding = data.len() - data.trim_end_matches('=').len();
+    let digits = &data[..data.len() - padding];
+    data.len() % 4 == 0
+        && padding <= 2
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/'))
+        && data.len() / 4 * 3 - padding >= ENCRYPTION_OVERHEAD
+}
+
+/// Check that private entries only hold encrypted text
+pub fn validate_visibility(visibility: Visibility, title: &str, content: &str) -> Result<(), String> {
+    if visibility == Visibility::Private && !(is_encrypted(title) && is_encrypted(content)) {
+        return Err("Private entries must have an encrypted title and content".to_string());
+    }
+    Ok(())
+}
+
+/// How the author felt when writing an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, async_graphql::Enum)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Mood {
+    Great,
+    Good,
+    Okay,
+    Bad,
+    Awful,
+}
+
+/// Self-reported energy level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, async_graphql::Enum)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EnergyLevel {
+    High,
+    Medium,
+    Low,
+}
+
+/// Declared type of a custom metadata field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, async_graphql::Enum)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FieldType {
+    Text,
+    Number,
+    Boolean,
+    /// A calendar date formatted as `YYYY-MM-DD`
+    Date,
+}
+
+/// A typed key/value pair attached to an entry
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    async_graphql::SimpleObject,
+    async_graphql::InputObject,
+)]
+#[graphql(input_name = "CustomFieldInput")]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    pub key: String,
+    pub field_type: FieldType,
+    /// The value, encoded as a string that must parse as `field_type`
+    pub value: String,
+}
+
+impl CustomField {
+    /// The value as a number, for `Number` fields
+    pub fn as_number(&self) -> Option<f64> {
+        match self.field_type {
+            FieldType::Number => self.value.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Structured metadata attached to an entry
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    async_graphql::SimpleObject,
+    async_graphql::InputObject,
+)]
+#[graphql(input_name = "EntryMetadataInput")]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMetadata {
+    pub mood: Option<Mood>,
+    /// Rating from 1 to 5
+    pub rating: Option<u8>,
+    pub energy: Option<EnergyLevel>,
+    pub custom_fields: Vec<CustomField>,
+}
+
+impl EntryMetadata {
+    /// Check the rating range, field keys and that every custom value matches its declared type
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(rating) = self.rating {
+            if !(1..=5).contains(&rating) {
+                return Err("Rating must be between 1 and 5".to_string());
+            }
+        }
+
+        let mut keys = Vec::new();
+        for field in &self.custom_fields {
+            if field.key.trim().is_empty() {
+                return Err("Custom field key cannot be empty".to_string());
+            }
+            if keys.contains(&&field.key) {
+                return Err(format!("Duplicate custom field '{}'", field.key));
+            }
+            keys.push(&field.key);
+
+            let valid = match field.field_type {
+                FieldType::Text => true,
+                FieldType::Number => field.value.parse::<f64>().is_ok_and(f64::is_finite),
+                FieldType::Boolean => field.value == "true" || field.value == "false",
+                FieldType::Date => calendar::parse_date(&field.value).is_some(),
+            };
+            if !valid {
+                return Err(format!(
+                    "Custom field '{}' is not a valid {:?}",
+                    field.key, field.field_type
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look up a custom field by key
+    pub fn custom_field(&self, key: &str) -> Option<&CustomField> {
+        self.custom_fields.iter().find(|f| f.key == key)
+    }
+}
+
+/// A single diary entry. Serde names follow the GraphQL schema, so clients can decode
+/// query results into this type; the on-chain BCS encoding does not use field names.
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[graphql(complex)]
+#[serde(rename_all = "camelCase")]
+pub struct DiaryEntry {
+    /// Entry ID
+    pub id: u64,
+
+    /// Entry title
+    pub title: String,
+
+    /// Entry content
+    pub content: String,
+
+    /// Timestamp of the last edit (microseconds)
+    pub timestamp: u64,
+
+    /// Creation timestamp (microseconds), unchanged by edits
+    pub created_at: u64,
+
+    /// Notebook this entry belongs to
+    pub notebook_id: u64,
+
+    /// Mood, rating, energy and custom fields
+    pub metadata: EntryMetadata,
+
+    /// Files attached to the entry
+    pub attachments: Vec<Attachment>,
+
+    /// Who can read the entry
+    pub visibility: Visibility,
+
+    /// Number of changes made to the entry since it was created
+    pub revision: u64,
+}
+
+#[async_graphql::ComplexObject]
+impl DiaryEntry {
+    /// `#hashtag` tags used in the content
+    async fn tags(&self) -> Vec<String> {
+        text::extract_tags(&self.content)
+    }
+
+    /// Number of words in the content
+    async fn word_count(&self) -> u64 {
+        text::word_count(&self.content)
+    }
+}
+
+impl DiaryEntry {
+    /// Reassemble an entry from its header and content
+    pub fn from_parts(header: EntryHeader, content: String) -> Self {
+        DiaryEntry {
+            id: header.id,
+            title: header.title,
+            content,
+            timestamp: header.timestamp,
+            created_at: header.created_at,
+            notebook_id: header.notebook_id,
+            metadata: header.metadata,
+            attachments: header.attachments,
+            visibility: header.visibility,
+            revision: header.revision,
+        }
+    }
+
+    /// Check that the entry is still at `expected` revision, if one is given
+    pub fn check_revision(&self, expected: Option<u64>) -> Result<(), RevisionConflict> {
+        match expected {
+            Some(expected) if expected != self.revision => Err(RevisionConflict {
+                entry_id: self.id,
+                expected,
+                actual: self.revision,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An entry changed since the revision a client read, so its update or deletion would
+/// overwrite changes the client has not seen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[serde(rename_all = "camelCase")]
+#[error("Entry {entry_id} is at revision {actual}, not the expected revision {expected}")]
+pub struct RevisionConflict {
+    pub entry_id: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl RevisionConflict {
+    /// The `code` extension of the GraphQL error reporting a conflict
+    pub const CODE: &'static str = "REVISION_CONFLICT";
+}
+
+impl async_graphql::ErrorExtensions for RevisionConflict {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", Self::CODE);
+            extensions.set("entryId", self.entry_id);
+            extensions.set("expected", self.expected);
+            extensions.set("actual", self.actual);
+        })
+    }
+}
+
+/// Characters of content kept in the excerpt of an entry header
+pub const EXCERPT_LENGTH: usize = 150;
+
+/// Everything about an entry except its content, stored apart from it so that list
+/// queries don't read or return the full text. `entry(id)` returns the whole entry.
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryHeader {
+    /// Entry ID
+    pub id: u64,
+
+    /// Entry title
+    pub title: String,
+
+    /// Timestamp of the last edit (microseconds)
+    pub timestamp: u64,
+
+    /// Creation timestamp (microseconds), unchanged by edits
+    pub created_at: u64,
+
+    /// Notebook this entry belongs to
+    pub notebook_id: u64,
+
+    /// `#hashtag` tags used in the content
+    pub tags: Vec<String>,
+
+    /// The start of the content, at most 150 characters; empty for encrypted content
+    pub excerpt: String,
+
+    /// Number of words in the content
+    pub word_count: u64,
+
+    /// Mood, rating, energy and custom fields
+    pub metadata: EntryMetadata,
+
+    /// Files attached to the entry
+    pub attachments: Vec<Attachment>,
+
+    /// Who can read the entry
+    pub visibility: Visibility,
+
+    /// Number of changes made to the entry since it was created
+    pub revision: u64,
+}
+
+impl From<&DiaryEntry> for EntryHeader {
+    fn from(entry: &DiaryEntry) -> Self {
+        EntryHeader {
+            id: entry.id,
+            title: entry.title.clone(),
+            timestamp: entry.timestamp,
+            created_at: entry.created_at,
+            notebook_id: entry.notebook_id,
+            tags: text::extract_tags(&entry.content),
+            excerpt: content_excerpt(&entry.content),
+            word_count: text::word_count(&entry.content),
+            metadata: entry.metadata.clone(),
+            attachments: entry.attachments.clone(),
+            visibility: entry.visibility,
+            revision: entry.revision,
+        }
+    }
+}
+
+/// The excerpt an entry header keeps of `content`; empty for encrypted content
+pub fn content_excerpt(content: &str) -> String {
+    if is_encrypted(content) {
+        String::new()
+    } else {
+        text::excerpt(content, EXCERPT_LENGTH)
+    }
+}
+
+/// A reference to a file published as a data blob
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    /// Hash of the data blob holding the file
+    pub blob_hash: DataBlobHash,
+
+    /// MIME type of the file
+    pub mime_type: String,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Original file name
+    pub filename: String,
+
+    /// Timestamp (microseconds) at which the file was attached
+    pub attached_at: u64,
+}
+
+/// A named journal grouping entries within the diary
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub struct Notebook {
+    /// Notebook ID
+    pub id: u64,
+
+    /// Notebook name
+    pub name: String,
+
+    /// Archived notebooks are read-only
+    pub archived: bool,
+
+    /// SHA-256 hash of the notebook's own secret phrase, if any
+    #[graphql(skip)]
+    pub secret_phrase_hash: Option<String>,
+
+    /// Creation timestamp (microseconds)
+    pub created_at: u64,
+}
+
+#[async_graphql::ComplexObject]
+impl Notebook {
+    /// Whether the notebook is protected by its own secret phrase
+    async fn is_protected(&self) -> bool {
+        self.secret_phrase_hash.is_some()
+    }
+}
+
+/// How long a proposed owner transfer can be accepted, in microseconds
+pub const OWNER_TRANSFER_EXPIRY_MICROS: u64 = 7 * 24 * 60 * 60 * 1_000_000;
+
+/// An owner transfer waiting for the new owner to accept it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerTransfer {
+    /// The owner the diary is offered to
+    pub new_owner: String,
+
+    /// Timestamp (microseconds) of the proposal
+    pub proposed_at: u64,
+
+    /// Timestamp (microseconds) after which the proposal can no longer be accepted
+    pub expires_at: u64,
+}
+
+impl OwnerTransfer {
+    /// Whether the proposal can still be accepted at `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at
+    }
+}
+
+/// A step of an owner transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, async_graphql::Enum)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OwnershipAction {
+    /// The owner offered the diary to `new_owner`
+    Proposed,
+    /// The owner withdrew the offer
+    Cancelled,
+    /// `new_owner` accepted the offer and now owns the diary
+    Accepted,
+}
+
+/// An entry of the ownership audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnershipEvent {
+    /// What happened
+    pub action: OwnershipAction,
+
+    /// The owner before the event
+    pub owner: String,
+
+    /// The owner the diary was offered to
+    pub new_owner: String,
+
+    /// Timestamp (microseconds) of the event
+    pub timestamp: u64,
+}
+
+impl Notebook {
+    /// Check a secret phrase against the notebook's own secret,
+    /// falling back to the diary secret for unprotected notebooks
+    pub fn accepts_secret(&self, secret_phrase: &str, diary_secret_hash: &str) -> bool {
+        let provided_hash = hash_secret_phrase(secret_phrase);
+        match &self.secret_phrase_hash {
+            Some(hash) => &provided_hash == hash,
+            None => provided_hash == diary_secret_hash,
+        }
+    }
+}
+
+/// A draft that becomes an entry once published
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+pub struct Draft {
+    /// Draft ID (independent of entry IDs)
+    pub id: u64,
+
+    /// Draft title, may be empty while writing
+    pub title: String,
+
+    /// Draft content, may be empty while writing
+    pub content: String,
+
+    /// Notebook the entry will be published to
+    pub notebook_id: u64,
+
+    /// Metadata the entry will be published with
+    pub metadata: EntryMetadata,
+
+    /// Creation timestamp (microseconds)
+    pub created_at: u64,
+
+    /// Timestamp (microseconds) of the last save
+    pub updated_at: u64,
+}
+
+/// A reusable structure for recurring entries, such as a daily standup
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub struct Template {
+    /// Template ID
+    pub id: u64,
+
+    /// Template name
+    pub name: String,
+
+    /// Pattern for the entry title, e.g. `Standup {{date}}`
+    pub title_pattern: String,
+
+    /// Skeleton for the entry content with `{{placeholder}}` fields
+    pub content_skeleton: String,
+
+    /// Creation timestamp (microseconds)
+    pub created_at: u64,
+}
+
+#[async_graphql::ComplexObject]
+impl Template {
+    /// Placeholders used in the title pattern and content skeleton
+    async fn placeholders(&self) -> Vec<String> {
+        let mut names = template::placeholders(&self.title_pattern).unwrap_or_default();
+        for name in template::placeholders(&self.content_skeleton).unwrap_or_default() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
+
+/// Application parameters, given with `--json-parameters` when the application is
+/// created. `null` keeps the default query limits.
+pub type DiaryParameters = Option<QueryLimits>;
+
+/// Limits on the work a single GraphQL query can make the service do
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, async_graphql::SimpleObject)]
+#[serde(default, rename_all = "camelCase")]
+pub struct QueryLimits {
+    /// Deepest nesting of fields
+    pub max_depth: u32,
+    /// Highest total complexity. Every field counts 1, and every field reading
+    /// all entries or another whole collection 50 more, once per alias
+    pub max_complexity: u32,
+    /// Most items a list field may return
+    pub max_results: u32,
+}
+
+/// Complexity added by each field that reads every entry or another whole collection
+pub const SCAN_COMPLEXITY: usize = 50;
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_depth: 16,
+            max_complexity: 1000,
+            max_results: 500,
+        }
+    }
+}
+
+impl QueryLimits {
+    /// Check that every limit lets at least the simplest queries through
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_depth == 0 || self.max_complexity == 0 || self.max_results == 0 {
+            return Err("Query limits must be positive".to_string());
+        }
+        if (self.max_complexity as usize) <= SCAN_COMPLEXITY {
+            return Err(format!(
+                "Maximum complexity must be above {}",
+                SCAN_COMPLEXITY
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// A simple response type returned by GraphQL mutations to indicate success or error.
@@ -0,0 +1,177 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Patches of entry content: edits to byte ranges of a known revision of an entry, and
+//! rebasing them over the revisions made since.
+//!
+//! Each revision of an entry records the ranges it replaced as [`EditSpan`]s. A patch made
+//! on an earlier revision still applies when none of its edits touches a range replaced
+//! since: its offsets are shifted by what was inserted or removed before them. Edits that
+//! overlap or touch a changed range conflict, as does a base revision older than the
+//! recorded history.
+
+use serde::{Deserialize, Serialize};
+
+/// Number of revisions of an entry whose changed ranges are kept for rebasing patches
+pub const MAX_REBASE_REVISIONS: usize = 32;
+
+/// Replace `len` bytes of the content at byte `offset` with `text`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, async_graphql::InputObject)]
+#[graphql(input_name = "ContentEditInput")]
+pub struct ContentEdit {
+    pub offset: u64,
+    pub len: u64,
+    pub text: String,
+}
+
+impl ContentEdit {
+    /// Byte offset of the end of the replaced range
+    pub fn end(&self) -> u64 {
+        self.offset.saturating_add(self.len)
+    }
+}
+
+/// A range replaced by a revision, and the length of the text that replaced it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditSpan {
+    pub offset: u64,
+    pub len: u64,
+    pub new_len: u64,
+}
+
+/// The ranges one revision of an entry replaced, in the content of the revision before
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevisionEdits {
+    pub revision: u64,
+    pub spans: Vec<EditSpan>,
+}
+
+/// Check that the edits of a patch are in order, do not overlap and lie within a content
+/// of `content_len` bytes. Character boundaries are checked where the content is read.
+pub fn validate(patch: &[ContentEdit], content_len: usize) -> Result<(), String> {
+    if patch.is_empty() {
+        return Err("Patch has no edits".to_string());
+    }
+
+    let mut previous_end = 0;
+    for edit in patch {
+        if edit.len == 0 && edit.text.is_empty() {
+            return Err(format!("Edit at {} changes nothing", edit.offset));
+        }
+        if edit.offset < previous_end {
+            return Err(format!(
+                "Edit at {} overlaps or comes before the previous edit",
+                edit.offset
+            ));
+        }
+        if edit.end() > content_len as u64 {
+            return Err(format!(
+                "Range of {} bytes at {} is outside the {} bytes of the content",
+                edit.len, edit.offset, content_len
+            ));
+        }
+        previous_end = edit.end();
+    }
+
+    Ok(())
+}
+
+/// Apply a patch checked with [`validate`] to `content`, whose ranges start and end on
+/// character boundaries
+pub fn apply(content: &str, patch: &[ContentEdit]) -> String {
+    let mut patched = content.to_string();
+    // From the end, so that the offsets of the edits before stay valid
+    for edit in patch.iter().rev() {
+        patched.replace_range(edit.offset as usize..edit.end() as usize, &edit.text);
+    }
+    patched
+}
+
+/// The ranges a patch replaces, as recorded for its revision
+pub fn spans(patch: &[ContentEdit]) -> Vec<EditSpan> {
+    patch
+        .iter()
+        .map(|edit| EditSpan {
+            offset: edit.offset,
+            len: edit.len,
+            new_len: edit.text.len() as u64,
+        })
+        .collect()
+}
+
+/// Move a patch made on `base_revision` onto `current_revision`, through the revisions
+/// recorded in `history` since
+pub fn rebase(
+    mut patch: Vec<ContentEdit>,
+    base_revision: u64,
+    current_revision: u64,
+    history: &[RevisionEdits],
+) -> Result<Vec<ContentEdit>, String> {
+    if base_revision > current_revision {
+        return Err(format!(
+            "Revision {} does not exist yet; the entry is at revision {}",
+            base_revision, current_revision
+        ));
+    }
+
+    for revision in base_revision + 1..=current_revision {
+        let Some(edits) = history.iter().find(|edits| edits.revision == revision) else {
+            return Err(format!(
+                "Revision {} is too old to merge into revision {}",
+                base_revision, current_revision
+            ));
+        };
+        for edit in &mut patch {
+            edit.offset = rebase_offset(edit, &edits.spans).ok_or_else(|| {
+                format!(
+                    "Edit at {} conflicts with revision {}",
+                    edit.offset, edits.revision
+                )
+            })?;
+        }
+    }
+
+    Ok(patch)
+}
+
+/// The offset of `edit` once `spans` are applied, or `None` if it touches one of them
+fn rebase_offset(edit: &ContentEdit, spans: &[EditSpan]) -> Option<u64> {
+    let mut offset = edit.offset;
+    for span in spans {
+        let span_end = span.offset + span.len;
+        if span_end < edit.offset {
+            offset = (offset + span.new_len).checked_sub(span.len)?;
+        } else if span.offset <= edit.end() {
+            return None;
+        }
+    }
+    Some(offset)
+}
+
+/// A patch turning `old` into `new`: a single edit covering everything between
+/// their common prefix and common suffix, or no edit if they are equal
+pub fn diff(old: &str, new: &str) -> Vec<ContentEdit> {
+    if old == new {
+        return Vec::new();
+    }
+
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((index, _), _)| index);
+
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+
+    vec![ContentEdit {
+        offset: prefix as u64,
+        len: (old.len() - prefix - suffix) as u64,
+        text: new[prefix..new.len() - suffix].to_string(),
+    }]
+}
@@ -0,0 +1,401 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Export of the service's GraphQL schema as SDL, and detection of breaking
+//! changes between two versions of it.
+//!
+//! The SDL is rebuilt from an introspection result, so it can be taken from
+//! any running service. Types are sorted by name and fields keep their
+//! declaration order, so the output only changes when the schema does.
+
+use std::collections::BTreeMap;
+
+use async_graphql::{
+    parser::{
+        parse_schema,
+        types::{
+            BaseType, FieldDefinition, InputValueDefinition, Type, TypeDefinition, TypeKind,
+            TypeSystemDefinition,
+        },
+    },
+    Positioned,
+};
+use serde_json::Value;
+
+/// Introspection query with everything [`sdl_from_introspection`] needs
+pub const INTROSPECTION_QUERY: &str = r#"
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types {
+      kind
+      name
+      description
+      fields(includeDeprecated: true) {
+        name
+        description
+        args { ...InputValue }
+        type { ...TypeRef }
+        isDeprecated
+        deprecationReason
+      }
+      inputFields { ...InputValue }
+      interfaces { name }
+      enumValues(includeDeprecated: true) {
+        name
+        description
+        isDeprecated
+        deprecationReason
+      }
+      possibleTypes { name }
+    }
+  }
+}
+
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType { kind name ofType { kind name ofType { kind name ofType { kind name } } } }
+}
+"#;
+
+/// Scalars every GraphQL schema has, left out of the SDL
+const BUILTIN_SCALARS: &[&str] = &["Boolean", "Float", "ID", "Int", "String"];
+
+/// Render the result of [`INTROSPECTION_QUERY`] as SDL.
+/// Accepts either the `data` object or the whole response.
+pub fn sdl_from_introspection(introspection: &Value) -> Result<String, String> {
+    let schema = introspection
+        .get("__schema")
+        .or_else(|| introspection.pointer("/data/__schema"))
+        .ok_or_else(|| "Introspection result has no __schema".to_string())?;
+
+    let mut sdl = String::new();
+
+    let root = |key: &str| schema[key]["name"].as_str().map(str::to_string);
+    let roots = [
+        ("query", root("queryType")),
+        ("mutation", root("mutationType")),
+        ("subscription", root("subscriptionType")),
+    ];
+    sdl.push_str("schema {\n");
+    for (operation, name) in &roots {
+        if let Some(name) = name {
+            sdl.push_str(&format!("  {}: {}\n", operation, name));
+        }
+    }
+    sdl.push_str("}\n");
+
+    let mut types: Vec<&Value> = schema["types"]
+        .as_array()
+        .ok_or_else(|| "Introspection result has no types".to_string())?
+        .iter()
+        .filter(|t| {
+            let name = t["name"].as_str().unwrap_or_default();
+            !name.starts_with("__") && !BUILTIN_SCALARS.contains(&name)
+        })
+        .collect();
+    types.sort_by_key(|t| t["name"].as_str().unwrap_or_default());
+
+    for t in types {
+        let name = t["name"].as_str().unwrap_or_default();
+        sdl.push('\n');
+        // Scalars come from dependencies, so their descriptions are not part of our API
+        if t["kind"] != "SCALAR" {
+            push_description(&mut sdl, "", &t["description"]);
+        }
+
+        match t["kind"].as_str().unwrap_or_default() {
+            "SCALAR" => sdl.push_str(&format!("scalar {}\n", name)),
+            "OBJECT" | "INTERFACE" => {
+                let keyword = if t["kind"] == "OBJECT" {
+                    "type"
+                } else {
+                    "interface"
+                };
+                sdl.push_str(&format!("{} {}", keyword, name));
+                let interfaces = names(&t["interfaces"]);
+                if !interfaces.is_empty() {
+                    sdl.push_str(&format!(" implements {}", interfaces.join(" & ")));
+                }
+                sdl.push_str(" {\n");
+                for field in t["fields"].as_array().into_iter().flatten() {
+                    push_description(&mut sdl, "  ", &field["description"]);
+                    sdl.push_str(&format!("  {}", field["name"].as_str().unwrap_or_default()));
+                    let args: Vec<String> = field["args"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(input_value)
+                        .collect();
+                    if !args.is_empty() {
+                        sdl.push_str(&format!("({})", args.join(", ")));
+                    }
+                    sdl.push_str(&format!(": {}", type_ref(&field["type"])));
+                    push_deprecation(&mut sdl, field);
+                    sdl.push('\n');
+                }
+                sdl.push_str("}\n");
+            }
+            "UNION" => {
+                sdl.push_str(&format!(
+                    "union {} = {}\n",
+                    name,
+                    names(&t["possibleTypes"]).join(" | ")
+                ));
+            }
+            "ENUM" => {
+                sdl.push_str(&format!("enum {} {{\n", name));
+                for value in t["enumValues"].as_array().into_iter().flatten() {
+                    push_description(&mut sdl, "  ", &value["description"]);
+                    sdl.push_str(&format!("  {}", value["name"].as_str().unwrap_or_default()));
+                    push_deprecation(&mut sdl, value);
+                    sdl.push('\n');
+                }
+                sdl.push_str("}\n");
+            }
+            "INPUT_OBJECT" => {
+                sdl.push_str(&format!("input {} {{\n", name));
+                for field in t["inputFields"].as_array().into_iter().flatten() {
+                    push_description(&mut sdl, "  ", &field["description"]);
+                    sdl.push_str(&format!("  {}\n", input_value(field)));
+                }
+                sdl.push_str("}\n");
+            }
+            kind => return Err(format!("Unknown kind {} of type {}", kind, name)),
+        }
+    }
+
+    Ok(sdl)
+}
+
+/// Changes from `old` to `new` that can break existing clients:
+/// removed types, fields, arguments and enum values, incompatible type changes,
+/// and new required arguments or input fields
+pub fn breaking_changes(old: &str, new: &str) -> Result<Vec<String>, String> {
+    let old_types = type_definitions(old)?;
+    let new_types = type_definitions(new)?;
+    let mut changes = Vec::new();
+
+    for (name, old_type) in &old_types {
+        let Some(new_type) = new_types.get(name) else {
+            changes.push(format!("Type {} was removed", name));
+            continue;
+        };
+
+        match (&old_type.kind, &new_type.kind) {
+            (TypeKind::Scalar, TypeKind::Scalar) => {}
+            (TypeKind::Object(old_object), TypeKind::Object(new_object)) => {
+                compare_fields(name, &old_object.fields, &new_object.fields, &mut changes);
+            }
+            (TypeKind::Interface(old_interface), TypeKind::Interface(new_interface)) => {
+                compare_fields(
+                    name,
+                    &old_interface.fields,
+                    &new_interface.fields,
+                    &mut changes,
+                );
+            }
+            (TypeKind::Union(old_union), TypeKind::Union(new_union)) => {
+                for member in &old_union.members {
+                    if !new_union.members.iter().any(|m| m.node == member.node) {
+                        changes.push(format!("{} no longer includes {}", name, member.node));
+                    }
+                }
+            }
+            (TypeKind::Enum(old_enum), TypeKind::Enum(new_enum)) => {
+                for value in &old_enum.values {
+                    let value = &value.node.value.node;
+                    if !new_enum.values.iter().any(|v| &v.node.value.node == value) {
+                        changes.push(format!("{}.{} was removed", name, value));
+                    }
+                }
+            }
+            (TypeKind::InputObject(old_input), TypeKind::InputObject(new_input)) => {
+                compare_inputs(name, &old_input.fields, &new_input.fields, &mut changes);
+            }
+            _ => changes.push(format!("Type {} changed kind", name)),
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Type definitions of an SDL document by name
+fn type_definitions(sdl: &str) -> Result<BTreeMap<String, TypeDefinition>, String> {
+    let document = parse_schema(sdl).map_err(|e| format!("Invalid SDL: {}", e))?;
+    Ok(document
+        .definitions
+        .into_iter()
+        .filter_map(|definition| match definition {
+            TypeSystemDefinition::Type(t) => Some((t.node.name.node.to_string(), t.node)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Compare the fields of an object or interface
+fn compare_fields(
+    type_name: &str,
+    old_fields: &[Positioned<FieldDefinition>],
+    new_fields: &[Positioned<FieldDefinition>],
+    changes: &mut Vec<String>,
+) {
+    for old_field in old_fields {
+        let old_field = &old_field.node;
+        let path = format!("{}.{}", type_name, old_field.name.node);
+        let Some(new_field) = new_fields
+            .iter()
+            .map(|f| &f.node)
+            .find(|f| f.name.node == old_field.name.node)
+        else {
+            changes.push(format!("{} was removed", path));
+            continue;
+        };
+
+        if !output_compatible(&old_field.ty.node, &new_field.ty.node) {
+            changes.push(format!(
+                "{} changed type from {} to {}",
+                path, old_field.ty.node, new_field.ty.node
+            ));
+        }
+        compare_inputs(&path, &old_field.arguments, &new_field.arguments, changes);
+    }
+}
+
+/// Compare the arguments of a field or the fields of an input object
+fn compare_inputs(
+    path: &str,
+    old_inputs: &[Positioned<InputValueDefinition>],
+    new_inputs: &[Positioned<InputValueDefinition>],
+    changes: &mut Vec<String>,
+) {
+    for old_input in old_inputs {
+        let old_input = &old_input.node;
+        let Some(new_input) = new_inputs
+            .iter()
+            .map(|i| &i.node)
+            .find(|i| i.name.node == old_input.name.node)
+        else {
+            changes.push(format!("{}({}) was removed", path, old_input.name.node));
+            continue;
+        };
+
+        if !input_compatible(&old_input.ty.node, &new_input.ty.node) {
+            changes.push(format!(
+                "{}({}) changed type from {} to {}",
+                path, old_input.name.node, old_input.ty.node, new_input.ty.node
+            ));
+        }
+    }
+
+    for new_input in new_inputs {
+        let new_input = &new_input.node;
+        let existed = old_inputs
+            .iter()
+            .any(|i| i.node.name.node == new_input.name.node);
+        if !existed && !new_input.ty.node.nullable && new_input.default_value.is_none() {
+            changes.push(format!(
+                "{}({}) is new and required",
+                path, new_input.name.node
+            ));
+        }
+    }
+}
+
+/// Whether clients reading `old` can read `new`: the same type, or one that is
+/// no longer nullable
+fn output_compatible(old: &Type, new: &Type) -> bool {
+    if !old.nullable && new.nullable {
+        return false;
+    }
+    match (&old.base, &new.base) {
+        (BaseType::Named(old), BaseType::Named(new)) => old == new,
+        (BaseType::List(old), BaseType::List(new)) => output_compatible(old, new),
+        _ => false,
+    }
+}
+
+/// Whether clients sending `old` can keep sending it as `new`: the same type,
+/// or one that became nullable
+fn input_compatible(old: &Type, new: &Type) -> bool {
+    if old.nullable && !new.nullable {
+        return false;
+    }
+    match (&old.base, &new.base) {
+        (BaseType::Named(old), BaseType::Named(new)) => old == new,
+        (BaseType::List(old), BaseType::List(new)) => input_compatible(old, new),
+        _ => false,
+    }
+}
+
+/// `name: Type = default` for an argument or input field
+fn input_value(value: &Value) -> String {
+    let mut sdl = format!(
+        "{}: {}",
+        value["name"].as_str().unwrap_or_default(),
+        type_ref(&value["type"])
+    );
+    if let Some(default) = value["defaultValue"].as_str() {
+        sdl.push_str(&format!(" = {}", default));
+    }
+    sdl
+}
+
+/// Render a type reference such as `[DiaryEntry!]!`
+fn type_ref(value: &Value) -> String {
+    match value["kind"].as_str() {
+        Some("NON_NULL") => format!("{}!", type_ref(&value["ofType"])),
+        Some("LIST") => format!("[{}]", type_ref(&value["ofType"])),
+        _ => value["name"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// Names of a list of named types
+fn names(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t["name"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// Append a `"""` block description, if there is one
+fn push_description(sdl: &mut String, indent: &str, description: &Value) {
+    let Some(description) = description.as_str().filter(|d| !d.trim().is_empty()) else {
+        return;
+    };
+    sdl.push_str(&format!("{}\"\"\"\n", indent));
+    for line in description.trim().lines() {
+        if !line.trim().is_empty() {
+            sdl.push_str(indent);
+            sdl.push_str(&line.replace("\"\"\"", "\\\"\"\""));
+        }
+        sdl.push('\n');
+    }
+    sdl.push_str(&format!("{}\"\"\"\n", indent));
+}
+
+/// Append a `@deprecated` directive, if the field or value is deprecated
+fn push_deprecation(sdl: &mut String, value: &Value) {
+    if value["isDeprecated"] != true {
+        return;
+    }
+    match value["deprecationReason"].as_str() {
+        Some(reason) => sdl.push_str(&format!(
+            " @deprecated(reason: {})",
+            Value::String(reason.to_string())
+        )),
+        None => sdl.push_str(" @deprecated"),
+    }
+}
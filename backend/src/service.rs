@@ -6,21 +6,37 @@
 mod state;
 
 use std::sync::Arc;
-use async_graphql::{EmptySubscription, Object, Schema};
+use async_graphql::{Context, EmptySubscription, ErrorExtensions, Object, Schema};
 use linera_sdk::{
-    linera_base_types::WithServiceAbi,
+    linera_base_types::{AccountOwner, ApplicationId, DataBlobHash, WithServiceAbi},
     views::View,
     Service, ServiceRuntime,
 };
 
-use diary_backend::{hash_secret_phrase, Operation, OperationResponse};
-use self::state::{DiaryEntry, DiaryState};
+use diary_backend::{
+    calendar,
+    feed::{self, FeedInfo, FeedItem},
+    hash_secret_phrase,
+    patch::{self, ContentEdit},
+    template, text,
+    upgrade::ExportProgress,
+    validate_attachment, validate_visibility, DiaryParameters, EnergyLevel, EntryMetadata, Mood,
+    Operation, OperationResponse, QueryLimits, TemplateValue, Visibility, SCAN_COMPLEXITY,
+};
+use self::state::{
+    Attachment, DiaryEntry, DiaryState, Draft, EntryFilter, EntryHeader, EntryOrder,
+    MetadataFilter, Notebook, OwnerTransfer, OwnershipEvent, Template,
+};
+
+/// Number of entries in a feed when no limit is given
+const DEFAULT_FEED_LIMIT: usize = 50;
 
 /// The GraphQL service for the diary application.
 /// This provides read-only queries and schedules operations through mutations.
 pub struct DiaryService {
     state: Arc<DiaryState>,
     runtime: Arc<ServiceRuntime<Self>>,
+    schema: Schema<QueryRoot, MutationRoot, EmptySubscription>,
 }
 
 linera_sdk::service!(DiaryService);
@@ -30,148 +46,1082 @@ impl WithServiceAbi for DiaryService {
 }
 
 impl Service for DiaryService {
-    type Parameters = ();
+    type Parameters = DiaryParameters;
 
     async fn new(runtime: ServiceRuntime<Self>) -> Self {
         let state = DiaryState::load(runtime.root_view_storage_context())
             .await
             .expect("Failed to load state");
+        let limits = runtime.application_parameters().unwrap_or_default();
+        let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+            .limit_depth(limits.max_depth as usize)
+            .limit_complexity(limits.max_complexity as usize)
+            .data(limits)
+            .finish();
         DiaryService {
             state: Arc::new(state),
             runtime: Arc::new(runtime),
+            schema,
         }
     }
 
     async fn handle_query(&self, request: async_graphql::Request) -> async_graphql::Response {
-        let schema = Schema::build(
-            QueryRoot {
-                state: self.state.clone(),
-            },
-            MutationRoot {
-                runtime: self.runtime.clone(),
-            },
-            EmptySubscription,
-        )
-        .finish();
+        let request = request.data(self.state.clone()).data(self.runtime.clone());
+        self.schema.execute(request).await
+    }
+}
+
+/// The state attached to the request by `handle_query`
+fn state<'a>(ctx: &Context<'a>) -> &'a DiaryState {
+    ctx.data_unchecked::<Arc<DiaryState>>()
+}
+
+/// The runtime attached to the request by `handle_query`
+fn runtime<'a>(ctx: &Context<'a>) -> &'a ServiceRuntime<DiaryService> {
+    ctx.data_unchecked::<Arc<ServiceRuntime<DiaryService>>>()
+}
+
+/// The limits the schema was built with
+fn limits<'a>(ctx: &Context<'a>) -> &'a QueryLimits {
+    ctx.data_unchecked::<QueryLimits>()
+}
+
+/// Longest list a query may return, `QueryLimits::max_results`
+fn max_results(ctx: &Context<'_>) -> usize {
+    limits(ctx).max_results as usize
+}
 
-        schema.execute(request).await
+/// Reject a list longer than `QueryLimits::max_results`
+fn limit_results<T>(ctx: &Context<'_>, results: Vec<T>) -> Result<Vec<T>, async_graphql::Error> {
+    let max_results = max_results(ctx);
+    if results.len() > max_results {
+        return Err(async_graphql::Error::new(format!(
+            "Query returns more than the limit of {} results; narrow it down with a filter, notebook or limit",
+            max_results
+        )));
     }
+    Ok(results)
 }
 
-/// GraphQL query root for reading diary data.
-struct QueryRoot {
-    state: Arc<DiaryState>,
+/// Reject a `limit` argument above `QueryLimits::max_results`
+fn check_limit(ctx: &Context<'_>, limit: i32) -> Result<usize, async_graphql::Error> {
+    let max_results = limits(ctx).max_results;
+    if limit <= 0 {
+        return Err(async_graphql::Error::new("Limit must be positive"));
+    }
+    if limit as u32 > max_results {
+        return Err(async_graphql::Error::new(format!(
+            "Limit must be at most {}",
+            max_results
+        )));
+    }
+    Ok(limit as usize)
+}
+
+/// Check a patch against the content of the entry it applies to, so that invalid patches
+/// fail here rather than when the operation is executed
+fn check_patch(patch: &[ContentEdit], content: &str) -> Result<(), async_graphql::Error> {
+    patch::validate(patch, content.len()).map_err(async_graphql::Error::new)?;
+    for edit in patch {
+        if !content.is_char_boundary(edit.offset as usize)
+            || !content.is_char_boundary(edit.end() as usize)
+        {
+            return Err(async_graphql::Error::new(
+                "Range must start and end on character boundaries",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Reject an operation on an entry that is no longer at `expected_revision`, with a
+/// `REVISION_CONFLICT` error, or that does not exist. The contract checks again when
+/// the operation runs.
+async fn check_revision(
+    ctx: &Context<'_>,
+    entry_id: u64,
+    expected_revision: Option<u64>,
+) -> Result<(), async_graphql::Error> {
+    if expected_revision.is_none() {
+        return Ok(());
+    }
+    let entry = state(ctx)
+        .read_entry(entry_id)
+        .await
+        .map_err(|e| async_graphql::Error::new(format!("Failed to read entry: {}", e)))?
+        .ok_or_else(|| async_graphql::Error::new("Entry not found"))?;
+    entry
+        .check_revision(expected_revision)
+        .map_err(|conflict| conflict.extend())
 }
 
+/// GraphQL query root for reading diary data.
+struct QueryRoot;
+
 #[Object]
 impl QueryRoot {
+    /// The public read API: only public entries, without notebook or metadata details
+    async fn public(&self) -> PublicQuery {
+        PublicQuery
+    }
+
     /// Check if the diary has been initialized
-    async fn is_initialized(&self) -> bool {
-        self.state.is_initialized()
+    async fn is_initialized(&self, ctx: &Context<'_>) -> bool {
+        state(ctx).is_initialized()
     }
 
     /// Get the diary owner
-    async fn owner(&self) -> String {
-        self.state.get_owner()
+    async fn owner(&self, ctx: &Context<'_>) -> String {
+        state(ctx).get_owner()
     }
 
-    /// Get the total number of entries
-    async fn entry_count(&self) -> u64 {
-        self.state.get_entry_count()
+    /// The owner transfer waiting to be accepted, if any. It may have expired.
+    async fn pending_owner_transfer(&self, ctx: &Context<'_>) -> Option<OwnerTransfer> {
+        state(ctx).pending_owner_transfer.get().clone()
     }
 
-    /// Get all diary entries (sorted by newest first)
-    async fn entries(&self) -> Result<Vec<DiaryEntry>, async_graphql::Error> {
-        self.state
-            .get_all_entries()
-            .await
-            .map_err(|e| async_graphql::Error::new(format!("Failed to get entries: {}", e)))
+    /// Every proposed, cancelled and accepted owner transfer, oldest first
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn ownership_log(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<OwnershipEvent>, async_graphql::Error> {
+        limit_results(ctx, state(ctx).ownership_log.get().clone())
     }
 
-    /// Get a specific diary entry by ID
-    async fn entry(&self, id: u64) -> Result<Option<DiaryEntry>, async_graphql::Error> {
-        self.state
-            .get_entry(id)
-            .await
-            .map_err(|e| async_graphql::Error::new(format!("Failed to get entry: {}", e)))
+    /// Get the number of entries, not counting deleted ones
+    async fn entry_count(&self, ctx: &Context<'_>) -> u64 {
+        state(ctx).get_entry_count().await
+    }
+
+    /// Schema version of the stored state. Below the contract's version while
+    /// entries written by an earlier version are still being migrated
+    async fn schema_version(&self, ctx: &Context<'_>) -> u32 {
+        state(ctx).get_schema_version()
+    }
+
+    /// The limits queries to this service must stay within
+    async fn query_limits(&self, ctx: &Context<'_>) -> QueryLimits {
+        limits(ctx).clone()
+    }
+
+    /// Get all notebooks
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn notebooks(&self, ctx: &Context<'_>) -> Result<Vec<Notebook>, async_graphql::Error> {
+        limit_results(ctx, state(ctx).get_all_notebooks().await)
+    }
+
+    /// Get a specific notebook by ID
+    async fn notebook(&self, ctx: &Context<'_>, id: u64) -> Option<Notebook> {
+        state(ctx).get_notebook(id).await
+    }
+
+    /// Get diary entries, optionally matching a filter. Without `orderBy`,
+    /// pinned entries come first, then the rest sorted by newest first.
+    /// Without a notebook, entries of protected notebooks are left out, and
+    /// entries the reader phrase does not give access to always are.
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn entries(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<EntryFilter>,
+        order_by: Option<EntryOrder>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let filter = filter.unwrap_or_default();
+        let entries = self
+            .query_entries(
+                ctx,
+                filter,
+                order_by,
+                notebook_id,
+                secret_phrase,
+                reader_phrase,
+                Some(max_results(ctx)),
+            )
+            .await?;
+        limit_results(ctx, entries)
     }
 
-    /// Get the latest N entries
-    async fn latest_entries(&self, limit: i32) -> Result<Vec<DiaryEntry>, async_graphql::Error> {
-        if limit <= 0 {
-            return Err(async_graphql::Error::new("Limit must be positive"));
+    /// Get the IDs of the pinned entries readable in the requested scope, in display order
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn pinned_ids(
+        &self,
+        ctx: &Context<'_>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<u64>, async_graphql::Error> {
+        self.check_reader_phrase(ctx, reader_phrase.as_deref())?;
+        let notebooks = self
+            .visible_notebook_ids(ctx, notebook_id, secret_phrase.as_deref())
+            .await?;
+        let mut ids = Vec::new();
+        for id in state(ctx).pinned.get().clone() {
+            if let Some(entry) = state(ctx).get_header(id).await {
+                if notebooks.contains(&entry.notebook_id)
+                    && state(ctx).can_read(entry.visibility, reader_phrase.as_deref())
+                {
+                    ids.push(id);
+                }
+            }
+        }
+        limit_results(ctx, ids)
+    }
+
+    /// Get the favorite entries (newest first)
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn favorites(
+        &self,
+        ctx: &Context<'_>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let filter = EntryFilter {
+            favorite: Some(true),
+            ..EntryFilter::default()
+        };
+        let order = Some(EntryOrder::CreatedDesc);
+        let favorites = self
+            .query_entries(
+                ctx,
+                filter,
+                order,
+                notebook_id,
+                secret_phrase,
+                reader_phrase,
+                Some(max_results(ctx)),
+            )
+            .await?;
+        limit_results(ctx, favorites)
+    }
+
+    /// Get a specific diary entry by ID, with its content.
+    /// List queries only return entry headers.
+    async fn entry(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Option<DiaryEntry>, async_graphql::Error> {
+        let Some(header) = state(ctx).get_header(id).await else {
+            return Ok(None);
+        };
+
+        self.check_read_access(ctx, &header, secret_phrase.as_deref(), reader_phrase.as_deref())
+            .await?;
+        Ok(state(ctx).get_entry(id).await)
+    }
+
+    /// Get unpublished drafts (most recently saved first).
+    /// Without a notebook, drafts of protected notebooks are left out.
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn drafts(
+        &self,
+        ctx: &Context<'_>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+    ) -> Result<Vec<Draft>, async_graphql::Error> {
+        if let Some(notebook_id) = notebook_id {
+            self.check_notebook_access(ctx, notebook_id, secret_phrase.as_deref())
+                .await?;
+        }
+
+        let mut drafts = Vec::new();
+        for draft in state(ctx).get_all_drafts().await {
+            let visible = match notebook_id {
+                Some(notebook_id) => draft.notebook_id == notebook_id,
+                None => self.check_notebook_access(ctx, draft.notebook_id, None).await.is_ok(),
+            };
+            if visible {
+                drafts.push(draft);
+            }
         }
 
-        self.state
-            .get_latest_entries(limit as usize)
+        limit_results(ctx, drafts)
+    }
+
+    /// Get a specific draft by ID
+    async fn draft(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+        secret_phrase: Option<String>,
+    ) -> Result<Option<Draft>, async_graphql::Error> {
+        let Some(draft) = state(ctx).get_draft(id).await else {
+            return Ok(None);
+        };
+
+        self.check_notebook_access(ctx, draft.notebook_id, secret_phrase.as_deref())
+            .await?;
+        Ok(Some(draft))
+    }
+
+    /// Get all entry templates
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn templates(&self, ctx: &Context<'_>) -> Result<Vec<Template>, async_graphql::Error> {
+        limit_results(ctx, state(ctx).get_all_templates().await)
+    }
+
+    /// Get a specific template by ID
+    async fn template(&self, ctx: &Context<'_>, id: u64) -> Option<Template> {
+        state(ctx).get_template(id).await
+    }
+
+    /// Render a template as `addEntryFromTemplate` would right now, without adding an entry
+    async fn preview_template(
+        &self,
+        ctx: &Context<'_>,
+        template_id: u64,
+        values: Vec<TemplateValue>,
+    ) -> Result<RenderedTemplate, async_graphql::Error> {
+        let template = state(ctx)
+            .get_template(template_id)
+            .await
+            .ok_or_else(|| async_graphql::Error::new("Template not found"))?;
+
+        let values = template::values_with_builtins(
+            values.into_iter().map(|v| (v.key, v.value)).collect(),
+            runtime(ctx).system_time().micros(),
+            *state(ctx).utc_offset_minutes.get(),
+        )
+        .map_err(async_graphql::Error::new)?;
+
+        Ok(RenderedTemplate {
+            title: template::render(&template.title_pattern, &values)
+                .map_err(async_graphql::Error::new)?,
+            content: template::render(&template.content_skeleton, &values)
+                .map_err(async_graphql::Error::new)?,
+        })
+    }
+
+    /// Get the entries an entry links to, through `[[entry:42]]` or `linkEntries`
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn linked_entries(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let (secret_phrase, reader_phrase) = (secret_phrase.as_deref(), reader_phrase.as_deref());
+        self.check_entry_access(ctx, id, secret_phrase, reader_phrase).await?;
+        let ids = state(ctx).get_linked_ids(id).await;
+        limit_results(ctx, self.visible_entries(ctx, ids, secret_phrase, reader_phrase).await)
+    }
+
+    /// Get the entries linking to an entry
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn backlinks(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let (secret_phrase, reader_phrase) = (secret_phrase.as_deref(), reader_phrase.as_deref());
+        self.check_entry_access(ctx, id, secret_phrase, reader_phrase).await?;
+        let ids = state(ctx).get_backlink_ids(id).await;
+        limit_results(ctx, self.visible_entries(ctx, ids, secret_phrase, reader_phrase).await)
+    }
+
+    /// Get the files attached to an entry
+    async fn attachments(
+        &self,
+        ctx: &Context<'_>,
+        entry_id: u64,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<Attachment>, async_graphql::Error> {
+        let entry = state(ctx)
+            .get_header(entry_id)
             .await
-            .map_err(|e| async_graphql::Error::new(format!("Failed to get latest entries: {}", e)))
+            .ok_or_else(|| async_graphql::Error::new("Entry not found"))?;
+
+        self.check_read_access(ctx, &entry, secret_phrase.as_deref(), reader_phrase.as_deref())
+            .await?;
+        Ok(entry.attachments)
+    }
+
+    /// Get the latest N entries, pinned entries first
+    #[graphql(
+        deprecation = "Use `entries`, which returns the same order without `orderBy`",
+        complexity = "SCAN_COMPLEXITY + child_complexity"
+    )]
+    async fn latest_entries(
+        &self,
+        ctx: &Context<'_>,
+        limit: i32,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let limit = check_limit(ctx, limit)?;
+
+        let filter = EntryFilter::default();
+        let mut entries = self
+            .query_entries(ctx, filter, None, notebook_id, secret_phrase, reader_phrase, None)
+            .await?;
+        entries.truncate(limit);
+        Ok(entries)
     }
 
-    /// Get entries within a specific time range
+    /// Get entries created within a specific time range
+    #[graphql(
+        deprecation = "Use `entries` with `createdAfter` and `createdBefore` in the filter",
+        complexity = "SCAN_COMPLEXITY + child_complexity"
+    )]
     async fn entries_in_range(
         &self,
+        ctx: &Context<'_>,
         start_timestamp: u64,
         end_timestamp: u64,
-    ) -> Result<Vec<DiaryEntry>, async_graphql::Error> {
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
         if start_timestamp > end_timestamp {
             return Err(async_graphql::Error::new(
                 "Start timestamp must be before end timestamp",
             ));
         }
 
-        self.state
-            .get_entries_in_range(start_timestamp, end_timestamp)
-            .await
-            .map_err(|e| {
-                async_graphql::Error::new(format!("Failed to get entries in range: {}", e))
-            })
+        let filter = EntryFilter {
+            created_after: Some(start_timestamp),
+            created_before: Some(end_timestamp),
+            ..EntryFilter::default()
+        };
+        let order = Some(EntryOrder::CreatedDesc);
+        let entries = self
+            .query_entries(
+                ctx,
+                filter,
+                order,
+                notebook_id,
+                secret_phrase,
+                reader_phrase,
+                Some(max_results(ctx)),
+            )
+            .await?;
+        limit_results(ctx, entries)
     }
 
     /// Search entries by title (case-insensitive)
-    async fn search_by_title(&self, query: String) -> Result<Vec<DiaryEntry>, async_graphql::Error> {
-        let all_entries = self.state.get_all_entries().await.map_err(|e| {
-            async_graphql::Error::new(format!("Failed to search entries: {}", e))
-        })?;
+    #[graphql(
+        deprecation = "Use `entries` with `titleContains` in the filter",
+        complexity = "SCAN_COMPLEXITY + child_complexity"
+    )]
+    async fn search_by_title(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let filter = EntryFilter {
+            title_contains: Some(query),
+            ..EntryFilter::default()
+        };
+        let order = Some(EntryOrder::CreatedDesc);
+        let entries = self
+            .query_entries(
+                ctx,
+                filter,
+                order,
+                notebook_id,
+                secret_phrase,
+                reader_phrase,
+                Some(max_results(ctx)),
+            )
+            .await?;
+        limit_results(ctx, entries)
+    }
+
+    /// Search entries by content (case-insensitive)
+    #[graphql(
+        deprecation = "Use `entries` with `contentContains` in the filter",
+        complexity = "SCAN_COMPLEXITY + child_complexity"
+    )]
+    async fn search_by_content(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let filter = EntryFilter {
+            content_contains: Some(query),
+            ..EntryFilter::default()
+        };
+        let order = Some(EntryOrder::CreatedDesc);
+        let entries = self
+            .query_entries(
+                ctx,
+                filter,
+                order,
+                notebook_id,
+                secret_phrase,
+                reader_phrase,
+                Some(max_results(ctx)),
+            )
+            .await?;
+        limit_results(ctx, entries)
+    }
+
+    /// Get entries whose metadata matches a filter
+    #[graphql(
+        deprecation = "Use `entries` with `metadata` in the filter",
+        complexity = "SCAN_COMPLEXITY + child_complexity"
+    )]
+    async fn entries_by_metadata(
+        &self,
+        ctx: &Context<'_>,
+        filter: MetadataFilter,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        let filter = EntryFilter {
+            metadata: Some(filter),
+            ..EntryFilter::default()
+        };
+        let order = Some(EntryOrder::CreatedDesc);
+        let entries = self
+            .query_entries(
+                ctx,
+                filter,
+                order,
+                notebook_id,
+                secret_phrase,
+                reader_phrase,
+                Some(max_results(ctx)),
+            )
+            .await?;
+        limit_results(ctx, entries)
+    }
 
-        let query_lower = query.to_lowercase();
-        let filtered: Vec<DiaryEntry> = all_entries
+    /// Aggregate mood, rating, energy and numeric custom fields,
+    /// optionally over the entries matching a filter
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn metadata_summary(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<MetadataFilter>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<MetadataSummary, async_graphql::Error> {
+        let filter = filter.unwrap_or_default();
+        let entries: Vec<EntryHeader> = self
+            .scoped_entries(ctx, notebook_id, secret_phrase, reader_phrase)
+            .await?
             .into_iter()
-            .filter(|entry| entry.title.to_lowercase().contains(&query_lower))
+            .filter(|entry| filter.matches(&entry.metadata))
             .collect();
 
-        Ok(filtered)
+        Ok(MetadataSummary::from_entries(&entries))
     }
 
-    /// Search entries by content (case-insensitive)
-    async fn search_by_content(
+    /// The owner's timezone as an offset from UTC in minutes
+    async fn utc_offset_minutes(&self, ctx: &Context<'_>) -> i32 {
+        *state(ctx).utc_offset_minutes.get()
+    }
+
+    /// Progress of the export to a successor application, if one was started
+    async fn export_progress(&self, ctx: &Context<'_>) -> Option<ExportProgress> {
+        state(ctx).export.get().clone()
+    }
+
+    /// The predecessor application while its export is being imported
+    async fn importing_from(&self, ctx: &Context<'_>) -> Option<ApplicationId> {
+        *state(ctx).importing_from.get()
+    }
+
+    /// Entries written on the same calendar day in previous years.
+    /// `date` is `YYYY-MM-DD` in the owner's timezone and defaults to today.
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn on_this_day(
         &self,
-        query: String,
-    ) -> Result<Vec<DiaryEntry>, async_graphql::Error> {
-        let all_entries = self.state.get_all_entries().await.map_err(|e| {
-            async_graphql::Error::new(format!("Failed to search entries: {}", e))
-        })?;
+        ctx: &Context<'_>,
+        date: Option<String>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<Anniversary>, async_graphql::Error> {
+        let day = self.local_date_or_today(ctx, date)?;
+        let entries = self
+            .scoped_entries(ctx, notebook_id, secret_phrase, reader_phrase)
+            .await?;
+
+        limit_results(ctx, self.anniversaries_between(ctx, entries, day, day))
+    }
+
+    /// Anniversaries of entries falling within `days` days starting at `date`
+    /// (default: the next 7 days starting today), soonest first
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn anniversaries(
+        &self,
+        ctx: &Context<'_>,
+        date: Option<String>,
+        days: Option<i32>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<Anniversary>, async_graphql::Error> {
+        let days = days.unwrap_or(7);
+        if !(1..=366).contains(&days) {
+            return Err(async_graphql::Error::new("Days must be between 1 and 366"));
+        }
 
-        let query_lower = query.to_lowercase();
-        let filtered: Vec<DiaryEntry> = all_entries
+        let first = self.local_date_or_today(ctx, date)?;
+        let entries = self
+            .scoped_entries(ctx, notebook_id, secret_phrase, reader_phrase)
+            .await?;
+
+        let last = first + i64::from(days) - 1;
+        limit_results(ctx, self.anniversaries_between(ctx, entries, first, last))
+    }
+
+    /// Writing statistics, maintained incrementally by the contract. Without a notebook,
+    /// protected notebooks are left out. Days follow the owner's timezone; the heatmap
+    /// covers the last `heatmap_days` days (default 365), and the per-period counts the
+    /// latest `maxResults` periods. Private entries are never counted, and while the
+    /// diary has a members phrase, the statistics need it.
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        top: Option<i32>,
+        heatmap_days: Option<i32>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<DiaryStats, async_graphql::Error> {
+        // The counters mix `Members` and `Public` entries, so they can only be shown
+        // to readers of both
+        self.check_reader_phrase(ctx, reader_phrase.as_deref())?;
+        if !state(ctx).can_read(Visibility::Members, reader_phrase.as_deref()) {
+            return Err(async_graphql::Error::new("Statistics need the members phrase"));
+        }
+
+        let top = top.map_or(Ok(10), |top| check_limit(ctx, top))?;
+        let heatmap_days = heatmap_days.map_or(Ok(365), |days| check_limit(ctx, days))?;
+
+        let notebook_ids = self
+            .visible_notebook_ids(ctx, notebook_id, secret_phrase.as_deref())
+            .await?;
+        let today = calendar::local_day_of(
+            runtime(ctx).system_time().micros(),
+            *state(ctx).utc_offset_minutes.get(),
+        );
+        let daily = state(ctx).get_daily_counts(&notebook_ids).await;
+        let top_words = state(ctx).get_top_words(&notebook_ids, top).await;
+        let top_tags = state(ctx).get_top_tags(&notebook_ids, top).await;
+
+        Ok(DiaryStats::new(
+            &daily,
+            state(ctx).get_total_words(&notebook_ids).await,
+            top_words,
+            top_tags,
+            today,
+            heatmap_days as i64,
+            limits(ctx).max_results as usize,
+        ))
+    }
+}
+
+impl QueryRoot {
+    /// Parse a `YYYY-MM-DD` date, or take today in the owner's timezone
+    fn local_date_or_today(
+        &self,
+        ctx: &Context<'_>,
+        date: Option<String>,
+    ) -> Result<i64, async_graphql::Error> {
+        match date {
+            Some(date) => calendar::parse_date(&date)
+                .ok_or_else(|| async_graphql::Error::new("Date must be formatted as YYYY-MM-DD")),
+            None => Ok(calendar::local_day_of(
+                runtime(ctx).system_time().micros(),
+                *state(ctx).utc_offset_minutes.get(),
+            )),
+        }
+    }
+
+    /// Anniversaries of the given entries falling on days `first..=last`, soonest first
+    fn anniversaries_between(
+        &self,
+        ctx: &Context<'_>,
+        entries: Vec<EntryHeader>,
+        first: i64,
+        last: i64,
+    ) -> Vec<Anniversary> {
+        let mut anniversaries = Vec::new();
+
+        for entry in entries {
+            let written = state(ctx).local_day_of(&entry);
+            let Some(day) = (first..=last).find(|day| calendar::is_anniversary(written, *day))
+            else {
+                continue;
+            };
+            let (written_year, _, _) = calendar::civil_from_days(written);
+            let (year, _, _) = calendar::civil_from_days(day);
+
+            anniversaries.push(Anniversary {
+                date: calendar::format_date(day),
+                written_on: calendar::format_date(written),
+                years_ago: (year - written_year) as u32,
+                entry,
+            });
+        }
+
+        anniversaries.sort_by(|a, b| a.date.cmp(&b.date).then(a.years_ago.cmp(&b.years_ago)));
+        anniversaries
+    }
+
+    /// Entries matching a filter in the requested scope, planned by the state.
+    /// With `max_results`, the scan stops once the result is too long to be returned.
+    #[allow(clippy::too_many_arguments)]
+    async fn query_entries(
+        &self,
+        ctx: &Context<'_>,
+        filter: EntryFilter,
+        order_by: Option<EntryOrder>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+        max_results: Option<usize>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        self.check_reader_phrase(ctx, reader_phrase.as_deref())?;
+        let notebooks = self
+            .visible_notebook_ids(ctx, notebook_id, secret_phrase.as_deref())
+            .await?;
+        let visible = |entry: &EntryHeader| {
+            notebooks.contains(&entry.notebook_id)
+                && state(ctx).can_read(entry.visibility, reader_phrase.as_deref())
+        };
+        state(ctx)
+            .query_entries(&filter, order_by, visible, max_results)
+            .await
+            .map_err(|e| async_graphql::Error::new(format!("Failed to query entries: {}", e)))
+    }
+
+    /// Entries visible for the requested scope: one notebook, or every
+    /// notebook without its own secret phrase, and the visibilities the
+    /// reader phrase gives access to
+    async fn scoped_entries(
+        &self,
+        ctx: &Context<'_>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<String>,
+        reader_phrase: Option<String>,
+    ) -> Result<Vec<EntryHeader>, async_graphql::Error> {
+        self.check_reader_phrase(ctx, reader_phrase.as_deref())?;
+        let mut entries = match notebook_id {
+            Some(notebook_id) => {
+                self.check_notebook_access(ctx, notebook_id, secret_phrase.as_deref())
+                    .await?;
+                state(ctx).get_notebook_headers(notebook_id).await
+            }
+            None => state(ctx).get_unprotected_headers().await,
+        };
+        entries.retain(|entry| state(ctx).can_read(entry.visibility, reader_phrase.as_deref()));
+        Ok(entries)
+    }
+
+    /// Notebooks readable for the requested scope: one notebook, or every
+    /// notebook without its own secret phrase
+    async fn visible_notebook_ids(
+        &self,
+        ctx: &Context<'_>,
+        notebook_id: Option<u64>,
+        secret_phrase: Option<&str>,
+    ) -> Result<Vec<u64>, async_graphql::Error> {
+        match notebook_id {
+            Some(notebook_id) => {
+                self.check_notebook_access(ctx, notebook_id, secret_phrase)
+                    .await?;
+                Ok(vec![notebook_id])
+            }
+            None => Ok(state(ctx)
+                .get_all_notebooks()
+                .await
+                .into_iter()
+                .filter(|n| n.secret_phrase_hash.is_none())
+                .map(|n| n.id)
+                .collect()),
+        }
+    }
+
+    /// Reject reads of a missing entry, of an entry in a locked notebook, or of
+    /// an entry the reader phrase does not give access to
+    async fn check_entry_access(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+        secret_phrase: Option<&str>,
+        reader_phrase: Option<&str>,
+    ) -> Result<(), async_graphql::Error> {
+        let entry = state(ctx)
+            .get_header(id)
+            .await
+            .ok_or_else(|| async_graphql::Error::new("Entry not found"))?;
+        self.check_read_access(ctx, &entry, secret_phrase, reader_phrase)
+            .await
+    }
+
+    /// Reject reads of an entry in a locked notebook, or of an entry
+    /// the reader phrase does not give access to
+    async fn check_read_access(
+        &self,
+        ctx: &Context<'_>,
+        entry: &EntryHeader,
+        secret_phrase: Option<&str>,
+        reader_phrase: Option<&str>,
+    ) -> Result<(), async_graphql::Error> {
+        self.check_reader_phrase(ctx, reader_phrase)?;
+        self.check_notebook_access(ctx, entry.notebook_id, secret_phrase)
+            .await?;
+        if !state(ctx).can_read(entry.visibility, reader_phrase) {
+            return Err(async_graphql::Error::new("Entry is locked"));
+        }
+        Ok(())
+    }
+
+    /// The given entries that still exist and can be read with `secret_phrase`
+    /// and `reader_phrase`
+    async fn visible_entries(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<u64>,
+        secret_phrase: Option<&str>,
+        reader_phrase: Option<&str>,
+    ) -> Vec<EntryHeader> {
+        let mut entries = Vec::new();
+        for id in ids {
+            let Some(entry) = state(ctx).get_header(id).await else {
+                continue;
+            };
+            if self
+                .check_read_access(ctx, &entry, secret_phrase, reader_phrase)
+                .await
+                .is_ok()
+            {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+
+    /// Reject a reader phrase that is neither the diary's secret phrase nor its members phrase
+    fn check_reader_phrase(
+        &self,
+        ctx: &Context<'_>,
+        reader_phrase: Option<&str>,
+    ) -> Result<(), async_graphql::Error> {
+        let Some(phrase) = reader_phrase else {
+            return Ok(());
+        };
+        let hash = hash_secret_phrase(phrase);
+        if &hash != state(ctx).secret_phrase_hash.get()
+            && state(ctx).members_phrase_hash.get().as_ref() != Some(&hash)
+        {
+            return Err(async_graphql::Error::new("Invalid reader phrase"));
+        }
+        Ok(())
+    }
+
+    /// Reject reads from a protected notebook unless its secret phrase is given
+    async fn check_notebook_access(
+        &self,
+        ctx: &Context<'_>,
+        notebook_id: u64,
+        secret_phrase: Option<&str>,
+    ) -> Result<(), async_graphql::Error> {
+        let notebook = state(ctx)
+            .get_notebook(notebook_id)
+            .await
+            .ok_or_else(|| async_graphql::Error::new("Notebook not found"))?;
+
+        if let Some(hash) = &notebook.secret_phrase_hash {
+            let provided = secret_phrase.map(hash_secret_phrase);
+            if provided.as_ref() != Some(hash) {
+                return Err(async_graphql::Error::new("Notebook is locked"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read-only queries over the public entries of the diary.
+struct PublicQuery;
+
+#[Object]
+impl PublicQuery {
+    /// Number of public entries
+    async fn entry_count(&self, ctx: &Context<'_>) -> Result<u64, async_graphql::Error> {
+        state(ctx)
+            .public_entries
+            .count()
+            .await
+            .map(|count| count as u64)
+            .map_err(|e| async_graphql::Error::new(format!("Failed to count entries: {}", e)))
+    }
+
+    /// Public entries, newest first
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn entries(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i32>,
+    ) -> Result<Vec<PublicEntry>, async_graphql::Error> {
+        let mut headers = state(ctx).get_public_headers().await;
+        if let Some(limit) = limit {
+            headers.truncate(check_limit(ctx, limit)?);
+        }
+        let headers = limit_results(ctx, headers)?;
+        Ok(self
+            .with_content(ctx, headers)
+            .await
+            .into_iter()
+            .map(PublicEntry::from)
+            .collect())
+    }
+
+    /// A public entry by ID; `null` for entries that are not public
+    async fn entry(&self, ctx: &Context<'_>, id: u64) -> Option<PublicEntry> {
+        let header = state(ctx).get_public_header(id).await?;
+        self.with_content(ctx, vec![header])
+            .await
+            .pop()
+            .map(PublicEntry::from)
+    }
+
+    /// Public entries as an Atom 1.0 document, newest first
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn atom_feed(
+        &self,
+        ctx: &Context<'_>,
+        link: String,
+        title: Option<String>,
+        description: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<String, async_graphql::Error> {
+        let (info, items) = self.feed(ctx, link, title, description, limit).await?;
+        Ok(feed::atom(&info, &items))
+    }
+
+    /// Public entries as an RSS 2.0 document, newest first
+    #[graphql(complexity = "SCAN_COMPLEXITY + child_complexity")]
+    async fn rss_feed(
+        &self,
+        ctx: &Context<'_>,
+        link: String,
+        title: Option<String>,
+        description: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<String, async_graphql::Error> {
+        let (info, items) = self.feed(ctx, link, title, description, limit).await?;
+        Ok(feed::rss(&info, &items))
+    }
+}
+
+impl PublicQuery {
+    /// Collect the channel details and items shared by both feed formats
+    async fn feed(
+        &self,
+        ctx: &Context<'_>,
+        link: String,
+        title: Option<String>,
+        description: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<(FeedInfo, Vec<FeedItem>), async_graphql::Error> {
+        if link.trim().is_empty() {
+            return Err(async_graphql::Error::new("Feed link cannot be empty"));
+        }
+        let limit = match limit {
+            Some(limit) => check_limit(ctx, limit)?,
+            None => DEFAULT_FEED_LIMIT.min(limits(ctx).max_results as usize),
+        };
+
+        let owner = state(ctx).get_owner();
+        let info = FeedInfo {
+            chain_id: runtime(ctx).chain_id().to_string(),
+            title: title.unwrap_or_else(|| "Diary".to_string()),
+            link,
+            description: description.unwrap_or_else(|| "Public diary entries".to_string()),
+            author: owner,
+        };
+        let mut headers = state(ctx).get_public_headers().await;
+        headers.truncate(limit);
+        let items = self
+            .with_content(ctx, headers)
+            .await
             .into_iter()
-            .filter(|entry| entry.content.to_lowercase().contains(&query_lower))
+            .map(|entry| FeedItem {
+                id: entry.id,
+                tags: text::extract_tags(&entry.content),
+                title: entry.title,
+                content: entry.content,
+                created_at: entry.created_at,
+                updated_at: entry.timestamp,
+            })
             .collect();
 
-        Ok(filtered)
+        Ok((info, items))
+    }
+
+    /// Load the content of the given entries
+    async fn with_content(&self, ctx: &Context<'_>, headers: Vec<EntryHeader>) -> Vec<DiaryEntry> {
+        let mut entries = Vec::new();
+        for header in headers {
+            if let Some(entry) = state(ctx).get_entry(header.id).await {
+                entries.push(entry);
+            }
+        }
+        entries
     }
 }
 
-/// GraphQL mutation root for scheduling operations.
-struct MutationRoot {
-    runtime: Arc<ServiceRuntime<DiaryService>>,
+/// The publicly visible part of an entry
+#[derive(async_graphql::SimpleObject)]
+struct PublicEntry {
+    id: u64,
+    title: String,
+    content: String,
+    created_at: u64,
+    updated_at: u64,
+    tags: Vec<String>,
+}
+
+impl From<DiaryEntry> for PublicEntry {
+    fn from(entry: DiaryEntry) -> Self {
+        PublicEntry {
+            id: entry.id,
+            tags: text::extract_tags(&entry.content),
+            title: entry.title,
+            content: entry.content,
+            created_at: entry.created_at,
+            updated_at: entry.timestamp,
+        }
+    }
 }
 
+/// GraphQL mutation root for scheduling operations.
+struct MutationRoot;
+
 #[Object]
 impl MutationRoot {
     /// Initialize the diary with a secret phrase
     async fn initialize(
         &self,
+        ctx: &Context<'_>,
         secret_phrase: String,
     ) -> Result<OperationResponse, async_graphql::Error> {
         if secret_phrase.is_empty() {
@@ -184,12 +1134,9 @@ impl MutationRoot {
             ));
         }
 
-        // Hash the secret phrase
-        let secret_phrase_hash = hash_secret_phrase(&secret_phrase);
-
-        // Schedule the Initialize operation
-        self.runtime
-            .schedule_operation(Operation::Initialize { secret_phrase_hash })
+        // Schedule the Initialize operation; the contract stores only the hash
+        runtime(ctx)
+            .schedule_operation(Operation::Initialize { secret_phrase })
             .await;
 
         Ok(OperationResponse::ok(
@@ -198,11 +1145,16 @@ impl MutationRoot {
     }
 
     /// Add a new diary entry
+    #[allow(clippy::too_many_arguments)]
     async fn add_entry(
         &self,
+        ctx: &Context<'_>,
         secret_phrase: String,
         title: String,
         content: String,
+        notebook_id: Option<u64>,
+        metadata: Option<EntryMetadata>,
+        visibility: Option<Visibility>,
     ) -> Result<OperationResponse, async_graphql::Error> {
         if secret_phrase.is_empty() {
             return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
@@ -216,12 +1168,22 @@ impl MutationRoot {
             return Err(async_graphql::Error::new("Content cannot be empty"));
         }
 
+        if let Some(ref m) = metadata {
+            m.validate().map_err(async_graphql::Error::new)?;
+        }
+
+        validate_visibility(visibility.unwrap_or_default(), &title, &content)
+            .map_err(async_graphql::Error::new)?;
+
         // Schedule the AddEntry operation
-        self.runtime
+        runtime(ctx)
             .schedule_operation(Operation::AddEntry {
                 secret_phrase,
                 title: title.clone(),
                 content,
+                notebook_id,
+                metadata,
+                visibility,
             })
             .await;
 
@@ -231,21 +1193,27 @@ impl MutationRoot {
         )))
     }
 
-    /// Update an existing diary entry
+    /// Update an existing diary entry. With `expectedRevision`, the update fails with a
+    /// `REVISION_CONFLICT` error if the entry is no longer at that revision.
+    #[allow(clippy::too_many_arguments)]
     async fn update_entry(
         &self,
+        ctx: &Context<'_>,
         secret_phrase: String,
         entry_id: u64,
         title: Option<String>,
         content: Option<String>,
+        metadata: Option<EntryMetadata>,
+        visibility: Option<Visibility>,
+        expected_revision: Option<u64>,
     ) -> Result<OperationResponse, async_graphql::Error> {
         if secret_phrase.is_empty() {
             return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
         }
 
-        if title.is_none() && content.is_none() {
+        if title.is_none() && content.is_none() && metadata.is_none() && visibility.is_none() {
             return Err(async_graphql::Error::new(
-                "Must provide at least title or content to update",
+                "Must provide at least title, content, metadata or visibility to update",
             ));
         }
 
@@ -262,13 +1230,22 @@ impl MutationRoot {
             }
         }
 
+        if let Some(ref m) = metadata {
+            m.validate().map_err(async_graphql::Error::new)?;
+        }
+
+        check_revision(ctx, entry_id, expected_revision).await?;
+
         // Schedule the UpdateEntry operation
-        self.runtime
+        runtime(ctx)
             .schedule_operation(Operation::UpdateEntry {
                 secret_phrase,
                 entry_id,
                 title,
                 content,
+                metadata,
+                visibility,
+                expected_revision,
             })
             .await;
 
@@ -278,36 +1255,160 @@ impl MutationRoot {
         )))
     }
 
-    /// Delete a diary entry
-    async fn delete_entry(
+    /// Append text to the end of an entry's content. Only the end of a long
+    /// entry is rewritten, rather than its whole content.
+    async fn append_to_entry(
         &self,
+        ctx: &Context<'_>,
         secret_phrase: String,
         entry_id: u64,
+        text: String,
     ) -> Result<OperationResponse, async_graphql::Error> {
         if secret_phrase.is_empty() {
             return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
         }
 
-        // Schedule the DeleteEntry operation
-        self.runtime
-            .schedule_operation(Operation::DeleteEntry {
+        if text.is_empty() {
+            return Err(async_graphql::Error::new("Text cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::AppendToEntry {
                 secret_phrase,
                 entry_id,
+                text,
             })
             .await;
 
         Ok(OperationResponse::ok(format!(
-            "Entry {} deletion scheduled. Please wait for the operation to be executed.",
+            "Append to entry {} scheduled. Please wait for the operation to be executed.",
             entry_id
         )))
     }
 
-    /// Batch add multiple entries (convenience method)
-    async fn add_entries(
+    /// Replace `len` bytes of an entry's content starting at byte `offset` with `text`.
+    /// Offsets count bytes of the UTF-8 content and must fall on character boundaries.
+    async fn replace_range(
         &self,
+        ctx: &Context<'_>,
         secret_phrase: String,
-        entries: Vec<BatchEntryInput>,
-    ) -> Result<Vec<OperationResponse>, async_graphql::Error> {
+        entry_id: u64,
+        offset: u64,
+        len: u64,
+        text: String,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        let edit = ContentEdit {
+            offset,
+            len,
+            text: text.clone(),
+        };
+        let content = state(ctx)
+            .read_content(entry_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(format!("Failed to read entry: {}", e)))?;
+        if let Some(content) = content {
+            check_patch(&[edit], &content)?;
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::ReplaceRange {
+                secret_phrase,
+                entry_id,
+                offset,
+                len,
+                text,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Edit of entry {} scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Apply edits made on revision `baseRevision` of an entry's content, in order and with
+    /// offsets in that revision. If the entry changed since, the edits are merged when
+    /// they don't touch the ranges that changed, and rejected otherwise.
+    async fn patch_entry(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+        base_revision: u64,
+        patch: Vec<ContentEdit>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        let entry = state(ctx)
+            .read_entry(entry_id)
+            .await
+            .map_err(|e| async_graphql::Error::new(format!("Failed to read entry: {}", e)))?;
+        if let Some(entry) = entry {
+            let history = state(ctx).get_edit_history(entry_id).await;
+            let rebased = patch::rebase(patch.clone(), base_revision, entry.revision, &history)
+                .map_err(|e| async_graphql::Error::new(format!("Cannot merge patch: {}", e)))?;
+            check_patch(&rebased, &entry.content)?;
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::PatchEntry {
+                secret_phrase,
+                entry_id,
+                base_revision,
+                patch,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Patch of entry {} scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Delete a diary entry. With `expectedRevision`, the deletion fails with a
+    /// `REVISION_CONFLICT` error if the entry is no longer at that revision.
+    async fn delete_entry(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+        expected_revision: Option<u64>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        check_revision(ctx, entry_id, expected_revision).await?;
+
+        // Schedule the DeleteEntry operation
+        runtime(ctx)
+            .schedule_operation(Operation::DeleteEntry {
+                secret_phrase,
+                entry_id,
+                expected_revision,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Entry {} deletion scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Batch add multiple entries (convenience method)
+    async fn add_entries(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entries: Vec<BatchEntryInput>,
+        notebook_id: Option<u64>,
+    ) -> Result<Vec<OperationResponse>, async_graphql::Error> {
         if secret_phrase.is_empty() {
             return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
         }
@@ -326,12 +1427,26 @@ impl MutationRoot {
                 continue;
             }
 
+            if let Some(Err(error)) = entry.metadata.as_ref().map(EntryMetadata::validate) {
+                responses.push(OperationResponse::err(error));
+                continue;
+            }
+
+            let visibility = entry.visibility.unwrap_or_default();
+            if let Err(error) = validate_visibility(visibility, &entry.title, &entry.content) {
+                responses.push(OperationResponse::err(error));
+                continue;
+            }
+
             // Schedule each operation
-            self.runtime
+            runtime(ctx)
                 .schedule_operation(Operation::AddEntry {
                     secret_phrase: secret_phrase.clone(),
                     title: entry.title.clone(),
                     content: entry.content,
+                    notebook_id: entry.notebook_id.or(notebook_id),
+                    metadata: entry.metadata,
+                    visibility: entry.visibility,
                 })
                 .await;
 
@@ -343,11 +1458,1052 @@ impl MutationRoot {
 
         Ok(responses)
     }
-}
 
-/// Input type for batch entry creation
-#[derive(async_graphql::InputObject)]
-struct BatchEntryInput {
-    title: String,
-    content: String,
+    /// Create a new notebook, optionally protected by its own secret phrase
+    async fn create_notebook(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        name: String,
+        notebook_secret_phrase: Option<String>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if name.trim().is_empty() {
+            return Err(async_graphql::Error::new("Notebook name cannot be empty"));
+        }
+
+        if let Some(ref phrase) = notebook_secret_phrase {
+            if phrase.len() < 8 {
+                return Err(async_graphql::Error::new(
+                    "Notebook secret phrase must be at least 8 characters",
+                ));
+            }
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::CreateNotebook {
+                secret_phrase,
+                name: name.clone(),
+                notebook_secret_phrase,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Notebook '{}' creation scheduled. Please wait for the operation to be executed.",
+            name
+        )))
+    }
+
+    /// Rename a notebook
+    async fn rename_notebook(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        notebook_id: u64,
+        name: String,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if name.trim().is_empty() {
+            return Err(async_graphql::Error::new("Notebook name cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::RenameNotebook {
+                secret_phrase,
+                notebook_id,
+                name,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Notebook {} rename scheduled. Please wait for the operation to be executed.",
+            notebook_id
+        )))
+    }
+
+    /// Archive a notebook, making it read-only
+    async fn archive_notebook(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        notebook_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::ArchiveNotebook {
+                secret_phrase,
+                notebook_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Notebook {} archival scheduled. Please wait for the operation to be executed.",
+            notebook_id
+        )))
+    }
+
+    /// Make an archived notebook writable again
+    async fn unarchive_notebook(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        notebook_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::UnarchiveNotebook {
+                secret_phrase,
+                notebook_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Notebook {} unarchival scheduled. Please wait for the operation to be executed.",
+            notebook_id
+        )))
+    }
+
+    /// Move an entry into another notebook
+    async fn move_entry(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+        notebook_id: u64,
+        target_secret_phrase: Option<String>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::MoveEntry {
+                secret_phrase,
+                entry_id,
+                notebook_id,
+                target_secret_phrase,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Entry {} move to notebook {} scheduled. Please wait for the operation to be executed.",
+            entry_id, notebook_id
+        )))
+    }
+
+    /// Save a new draft. Drafts are not listed with the entries until published.
+    async fn save_draft(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        title: String,
+        content: String,
+        notebook_id: Option<u64>,
+        metadata: Option<EntryMetadata>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if let Some(ref m) = metadata {
+            m.validate().map_err(async_graphql::Error::new)?;
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::SaveDraft {
+                secret_phrase,
+                title,
+                content,
+                notebook_id,
+                metadata,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(
+            "Draft save scheduled. Please wait for the operation to be executed.",
+        ))
+    }
+
+    /// Overwrite a draft with its latest title, content and metadata
+    async fn update_draft(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        draft_id: u64,
+        title: String,
+        content: String,
+        metadata: Option<EntryMetadata>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if let Some(ref m) = metadata {
+            m.validate().map_err(async_graphql::Error::new)?;
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::UpdateDraft {
+                secret_phrase,
+                draft_id,
+                title,
+                content,
+                metadata,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Draft {} save scheduled. Please wait for the operation to be executed.",
+            draft_id
+        )))
+    }
+
+    /// Publish a draft as a regular entry
+    async fn publish_draft(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        draft_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::PublishDraft {
+                secret_phrase,
+                draft_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Draft {} publication scheduled. Please wait for the operation to be executed.",
+            draft_id
+        )))
+    }
+
+    /// Discard a draft
+    async fn discard_draft(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        draft_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::DiscardDraft {
+                secret_phrase,
+                draft_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Draft {} removal scheduled. Please wait for the operation to be executed.",
+            draft_id
+        )))
+    }
+
+    /// Create a template with a title pattern and a content skeleton
+    async fn create_template(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        name: String,
+        title_pattern: String,
+        content_skeleton: String,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if name.trim().is_empty() {
+            return Err(async_graphql::Error::new("Template name cannot be empty"));
+        }
+
+        if title_pattern.trim().is_empty() {
+            return Err(async_graphql::Error::new("Title pattern cannot be empty"));
+        }
+
+        template::placeholders(&title_pattern)
+            .and_then(|_| template::placeholders(&content_skeleton))
+            .map_err(async_graphql::Error::new)?;
+
+        runtime(ctx)
+            .schedule_operation(Operation::CreateTemplate {
+                secret_phrase,
+                name: name.clone(),
+                title_pattern,
+                content_skeleton,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Template '{}' creation scheduled. Please wait for the operation to be executed.",
+            name
+        )))
+    }
+
+    /// Update a template
+    async fn update_template(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        template_id: u64,
+        name: Option<String>,
+        title_pattern: Option<String>,
+        content_skeleton: Option<String>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if name.is_none() && title_pattern.is_none() && content_skeleton.is_none() {
+            return Err(async_graphql::Error::new(
+                "Must provide at least name, title pattern or content skeleton to update",
+            ));
+        }
+
+        for pattern in [&title_pattern, &content_skeleton].into_iter().flatten() {
+            template::placeholders(pattern).map_err(async_graphql::Error::new)?;
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::UpdateTemplate {
+                secret_phrase,
+                template_id,
+                name,
+                title_pattern,
+                content_skeleton,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Template {} update scheduled. Please wait for the operation to be executed.",
+            template_id
+        )))
+    }
+
+    /// Delete a template
+    async fn delete_template(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        template_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::DeleteTemplate {
+                secret_phrase,
+                template_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Template {} deletion scheduled. Please wait for the operation to be executed.",
+            template_id
+        )))
+    }
+
+    /// Add an entry rendered from a template. Built-in placeholders (`date`, `weekday`,
+    /// `day`, `month`, `year`, `time`) are filled in by the contract from the chain time.
+    async fn add_entry_from_template(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        template_id: u64,
+        values: Vec<TemplateValue>,
+        notebook_id: Option<u64>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+        template::check_keys(values.iter().map(|v| v.key.as_str()))
+            .map_err(async_graphql::Error::new)?;
+
+        runtime(ctx)
+            .schedule_operation(Operation::AddEntryFromTemplate {
+                secret_phrase,
+                template_id,
+                values,
+                notebook_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Entry creation from template {} scheduled. Please wait for the operation to be executed.",
+            template_id
+        )))
+    }
+
+    /// Attach a file to an entry. The file must first be published as a data blob,
+    /// e.g. with `linera publish-data-blob`.
+    async fn attach_file(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+        blob_hash: DataBlobHash,
+        mime_type: String,
+        filename: String,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        validate_attachment(&mime_type, &filename).map_err(async_graphql::Error::new)?;
+
+        runtime(ctx)
+            .schedule_operation(Operation::AttachFile {
+                secret_phrase,
+                entry_id,
+                blob_hash,
+                mime_type,
+                filename: filename.clone(),
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Attaching '{}' to entry {} scheduled. Please wait for the operation to be executed.",
+            filename, entry_id
+        )))
+    }
+
+    /// Remove an attachment from an entry
+    async fn detach_file(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+        blob_hash: DataBlobHash,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::DetachFile {
+                secret_phrase,
+                entry_id,
+                blob_hash,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Detaching file from entry {} scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Link one entry to another
+    async fn link_entries(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        from_entry_id: u64,
+        to_entry_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if from_entry_id == to_entry_id {
+            return Err(async_graphql::Error::new("An entry cannot link to itself"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::LinkEntries {
+                secret_phrase,
+                from_entry_id,
+                to_entry_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Link from entry {} to entry {} scheduled. Please wait for the operation to be executed.",
+            from_entry_id, to_entry_id
+        )))
+    }
+
+    /// Remove an explicit link between two entries
+    async fn unlink_entries(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        from_entry_id: u64,
+        to_entry_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::UnlinkEntries {
+                secret_phrase,
+                from_entry_id,
+                to_entry_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Unlinking entry {} from entry {} scheduled. Please wait for the operation to be executed.",
+            to_entry_id, from_entry_id
+        )))
+    }
+
+    /// Pin an entry, at the end of the pinned list or at `position`
+    async fn pin_entry(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+        position: Option<u32>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::PinEntry {
+                secret_phrase,
+                entry_id,
+                position,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Pinning entry {} scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Unpin an entry
+    async fn unpin_entry(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::UnpinEntry {
+                secret_phrase,
+                entry_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Unpinning entry {} scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Mark an entry as a favorite
+    async fn favorite(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::Favorite {
+                secret_phrase,
+                entry_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Adding entry {} to favorites scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Remove an entry from the favorites
+    async fn unfavorite(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        entry_id: u64,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::Unfavorite {
+                secret_phrase,
+                entry_id,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Removing entry {} from favorites scheduled. Please wait for the operation to be executed.",
+            entry_id
+        )))
+    }
+
+    /// Set the owner's timezone as an offset from UTC in minutes (e.g. 120 for UTC+2)
+    async fn set_timezone(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        utc_offset_minutes: i32,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if !(calendar::MIN_UTC_OFFSET_MINUTES..=calendar::MAX_UTC_OFFSET_MINUTES)
+            .contains(&utc_offset_minutes)
+        {
+            return Err(async_graphql::Error::new(
+                "UTC offset must be between -720 and 840 minutes",
+            ));
+        }
+        if utc_offset_minutes % calendar::SLOT_MINUTES != 0 {
+            return Err(async_graphql::Error::new(
+                "UTC offset must be a whole number of quarter hours",
+            ));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::SetTimezone {
+                secret_phrase,
+                utc_offset_minutes,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(
+            "Timezone change scheduled. Please wait for the operation to be executed.",
+        ))
+    }
+
+    /// Set the phrase readers give to see `MEMBERS` entries, or remove it
+    /// (`membersPhrase: null`) to let anyone see them
+    async fn set_members_phrase(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        members_phrase: Option<String>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+        if let Some(members_phrase) = &members_phrase {
+            if members_phrase.is_empty() {
+                return Err(async_graphql::Error::new("Members phrase cannot be empty"));
+            }
+            if members_phrase == &secret_phrase {
+                return Err(async_graphql::Error::new(
+                    "Members phrase must differ from the secret phrase",
+                ));
+            }
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::SetMembersPhrase {
+                secret_phrase,
+                members_phrase,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(
+            "Members phrase change scheduled. Please wait for the operation to be executed.",
+        ))
+    }
+
+    /// Send the next batch of the diary to a successor application on the same chain.
+    /// Repeat until `exportProgress.finished`; the diary is read-only from the first batch on.
+    async fn export_to_successor(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        successor: ApplicationId,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::ExportToSuccessor {
+                secret_phrase,
+                successor,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Export to {} scheduled. Please wait for the operation to be executed.",
+            successor
+        )))
+    }
+
+    /// Accept the export of `predecessor` into this diary, before it is initialized.
+    /// Only the signer that created the application can register it, and batches from
+    /// any other application are rejected.
+    async fn register_predecessor(
+        &self,
+        ctx: &Context<'_>,
+        predecessor: ApplicationId,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        runtime(ctx)
+            .schedule_operation(Operation::RegisterPredecessor { predecessor })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Registration of {} scheduled. Please wait for the operation to be executed.",
+            predecessor
+        )))
+    }
+
+    /// Offer the diary to `newOwner`, who can accept it with `acceptOwnership` within
+    /// 7 days. Replaces a pending proposal.
+    async fn propose_owner_transfer(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+        new_owner: AccountOwner,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if new_owner.to_string() == state(ctx).get_owner() {
+            return Err(async_graphql::Error::new("The diary already belongs to this owner"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::ProposeOwnerTransfer {
+                secret_phrase,
+                new_owner,
+            })
+            .await;
+
+        Ok(OperationResponse::ok(format!(
+            "Transfer to {} scheduled. Please wait for the operation to be executed.",
+            new_owner
+        )))
+    }
+
+    /// Take over the diary proposed to the signer of the block. On another chain than the
+    /// diary's, the acceptance is sent to the diary's chain and checked there.
+    async fn accept_ownership(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        let runtime = runtime(ctx);
+        if runtime.chain_id() == runtime.application_creator_chain_id() {
+            let now = runtime.system_time().micros();
+            match state(ctx).pending_owner_transfer.get() {
+                None => return Err(async_graphql::Error::new("No owner transfer is pending")),
+                Some(transfer) if transfer.is_expired(now) => {
+                    return Err(async_graphql::Error::new("The owner transfer expired"))
+                }
+                Some(_) => {}
+            }
+        }
+
+        runtime
+            .schedule_operation(Operation::AcceptOwnership)
+            .await;
+
+        Ok(OperationResponse::ok(
+            "Acceptance of ownership scheduled. Please wait for the operation to be executed.",
+        ))
+    }
+
+    /// Withdraw a pending owner transfer
+    async fn cancel_owner_transfer(
+        &self,
+        ctx: &Context<'_>,
+        secret_phrase: String,
+    ) -> Result<OperationResponse, async_graphql::Error> {
+        if secret_phrase.is_empty() {
+            return Err(async_graphql::Error::new("Secret phrase cannot be empty"));
+        }
+
+        if state(ctx).pending_owner_transfer.get().is_none() {
+            return Err(async_graphql::Error::new("No owner transfer is pending"));
+        }
+
+        runtime(ctx)
+            .schedule_operation(Operation::CancelOwnerTransfer { secret_phrase })
+            .await;
+
+        Ok(OperationResponse::ok(
+            "Cancellation of the owner transfer scheduled. Please wait for the operation to be executed.",
+        ))
+    }
+}
+
+/// Input type for batch entry creation
+#[derive(async_graphql::InputObject)]
+struct BatchEntryInput {
+    title: String,
+    content: String,
+    metadata: Option<EntryMetadata>,
+    /// Notebook of this entry, instead of the batch's `notebookId`
+    notebook_id: Option<u64>,
+    visibility: Option<Visibility>,
+}
+
+/// Number of entries with a given mood
+#[derive(async_graphql::SimpleObject)]
+struct MoodCount {
+    mood: Mood,
+    count: u64,
+}
+
+/// Number of entries with a given energy level
+#[derive(async_graphql::SimpleObject)]
+struct EnergyCount {
+    energy: EnergyLevel,
+    count: u64,
+}
+
+/// Average of a numeric custom field
+#[derive(async_graphql::SimpleObject)]
+struct FieldAverage {
+    key: String,
+    count: u64,
+    average: f64,
+}
+
+/// Aggregated metadata over a set of entries
+#[derive(async_graphql::SimpleObject)]
+struct MetadataSummary {
+    entry_count: u64,
+    rated_count: u64,
+    average_rating: Option<f64>,
+    moods: Vec<MoodCount>,
+    energy_levels: Vec<EnergyCount>,
+    numeric_fields: Vec<FieldAverage>,
+}
+
+impl MetadataSummary {
+    fn from_entries(entries: &[EntryHeader]) -> Self {
+        let mut moods: Vec<MoodCount> = Vec::new();
+        let mut energy_levels: Vec<EnergyCount> = Vec::new();
+        let mut sums: Vec<(String, u64, f64)> = Vec::new();
+        let mut rated_count = 0;
+        let mut rating_sum = 0u64;
+
+        for entry in entries {
+            let metadata = &entry.metadata;
+            if let Some(rating) = metadata.rating {
+                rated_count += 1;
+                rating_sum += u64::from(rating);
+            }
+            if let Some(mood) = metadata.mood {
+                match moods.iter_mut().find(|m| m.mood == mood) {
+                    Some(m) => m.count += 1,
+                    None => moods.push(MoodCount { mood, count: 1 }),
+                }
+            }
+            if let Some(energy) = metadata.energy {
+                match energy_levels.iter_mut().find(|e| e.energy == energy) {
+                    Some(e) => e.count += 1,
+                    None => energy_levels.push(EnergyCount { energy, count: 1 }),
+                }
+            }
+            for field in &metadata.custom_fields {
+                let Some(value) = field.as_number() else {
+                    continue;
+                };
+                match sums.iter_mut().find(|(key, _, _)| key == &field.key) {
+                    Some((_, count, sum)) => {
+                        *count += 1;
+                        *sum += value;
+                    }
+                    None => sums.push((field.key.clone(), 1, value)),
+                }
+            }
+        }
+
+        MetadataSummary {
+            entry_count: entries.len() as u64,
+            rated_count,
+            average_rating: (rated_count > 0).then(|| rating_sum as f64 / rated_count as f64),
+            moods,
+            energy_levels,
+            numeric_fields: sums
+                .into_iter()
+                .map(|(key, count, sum)| FieldAverage {
+                    key,
+                    count,
+                    average: sum / count as f64,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A template rendered with the current time and the given values
+#[derive(async_graphql::SimpleObject)]
+struct RenderedTemplate {
+    title: String,
+    content: String,
+}
+
+/// An entry remembered on the same calendar day of a later year
+#[derive(async_graphql::SimpleObject)]
+struct Anniversary {
+    /// The day of the anniversary (`YYYY-MM-DD`)
+    date: String,
+    /// The day the entry was written (`YYYY-MM-DD`)
+    written_on: String,
+    years_ago: u32,
+    entry: EntryHeader,
+}
+
+/// Number of entries in a day, week (labelled by its Monday) or month
+#[derive(async_graphql::SimpleObject)]
+struct PeriodCount {
+    period: String,
+    count: u64,
+}
+
+/// How often a word or tag is used
+#[derive(async_graphql::SimpleObject)]
+struct TermCount {
+    term: String,
+    count: u64,
+}
+
+/// One cell of the calendar heatmap
+#[derive(async_graphql::SimpleObject)]
+struct HeatmapDay {
+    date: String,
+    count: u64,
+    /// Intensity from 0 (no entries) to 4 (busiest days)
+    level: u8,
+}
+
+/// Writing statistics for the diary
+#[derive(async_graphql::SimpleObject)]
+struct DiaryStats {
+    total_entries: u64,
+    total_words: u64,
+    average_words: f64,
+    current_streak: u64,
+    longest_streak: u64,
+    per_day: Vec<PeriodCount>,
+    per_week: Vec<PeriodCount>,
+    per_month: Vec<PeriodCount>,
+    top_words: Vec<TermCount>,
+    top_tags: Vec<TermCount>,
+    heatmap: Vec<HeatmapDay>,
+}
+
+impl DiaryStats {
+    /// Build the statistics from the per-day counts (ordered by day), keeping the
+    /// latest `max_periods` days, weeks and months
+    fn new(
+        daily: &[(i64, u64)],
+        total_words: u64,
+        top_words: Vec<(String, u64)>,
+        top_tags: Vec<(String, u64)>,
+        today: i64,
+        heatmap_days: i64,
+        max_periods: usize,
+    ) -> Self {
+        let total_entries: u64 = daily.iter().map(|(_, count)| count).sum();
+
+        let mut longest_streak = 0;
+        let mut run = 0;
+        let mut previous: Option<i64> = None;
+        for (day, _) in daily {
+            run = if previous == Some(day - 1) { run + 1 } else { 1 };
+            longest_streak = longest_streak.max(run);
+            previous = Some(*day);
+        }
+        // The current streak is still alive if the last entry was written today or yesterday
+        let current_streak = match previous {
+            Some(last) if last >= today - 1 => run,
+            _ => 0,
+        };
+
+        let group = |label: &dyn Fn(i64) -> String| {
+            let mut periods: Vec<PeriodCount> = Vec::new();
+            for (day, count) in daily {
+                let period = label(*day);
+                match periods.last_mut() {
+                    Some(last) if last.period == period => last.count += count,
+                    _ => periods.push(PeriodCount {
+                        period,
+                        count: *count,
+                    }),
+                }
+            }
+            periods.split_off(periods.len().saturating_sub(max_periods))
+        };
+
+        let busiest = daily.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        let heatmap = (today - heatmap_days + 1..=today)
+            .map(|day| {
+                let count = daily
+                    .binary_search_by_key(&day, |(d, _)| *d)
+                    .map(|i| daily[i].1)
+                    .unwrap_or(0);
+                let level = if count == 0 {
+                    0
+                } else {
+                    (1 + 3 * (count - 1) / (busiest - 1).max(1)).min(4) as u8
+                };
+                HeatmapDay {
+                    date: calendar::format_date(day),
+                    count,
+                    level,
+                }
+            })
+            .collect();
+
+        let terms = |counts: Vec<(String, u64)>| {
+            counts
+                .into_iter()
+                .map(|(term, count)| TermCount { term, count })
+                .collect()
+        };
+
+        DiaryStats {
+            total_entries,
+            total_words,
+            average_words: if total_entries == 0 {
+                0.0
+            } else {
+                total_words as f64 / total_entries as f64
+            },
+            current_streak,
+            longest_streak,
+            per_day: group(&calendar::format_date),
+            per_week: group(&|day| calendar::format_date(calendar::week_start(day))),
+            per_month: group(&calendar::format_month),
+            top_words: terms(top_words),
+            top_tags: terms(top_tags),
+            heatmap,
+        }
+    }
 }
@@ -1,8 +1,23 @@
 // Copyright (c) Linera.
 // SPDX-License-Identifier: Apache-2.0
 
-use linera_sdk::views::{linera_views, MapView, RegisterView, RootView, ViewStorageContext};
-use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ops::Range};
+
+pub use diary_backend::{
+    Attachment, DiaryEntry, Draft, EntryHeader, Notebook, OwnerTransfer, OwnershipEvent, Template,
+};
+use diary_backend::{
+    calendar, content_excerpt,
+    patch::{EditSpan, RevisionEdits, MAX_REBASE_REVISIONS},
+    storage::{self, ChunkRef, EntryV1, StoredBody, StoredHeader, SCHEMA_VERSION},
+    text,
+    upgrade::{DiaryHeader, ExportProgress, ExportedEntry},
+    EnergyLevel, EntryMetadata, Mood, Visibility, EXCERPT_LENGTH,
+};
+use linera_sdk::{
+    linera_base_types::ApplicationId,
+    views::{linera_views, MapView, RegisterView, RootView, SetView, ViewError, ViewStorageContext},
+};
 
 /// The application state stored on-chain.
 #[derive(RootView, async_graphql::SimpleObject)]
@@ -14,28 +29,147 @@ pub struct DiaryState {
     /// Owner identifier (stored as string)
     pub owner: RegisterView<String>,
     
-    /// Map of diary entries (entry_id -> DiaryEntry)
+    /// Entries written before entries were versioned (entry_id -> EntryV1),
+    /// moved to `headers` and `bodies` by the schema version 2 migration
     #[graphql(skip)]
-    pub entries: MapView<u64, DiaryEntry>,
+    pub legacy_entries: MapView<u64, EntryV1>,
     
     /// Entry counter
     pub entry_count: RegisterView<u64>,
+
+    /// Map of notebooks (notebook_id -> Notebook)
+    #[graphql(skip)]
+    pub notebooks: MapView<u64, Notebook>,
+
+    /// Notebook counter
+    pub notebook_count: RegisterView<u64>,
+
+    /// Entries created per notebook and quarter hour ((notebook_id, UTC slot since the
+    /// epoch) -> count), grouped into days in the owner's timezone when queried
+    #[graphql(skip)]
+    pub slot_counts: MapView<(u64, i64), u64>,
+
+    /// Total number of words per notebook (notebook_id -> words)
+    #[graphql(skip)]
+    pub total_words: MapView<u64, u64>,
+
+    /// Occurrences of each significant word per notebook
+    #[graphql(skip)]
+    pub word_counts: MapView<(u64, String), u64>,
+
+    /// Number of entries using each tag per notebook
+    #[graphql(skip)]
+    pub tag_counts: MapView<(u64, String), u64>,
+
+    /// The owner's timezone as an offset from UTC in minutes, used for calendar days
+    pub utc_offset_minutes: RegisterView<i32>,
+
+    /// Unpublished drafts (draft_id -> Draft), never listed with the entries
+    #[graphql(skip)]
+    pub drafts: MapView<u64, Draft>,
+
+    /// Draft counter
+    pub draft_count: RegisterView<u64>,
+
+    /// Entry templates (template_id -> Template)
+    #[graphql(skip)]
+    pub templates: MapView<u64, Template>,
+
+    /// Template counter
+    pub template_count: RegisterView<u64>,
+
+    /// Entries linked from an entry's content with `[[entry:42]]` (source -> targets)
+    #[graphql(skip)]
+    pub content_links: MapView<u64, Vec<u64>>,
+
+    /// Entries linked with `LinkEntries` (source -> targets)
+    #[graphql(skip)]
+    pub explicit_links: MapView<u64, Vec<u64>>,
+
+    /// Reverse index of both kinds of links (target -> sources)
+    #[graphql(skip)]
+    pub backlinks: MapView<u64, Vec<u64>>,
+
+    /// Pinned entry IDs, in display order
+    pub pinned: RegisterView<Vec<u64>>,
+
+    /// Favorite entry IDs
+    #[graphql(skip)]
+    pub favorites: SetView<u64>,
+
+    /// Entries using each tag (tag -> entry IDs), used by the query planner
+    #[graphql(skip)]
+    pub tag_index: MapView<String, Vec<u64>>,
+
+    /// Entries created on each UTC day (day -> entry IDs), used by the query planner
+    #[graphql(skip)]
+    pub creation_days: MapView<i64, Vec<u64>>,
+
+    /// Hash of the phrase readers give to see `Members` entries; anyone sees them without one
+    #[graphql(skip)]
+    pub members_phrase_hash: RegisterView<Option<String>>,
+
+    /// Public entries outside of protected notebooks, read by the public API
+    #[graphql(skip)]
+    pub public_entries: SetView<u64>,
+
+    /// Schema version of the stored state, 0 for state written before versioning
+    pub schema_version: RegisterView<u32>,
+
+    /// Entry headers, read by list queries (entry_id -> StoredHeader)
+    #[graphql(skip)]
+    pub headers: MapView<u64, StoredHeader>,
+
+    /// Entry contents, only read for whole entries (entry_id -> StoredBody)
+    #[graphql(skip)]
+    pub bodies: MapView<u64, StoredBody>,
+
+    /// Progress of the export to a successor application; the diary is read-only once set
+    #[graphql(skip)]
+    pub export: RegisterView<Option<ExportProgress>>,
+
+    /// The predecessor application while its export is being imported
+    #[graphql(skip)]
+    pub importing_from: RegisterView<Option<ApplicationId>>,
+
+    /// Chunks of the contents stored as `StoredBody::Chunked` ((entry_id, chunk_id) -> StoredBody)
+    #[graphql(skip)]
+    pub body_chunks: MapView<(u64, u32), StoredBody>,
+
+    /// Ranges changed by the latest revisions of each entry, to rebase patches on
+    /// (entry_id -> oldest first, at most `MAX_REBASE_REVISIONS`)
+    #[graphql(skip)]
+    pub edit_history: MapView<u64, Vec<RevisionEdits>>,
+
+    /// Owner transfer waiting to be accepted by the new owner
+    #[graphql(skip)]
+    pub pending_owner_transfer: RegisterView<Option<OwnerTransfer>>,
+
+    /// Every proposed, cancelled and accepted owner transfer, oldest first
+    #[graphql(skip)]
+    pub ownership_log: RegisterView<Vec<OwnershipEvent>>,
+
+    /// Signer that created the application, the only one who can register a predecessor
+    #[graphql(skip)]
+    pub creator: RegisterView<Option<String>>,
 }
 
-/// A single diary entry
-#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
-pub struct DiaryEntry {
-    /// Entry ID
-    pub id: u64,
-    
-    /// Entry title
-    pub title: String,
-    
-    /// Entry content
-    pub content: String,
-    
-    /// Timestamp (milliseconds)
-    pub timestamp: u64,
+/// The notebook every diary starts with and that entries go to by default
+pub const DEFAULT_NOTEBOOK_ID: u64 = 0;
+
+/// Time ranges spanning fewer days than this are planned by reading each day of
+/// `creation_days`; longer ones read the list of days that have entries first
+const MAX_PLANNED_DAYS: i64 = 62;
+
+/// Part of an entry's content read for an edit by [`DiaryState::read_span`]
+#[derive(Debug, Clone)]
+pub struct ContentSpan {
+    /// Offset of `text` in the content, in bytes
+    pub start: usize,
+    /// The text of the span
+    pub text: String,
+    /// The chunks of a chunked body and the range of them the span covers
+    chunks: Option<(Vec<ChunkRef>, Range<usize>)>,
 }
 
 impl DiaryState {
@@ -49,45 +183,1227 @@ impl DiaryState {
         self.owner.get().clone()
     }
 
-    /// Get the entry count
-    pub fn get_entry_count(&self) -> u64 {
-        *self.entry_count.get()
+    /// Get the number of entries that exist, in either layout while a migration is in
+    /// progress. Unlike `entry_count`, the next entry ID, deleted entries are not counted.
+    pub async fn get_entry_count(&self) -> u64 {
+        let stored = self.headers.count().await.unwrap_or_default();
+        let legacy = self.legacy_entries.count().await.unwrap_or_default();
+        (stored + legacy) as u64
     }
 
     /// Get a specific entry by ID
     pub async fn get_entry(&self, id: u64) -> Option<DiaryEntry> {
-        self.entries.get(&id).await.ok().flatten()
+        self.read_entry(id).await.ok().flatten()
     }
 
-    /// Get all entries (for GraphQL queries)
-    pub async fn get_all_entries(&self) -> Vec<DiaryEntry> {
+    /// Get the header of an entry by ID
+    pub async fn get_header(&self, id: u64) -> Option<EntryHeader> {
+        self.read_header(id).await.ok().flatten()
+    }
+
+    /// Read an entry in whichever layout it was stored
+    pub async fn read_entry(&self, id: u64) -> Result<Option<DiaryEntry>, ViewError> {
+        let Some(header) = self.headers.get(&id).await? else {
+            return self.read_legacy_entry(id).await;
+        };
+        let content = match self.bodies.get(&id).await? {
+            Some(body) => self.read_body(id, body).await?,
+            None => String::new(),
+        };
+        Ok(Some(DiaryEntry::from_parts(header.into_header(), content)))
+    }
+
+    /// Read the header of an entry without its content
+    pub async fn read_header(&self, id: u64) -> Result<Option<EntryHeader>, ViewError> {
+        if let Some(header) = self.headers.get(&id).await? {
+            return Ok(Some(header.into_header()));
+        }
+        Ok(self
+            .read_legacy_entry(id)
+            .await?
+            .map(|entry| EntryHeader::from(&entry)))
+    }
+
+    /// Read the content of an entry
+    pub async fn read_content(&self, id: u64) -> Result<Option<String>, ViewError> {
+        if let Some(body) = self.bodies.get(&id).await? {
+            return Ok(Some(self.read_body(id, body).await?));
+        }
+        Ok(self.read_legacy_entry(id).await?.map(|entry| entry.content))
+    }
+
+    /// The text of a body, reading the chunks of a chunked body one after the other
+    async fn read_body(&self, id: u64, body: StoredBody) -> Result<String, ViewError> {
+        let StoredBody::Chunked(chunks) = body else {
+            return Ok(body.into_content());
+        };
+        let len = chunks.iter().map(|chunk| chunk.len as usize).sum();
+        let mut content = String::with_capacity(len);
+        for chunk in chunks {
+            if let Some(text) = self.body_chunks.get(&(id, chunk.id)).await? {
+                content.push_str(&text.into_content());
+            }
+        }
+        Ok(content)
+    }
+
+    /// Read an entry the migration has not moved yet
+    async fn read_legacy_entry(&self, id: u64) -> Result<Option<DiaryEntry>, ViewError> {
+        if self.get_schema_version() >= SCHEMA_VERSION {
+            return Ok(None);
+        }
+        Ok(self.legacy_entries.get(&id).await?.map(DiaryEntry::from))
+    }
+
+    /// Store an entry in the current layout. Entries waiting for the migration are
+    /// migrated with [`Self::migrate_entry`] before they are changed.
+    pub async fn write_entry(&mut self, entry: DiaryEntry) -> Result<(), ViewError> {
+        let id = entry.id;
+        self.headers.insert(&id, EntryHeader::from(&entry).into())?;
+        self.write_body(id, &entry.content).await
+    }
+
+    /// Record the ranges the latest revision of an entry changed
+    pub async fn record_revision(
+        &mut self,
+        id: u64,
+        revision: u64,
+        spans: Vec<EditSpan>,
+    ) -> Result<(), ViewError> {
+        let mut history = self.edit_history.get(&id).await?.unwrap_or_default();
+        history.push(RevisionEdits { revision, spans });
+        let excess = history.len().saturating_sub(MAX_REBASE_REVISIONS);
+        history.drain(..excess);
+        self.edit_history.insert(&id, history)
+    }
+
+    /// The ranges changed by the latest revisions of an entry, oldest first
+    pub async fn get_edit_history(&self, id: u64) -> Vec<RevisionEdits> {
+        self.edit_history.get(&id).await.ok().flatten().unwrap_or_default()
+    }
+
+    /// Remove a migrated entry
+    pub async fn remove_entry(&mut self, id: u64) -> Result<(), ViewError> {
+        self.headers.remove(&id)?;
+        self.edit_history.remove(&id)?;
+        self.remove_chunks(id).await?;
+        self.bodies.remove(&id)
+    }
+
+    /// Store the content of an entry, split into chunks if it is longer than `CHUNK_SIZE`
+    async fn write_body(&mut self, id: u64, content: &str) -> Result<(), ViewError> {
+        self.remove_chunks(id).await?;
+        if content.len() <= storage::CHUNK_SIZE {
+            return self.bodies.insert(&id, content.to_string().into());
+        }
+
+        let mut chunks = Vec::new();
+        for (chunk_id, text) in (0..).zip(storage::split_chunks(content)) {
+            chunks.push(self.write_chunk(id, chunk_id, text)?);
+        }
+        self.bodies.insert(&id, StoredBody::Chunked(chunks))
+    }
+
+    /// Store one chunk of an entry's body and return the reference to it
+    fn write_chunk(&mut self, id: u64, chunk_id: u32, text: &str) -> Result<ChunkRef, ViewError> {
+        self.body_chunks.insert(&(id, chunk_id), text.to_string().into())?;
+        Ok(ChunkRef::new(chunk_id, text))
+    }
+
+    /// Remove the chunks of an entry's body, if it is chunked
+    async fn remove_chunks(&mut self, id: u64) -> Result<(), ViewError> {
+        if let Some(StoredBody::Chunked(chunks)) = self.bodies.get(&id).await? {
+            for chunk in chunks {
+                self.body_chunks.remove(&(id, chunk.id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Length of a migrated entry's content in bytes, without decompressing it or reading
+    /// its chunks
+    pub async fn content_len(&self, id: u64) -> Result<usize, ViewError> {
+        Ok(self
+            .bodies
+            .get(&id)
+            .await?
+            .map_or(0, |body| body.content_len()))
+    }
+
+    /// Read the part of a migrated entry's content that an edit of the bytes from `start`
+    /// to `end` changes: the chunks the range overlaps, or the whole content if it is not
+    /// chunked. A range ending where a chunk ends also takes the next chunk, whose first
+    /// word the edit may join.
+    pub async fn read_span(
+        &self,
+        id: u64,
+        start: usize,
+        end: usize,
+    ) -> Result<ContentSpan, ViewError> {
+        let body = self.bodies.get(&id).await?;
+        let Some(StoredBody::Chunked(chunks)) = body else {
+            let text = body.map(StoredBody::into_content).unwrap_or_default();
+            return Ok(ContentSpan {
+                start: 0,
+                text,
+                chunks: None,
+            });
+        };
+
+        let mut first = None;
+        let mut last = chunks.len();
+        let mut span_start = 0;
+        let mut offset = 0;
+        for (index, chunk) in chunks.iter().enumerate() {
+            let chunk_end = offset + chunk.len as usize;
+            if first.is_none() && (start < chunk_end || index + 1 == chunks.len()) {
+                first = Some(index);
+                span_start = offset;
+            }
+            if first.is_some() && end < chunk_end {
+                last = index + 1;
+                break;
+            }
+            offset = chunk_end;
+        }
+        let first = first.unwrap_or(0);
+
+        let mut text = String::new();
+        for chunk in &chunks[first..last] {
+            if let Some(body) = self.body_chunks.get(&(id, chunk.id)).await? {
+                text.push_str(&body.into_content());
+            }
+        }
+        Ok(ContentSpan {
+            start: span_start,
+            text,
+            chunks: Some((chunks, first..last)),
+        })
+    }
+
+    /// Replace the text of a span read with [`Self::read_span`] with `edited`, rewriting
+    /// only the chunks of the span. The word statistics and the tag index are adjusted from
+    /// the removed and inserted text, and so are the word count, tags and excerpt of
+    /// `header`, which is left for the caller to store with [`Self::write_header`]. Returns
+    /// the entry IDs the new content links to, whether they exist or not.
+    ///
+    /// Private entries are left out of the statistics, so only the other ones are edited
+    /// this way.
+    pub async fn write_span(
+        &mut self,
+        header: &mut EntryHeader,
+        span: ContentSpan,
+        edited: String,
+    ) -> Result<Vec<u64>, ViewError> {
+        let id = header.id;
+        self.index_words(header.notebook_id, &span.text, &edited).await?;
+        header.word_count = (header.word_count + text::word_count(&edited))
+            .saturating_sub(text::word_count(&span.text));
+
+        let (tags, links) = match span.chunks {
+            Some((mut chunks, range)) => {
+                let touched = chunks[range.clone()].to_vec();
+                let mut next_id = chunks.iter().map(|chunk| chunk.id + 1).max().unwrap_or(0);
+                let mut replacement = Vec::new();
+                for (index, text) in storage::split_chunks(&edited).into_iter().enumerate() {
+                    // Reuse the keys of the chunks replaced, then take keys no other chunk uses
+                    let chunk_id = match touched.get(index) {
+                        Some(chunk) => chunk.id,
+                        None => {
+                            next_id += 1;
+                            next_id - 1
+                        }
+                    };
+                    replacement.push(self.write_chunk(id, chunk_id, text)?);
+                }
+                for chunk in touched.iter().skip(replacement.len()) {
+                    self.body_chunks.remove(&(id, chunk.id))?;
+                }
+                let following = range.start + replacement.len();
+                chunks.splice(range.clone(), replacement);
+
+                if range.start == 0 {
+                    header.excerpt = self.chunked_excerpt(id, edited, &chunks[following..]).await?;
+                }
+                let mut tags: Vec<String> = Vec::new();
+                let mut links: Vec<u64> = Vec::new();
+                for chunk in &chunks {
+                    for tag in &chunk.tags {
+                        if !tags.contains(tag) {
+                            tags.push(tag.clone());
+                        }
+                    }
+                    for link in &chunk.links {
+                        if !links.contains(link) {
+                            links.push(*link);
+                        }
+                    }
+                }
+                self.bodies.insert(&id, StoredBody::Chunked(chunks))?;
+                (tags, links)
+            }
+            None => {
+                header.excerpt = content_excerpt(&edited);
+                let tags = text::extract_tags(&edited);
+                let links = text::extract_entry_links(&edited);
+                // Chunks the content if it grew past `CHUNK_SIZE`
+                self.write_body(id, &edited).await?;
+                (tags, links)
+            }
+        };
+
+        for tag in header.tags.iter().filter(|tag| !tags.contains(tag)) {
+            self.index_tag(id, header.notebook_id, tag, false).await?;
+        }
+        for tag in tags.iter().filter(|tag| !header.tags.contains(tag)) {
+            self.index_tag(id, header.notebook_id, tag, true).await?;
+        }
+        header.tags = tags;
+        Ok(links)
+    }
+
+    /// Store the header of an entry whose content was written with [`Self::write_span`]
+    pub fn write_header(&mut self, header: EntryHeader) -> Result<(), ViewError> {
+        self.headers.insert(&header.id, header.into())
+    }
+
+    /// The excerpt of a chunked content starting with `text`, reading as many of the
+    /// `following` chunks as it takes to fill it
+    async fn chunked_excerpt(
+        &self,
+        id: u64,
+        mut text: String,
+        following: &[ChunkRef],
+    ) -> Result<String, ViewError> {
+        for chunk in following {
+            if text.chars().filter(|c| !c.is_whitespace()).count() > EXCERPT_LENGTH {
+                break;
+            }
+            if let Some(body) = self.body_chunks.get(&(id, chunk.id)).await? {
+                text.push_str(&body.into_content());
+            }
+        }
+        Ok(content_excerpt(&text))
+    }
+
+    /// Schema version of the stored state; state written before versioning is version 1
+    pub fn get_schema_version(&self) -> u32 {
+        (*self.schema_version.get()).max(1)
+    }
+
+    /// Create the notebook every diary starts with
+    pub fn create_default_notebook(&mut self, created_at: u64) -> Result<(), ViewError> {
+        let notebook = Notebook {
+            id: DEFAULT_NOTEBOOK_ID,
+            name: "Diary".to_string(),
+            archived: false,
+            secret_phrase_hash: None,
+            created_at,
+        };
+        self.notebooks.insert(&DEFAULT_NOTEBOOK_ID, notebook)?;
+        self.notebook_count.set(DEFAULT_NOTEBOOK_ID + 1);
+        Ok(())
+    }
+
+    /// Bring the stored state up to `SCHEMA_VERSION`, moving at most `budget` entries.
+    /// `now` is the creation time of the notebooks the migration has to create.
+    ///
+    /// Returns whether the state is up to date. Otherwise the next call continues
+    /// where this one stopped, and reads see both layouts in the meantime.
+    pub async fn migrate(&mut self, budget: usize, now: u64) -> Result<bool, ViewError> {
+        if self.get_schema_version() >= SCHEMA_VERSION {
+            return Ok(true);
+        }
+        if !self.is_initialized() {
+            // Nothing stored yet: `Initialize` writes the current layout
+            self.schema_version.set(SCHEMA_VERSION);
+            return Ok(true);
+        }
+        if !self.notebooks.contains_key(&DEFAULT_NOTEBOOK_ID).await? {
+            self.create_default_notebook(now)?;
+        }
+
+        let mut ids = Vec::new();
+        self.legacy_entries
+            .for_each_index_while(|id| {
+                ids.push(id);
+                Ok(ids.len() < budget)
+            })
+            .await?;
+        for id in ids {
+            self.migrate_entry(id).await?;
+        }
+
+        if self.legacy_entries.count().await? > 0 {
+            return Ok(false);
+        }
+        self.schema_version.set(SCHEMA_VERSION);
+        Ok(true)
+    }
+
+    /// Move an entry written before versioning into `headers` and `bodies` and add it to
+    /// the statistics and indexes, which were only kept from schema version 2 on. Does
+    /// nothing for entries already migrated.
+    pub async fn migrate_entry(&mut self, id: u64) -> Result<(), ViewError> {
+        if self.get_schema_version() >= SCHEMA_VERSION {
+            return Ok(());
+        }
+        if let Some(legacy) = self.legacy_entries.get(&id).await? {
+            let entry = DiaryEntry::from(legacy);
+            self.index_entry(&entry, true).await?;
+            self.write_entry(entry).await?;
+            self.legacy_entries.remove(&id)?;
+        }
+        Ok(())
+    }
+
+    /// Get the headers of all entries (for GraphQL queries)
+    pub async fn get_all_headers(&self) -> Vec<EntryHeader> {
         let mut entries = Vec::new();
         let count = *self.entry_count.get();
         
         for id in 0..count {
-            if let Some(entry) = self.entries.get(&id).await.ok().flatten() {
+            if let Some(entry) = self.get_header(id).await {
                 entries.push(entry);
             }
         }
         
-        // Sort by timestamp descending (newest first)
-        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        // Newest first by creation time, later IDs first on ties
+        entries.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
         entries
     }
 
-    /// Get the latest N entries
-    pub async fn get_latest_entries(&self, limit: usize) -> Vec<DiaryEntry> {
-        let mut entries = self.get_all_entries().await;
-        entries.truncate(limit);
-        entries
+    /// Get a specific notebook by ID
+    pub async fn get_notebook(&self, id: u64) -> Option<Notebook> {
+        self.notebooks.get(&id).await.ok().flatten()
     }
 
-    /// Get entries within a time range
-    pub async fn get_entries_in_range(&self, start: u64, end: u64) -> Vec<DiaryEntry> {
-        let all_entries = self.get_all_entries().await;
-        all_entries
+    /// Get all notebooks, ordered by ID
+    pub async fn get_all_notebooks(&self) -> Vec<Notebook> {
+        let mut notebooks = Vec::new();
+        let count = *self.notebook_count.get();
+
+        for id in 0..count {
+            if let Some(notebook) = self.get_notebook(id).await {
+                notebooks.push(notebook);
+            }
+        }
+
+        notebooks
+    }
+
+    /// Get the headers of all entries of one notebook (newest first)
+    pub async fn get_notebook_headers(&self, notebook_id: u64) -> Vec<EntryHeader> {
+        self.get_all_headers()
+            .await
             .into_iter()
-            .filter(|e| e.timestamp >= start && e.timestamp <= end)
+            .filter(|e| e.notebook_id == notebook_id)
             .collect()
     }
+
+    /// Get the headers of all entries from notebooks without their own secret phrase
+    /// (newest first)
+    pub async fn get_unprotected_headers(&self) -> Vec<EntryHeader> {
+        let protected: Vec<u64> = self
+            .get_all_notebooks()
+            .await
+            .into_iter()
+            .filter(|n| n.secret_phrase_hash.is_some())
+            .map(|n| n.id)
+            .collect();
+
+        self.get_all_headers()
+            .await
+            .into_iter()
+            .filter(|e| !protected.contains(&e.notebook_id))
+            .collect()
+    }
+
+    /// Get a specific draft by ID
+    pub async fn get_draft(&self, id: u64) -> Option<Draft> {
+        self.drafts.get(&id).await.ok().flatten()
+    }
+
+    /// Get all drafts, most recently saved first
+    pub async fn get_all_drafts(&self) -> Vec<Draft> {
+        let mut drafts = Vec::new();
+        let count = *self.draft_count.get();
+
+        for id in 0..count {
+            if let Some(draft) = self.get_draft(id).await {
+                drafts.push(draft);
+            }
+        }
+
+        drafts.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        drafts
+    }
+
+    /// Get a specific template by ID
+    pub async fn get_template(&self, id: u64) -> Option<Template> {
+        self.templates.get(&id).await.ok().flatten()
+    }
+
+    /// Get all templates, ordered by ID
+    pub async fn get_all_templates(&self) -> Vec<Template> {
+        let mut templates = Vec::new();
+        let count = *self.template_count.get();
+
+        for id in 0..count {
+            if let Some(template) = self.get_template(id).await {
+                templates.push(template);
+            }
+        }
+
+        templates
+    }
+
+    /// IDs of the entries an entry links to, by content or explicitly
+    pub async fn get_linked_ids(&self, id: u64) -> Vec<u64> {
+        let mut targets = self
+            .content_links
+            .get(&id)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let explicit = self
+            .explicit_links
+            .get(&id)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        for target in explicit {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        targets
+    }
+
+    /// IDs of the entries linking to an entry
+    pub async fn get_backlink_ids(&self, id: u64) -> Vec<u64> {
+        self.backlinks
+            .get(&id)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    /// Replace the content links and/or explicit links of `source`
+    /// (`None` leaves that kind unchanged) and keep the backlinks in sync
+    pub async fn set_links(
+        &mut self,
+        source: u64,
+        content_targets: Option<Vec<u64>>,
+        explicit_targets: Option<Vec<u64>>,
+    ) -> Result<(), ViewError> {
+        let old_targets = self.get_linked_ids(source).await;
+
+        for (map, targets) in [
+            (&mut self.content_links, content_targets),
+            (&mut self.explicit_links, explicit_targets),
+        ] {
+            match targets {
+                Some(targets) if targets.is_empty() => map.remove(&source)?,
+                Some(targets) => map.insert(&source, targets)?,
+                None => {}
+            }
+        }
+
+        let new_targets = self.get_linked_ids(source).await;
+
+        for target in old_targets.iter().filter(|t| !new_targets.contains(t)) {
+            let mut sources = self.get_backlink_ids(*target).await;
+            sources.retain(|s| *s != source);
+            if sources.is_empty() {
+                self.backlinks.remove(target)?;
+            } else {
+                self.backlinks.insert(target, sources)?;
+            }
+        }
+        for target in new_targets.iter().filter(|t| !old_targets.contains(t)) {
+            let mut sources = self.get_backlink_ids(*target).await;
+            sources.push(source);
+            self.backlinks.insert(target, sources)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop every link from and to a deleted entry. Links to it written in the content of
+    /// other entries stay in their text, but no longer count.
+    pub async fn remove_links(&mut self, id: u64) -> Result<(), ViewError> {
+        self.set_links(id, Some(Vec::new()), Some(Vec::new())).await?;
+
+        for source in self.get_backlink_ids(id).await {
+            for map in [&mut self.content_links, &mut self.explicit_links] {
+                let Some(mut targets) = map.get(&source).await? else {
+                    continue;
+                };
+                targets.retain(|target| *target != id);
+                if targets.is_empty() {
+                    map.remove(&source)?;
+                } else {
+                    map.insert(&source, targets)?;
+                }
+            }
+        }
+        self.backlinks.remove(&id)
+    }
+
+    /// Move pinned entries to the front, in pinned order, keeping the order of the rest
+    pub fn pinned_first(&self, entries: Vec<EntryHeader>) -> Vec<EntryHeader> {
+        let pinned = self.pinned.get();
+        let (mut first, rest): (Vec<EntryHeader>, Vec<EntryHeader>) =
+            entries.into_iter().partition(|e| pinned.contains(&e.id));
+        first.sort_by_key(|e| pinned.iter().position(|id| *id == e.id));
+        first.extend(rest);
+        first
+    }
+
+    /// Calendar day (in the owner's timezone) on which an entry was created
+    pub fn local_day_of(&self, entry: &EntryHeader) -> i64 {
+        calendar::local_day_of(entry.created_at, *self.utc_offset_minutes.get())
+    }
+
+    /// Get the headers of all public entries from notebooks without their own secret phrase
+    /// (newest first)
+    pub async fn get_public_headers(&self) -> Vec<EntryHeader> {
+        let mut entries = Vec::new();
+        for id in self.public_entries.indices().await.unwrap_or_default() {
+            if let Some(entry) = self.get_header(id).await {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        entries
+    }
+
+    /// Get the header of a public entry outside of protected notebooks
+    pub async fn get_public_header(&self, id: u64) -> Option<EntryHeader> {
+        if !self.public_entries.contains(&id).await.unwrap_or(false) {
+            return None;
+        }
+        self.get_header(id).await
+    }
+
+    /// Whether readers who give `phrase`, if any, may see entries of a visibility
+    pub fn can_read(&self, visibility: Visibility, phrase: Option<&str>) -> bool {
+        let hash = phrase.map(diary_backend::hash_secret_phrase);
+        match visibility {
+            Visibility::Public => true,
+            Visibility::Members => match self.members_phrase_hash.get() {
+                None => true,
+                Some(members) => hash.as_ref().is_some_and(|hash| {
+                    hash == members || hash == self.secret_phrase_hash.get()
+                }),
+            },
+            Visibility::Private => hash.as_ref() == Some(self.secret_phrase_hash.get()),
+        }
+    }
+
+    /// Everything about the diary except its entries, for a successor application
+    pub async fn export_header(&self) -> DiaryHeader {
+        DiaryHeader {
+            secret_phrase_hash: self.secret_phrase_hash.get().clone(),
+            owner: self.owner.get().clone(),
+            utc_offset_minutes: *self.utc_offset_minutes.get(),
+            pinned: self.pinned.get().clone(),
+            entry_count: *self.entry_count.get(),
+            notebooks: self.get_all_notebooks().await,
+            notebook_count: *self.notebook_count.get(),
+            drafts: self.get_all_drafts().await,
+            draft_count: *self.draft_count.get(),
+            templates: self.get_all_templates().await,
+            template_count: *self.template_count.get(),
+            members_phrase_hash: self.members_phrase_hash.get().clone(),
+            pending_owner_transfer: self.pending_owner_transfer.get().clone(),
+            ownership_log: self.ownership_log.get().clone(),
+        }
+    }
+
+    /// The entries with IDs in `ids` and their links and favorite flags, for a successor application
+    pub async fn export_entries(
+        &self,
+        ids: std::ops::Range<u64>,
+    ) -> Result<Vec<ExportedEntry>, ViewError> {
+        let mut entries = Vec::new();
+        for id in ids {
+            let Some(entry) = self.read_entry(id).await? else {
+                continue;
+            };
+            entries.push(ExportedEntry {
+                entry,
+                content_links: self.content_links.get(&id).await?.unwrap_or_default(),
+                explicit_links: self.explicit_links.get(&id).await?.unwrap_or_default(),
+                favorite: self.favorites.contains(&id).await?,
+                edit_history: self.edit_history.get(&id).await?.unwrap_or_default(),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Add an entry to the statistics and the tag index (`added = true`) or take it out again.
+    /// Called by the contract whenever an entry is created, edited or deleted.
+    pub async fn index_entry(
+        &mut self,
+        entry: &DiaryEntry,
+        added: bool,
+    ) -> Result<(), ViewError> {
+        let sign = if added { 1 } else { -1 };
+        let notebook_id = entry.notebook_id;
+
+        self.index_creation_day(entry.id, entry.created_at, added).await?;
+        self.index_public_entry(entry.id, notebook_id, entry.visibility, added)
+            .await?;
+
+        // The text of private entries is encrypted, so they are left out of the
+        // statistics altogether rather than counted without their words and tags
+        if entry.visibility == Visibility::Private {
+            return Ok(());
+        }
+
+        let slot = (notebook_id, calendar::slot_of(entry.created_at));
+        Self::adjust_count(&mut self.slot_counts, slot, sign).await?;
+
+        if added {
+            self.index_words(notebook_id, "", &entry.content).await?;
+        } else {
+            self.index_words(notebook_id, &entry.content, "").await?;
+        }
+
+        for tag in text::extract_tags(&entry.content) {
+            self.index_tag(entry.id, notebook_id, &tag, added).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the word statistics of a notebook from `removed` text to `inserted` text
+    async fn index_words(
+        &mut self,
+        notebook_id: u64,
+        removed: &str,
+        inserted: &str,
+    ) -> Result<(), ViewError> {
+        let words = text::word_count(inserted) as i64 - text::word_count(removed) as i64;
+        Self::adjust_count(&mut self.total_words, notebook_id, words).await?;
+
+        let mut occurrences: BTreeMap<String, i64> = BTreeMap::new();
+        for word in text::significant_words(removed) {
+            *occurrences.entry(word).or_default() -= 1;
+        }
+        for word in text::significant_words(inserted) {
+            *occurrences.entry(word).or_default() += 1;
+        }
+        for (word, n) in occurrences {
+            if n != 0 {
+                Self::adjust_count(&mut self.word_counts, (notebook_id, word), n).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add an entry to, or remove it from, the entries using a tag
+    async fn index_tag(
+        &mut self,
+        id: u64,
+        notebook_id: u64,
+        tag: &str,
+        added: bool,
+    ) -> Result<(), ViewError> {
+        let mut ids = self.tag_index.get(tag).await?.unwrap_or_default();
+        ids.retain(|other| *other != id);
+        if added {
+            ids.push(id);
+        }
+        if ids.is_empty() {
+            self.tag_index.remove(tag)?;
+        } else {
+            self.tag_index.insert(tag, ids)?;
+        }
+
+        let sign = if added { 1 } else { -1 };
+        Self::adjust_count(&mut self.tag_counts, (notebook_id, tag.to_string()), sign).await
+    }
+
+    /// Add an entry to, or remove it from, the entries of the day it was created.
+    /// Adding an entry that is already there keeps a single copy.
+    async fn index_creation_day(
+        &mut self,
+        id: u64,
+        created_at: u64,
+        added: bool,
+    ) -> Result<(), ViewError> {
+        let day = calendar::day_of(created_at);
+        let mut ids = self.creation_days.get(&day).await?.unwrap_or_default();
+        ids.retain(|other| *other != id);
+        if added {
+            ids.push(id);
+        }
+        if ids.is_empty() {
+            self.creation_days.remove(&day)
+        } else {
+            self.creation_days.insert(&day, ids)
+        }
+    }
+
+    /// Add a public entry outside of protected notebooks to, or remove an entry from,
+    /// the entries of the public API
+    async fn index_public_entry(
+        &mut self,
+        id: u64,
+        notebook_id: u64,
+        visibility: Visibility,
+        added: bool,
+    ) -> Result<(), ViewError> {
+        let unprotected = self
+            .notebooks
+            .get(&notebook_id)
+            .await?
+            .is_some_and(|notebook| notebook.secret_phrase_hash.is_none());
+        if added && unprotected && visibility == Visibility::Public {
+            self.public_entries.insert(&id)
+        } else {
+            self.public_entries.remove(&id)
+        }
+    }
+
+    /// IDs of the entries created between two timestamps, either of them open.
+    /// Whole days are returned, so the caller still has to check the exact times.
+    async fn created_between(
+        &self,
+        after: Option<u64>,
+        before: Option<u64>,
+    ) -> Result<Vec<u64>, ViewError> {
+        let first = after.map_or(i64::MIN, calendar::day_of);
+        let last = before.map_or(i64::MAX, calendar::day_of);
+        let days: Vec<i64> = if last.saturating_sub(first) < MAX_PLANNED_DAYS {
+            (first..=last).collect()
+        } else {
+            self.creation_days
+                .indices()
+                .await?
+                .into_iter()
+                .filter(|day| (first..=last).contains(day))
+                .collect()
+        };
+
+        let mut ids = Vec::new();
+        for day in days {
+            ids.extend(self.creation_days.get(&day).await?.unwrap_or_default());
+        }
+        Ok(ids)
+    }
+
+    /// Add `delta` to a counter, dropping it once it reaches zero
+    async fn adjust_count<K>(
+        map: &mut MapView<K, u64>,
+        key: K,
+        delta: i64,
+    ) -> Result<(), ViewError>
+    where
+        K: serde::Serialize + serde::de::DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        let count = map.get(&key).await?.unwrap_or_default();
+        let count = count.saturating_add_signed(delta);
+        if count == 0 {
+            map.remove(&key)
+        } else {
+            map.insert(&key, count)
+        }
+    }
+
+    /// Entries created per day in the owner's timezone within the given notebooks,
+    /// ordered by day
+    pub async fn get_daily_counts(&self, notebook_ids: &[u64]) -> Vec<(i64, u64)> {
+        let utc_offset_minutes = *self.utc_offset_minutes.get();
+        let slots = self.slot_counts.index_values().await.unwrap_or_default();
+        let mut counts: BTreeMap<i64, u64> = BTreeMap::new();
+        for ((notebook_id, slot), count) in slots {
+            if notebook_ids.contains(&notebook_id) {
+                *counts
+                    .entry(calendar::local_day_of_slot(slot, utc_offset_minutes))
+                    .or_default() += count;
+            }
+        }
+        counts.into_iter().collect()
+    }
+
+    /// Total number of words within the given notebooks
+    pub async fn get_total_words(&self, notebook_ids: &[u64]) -> u64 {
+        let mut total = 0;
+        for notebook_id in notebook_ids {
+            total += self.total_words.get(notebook_id).await.ok().flatten().unwrap_or_default();
+        }
+        total
+    }
+
+    /// The most used words within the given notebooks, most frequent first
+    pub async fn get_top_words(&self, notebook_ids: &[u64], limit: usize) -> Vec<(String, u64)> {
+        Self::top_counts(&self.word_counts, notebook_ids, limit).await
+    }
+
+    /// The most used tags within the given notebooks, most frequent first
+    pub async fn get_top_tags(&self, notebook_ids: &[u64], limit: usize) -> Vec<(String, u64)> {
+        Self::top_counts(&self.tag_counts, notebook_ids, limit).await
+    }
+
+    async fn top_counts(
+        map: &MapView<(u64, String), u64>,
+        notebook_ids: &[u64],
+        limit: usize,
+    ) -> Vec<(String, u64)> {
+        let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+        for ((notebook_id, term), count) in map.index_values().await.unwrap_or_default() {
+            if notebook_ids.contains(&notebook_id) {
+                *totals.entry(term).or_default() += count;
+            }
+        }
+        let mut counts: Vec<(String, u64)> = totals.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(limit);
+        counts
+    }
+
+    /// Find the headers of the entries matching a filter, sorted by `order`.
+    ///
+    /// The planner first narrows the candidates with the ID list, the creation days,
+    /// the tag index, the pinned list and the favorites where the filter allows it,
+    /// and only falls back to scanning every entry when none of them applies. Contents
+    /// are only read when the filter searches them, and only of entries `visible` accepts.
+    /// Reading stops as soon as more than `max_results` entries match, as the caller
+    /// rejects such a result anyway.
+    pub async fn query_entries(
+        &self,
+        filter: &EntryFilter,
+        order: Option<EntryOrder>,
+        visible: impl Fn(&EntryHeader) -> bool,
+        max_results: Option<usize>,
+    ) -> Result<Vec<EntryHeader>, ViewError> {
+        let favorites = if filter.uses_favorites() {
+            self.favorites.indices().await?
+        } else {
+            Vec::new()
+        };
+        let context = FilterContext {
+            pinned: self.pinned.get().clone(),
+            favorites,
+        };
+
+        // The indexes are incomplete until the migration has gone through every entry
+        let planned = if self.get_schema_version() < SCHEMA_VERSION {
+            None
+        } else {
+            self.plan_candidates(filter, &context).await?
+        };
+        let candidates = match planned {
+            Some(ids) => ids,
+            None => (0..*self.entry_count.get()).collect(),
+        };
+
+        let uses_content = filter.uses_content();
+        let mut entries = Vec::new();
+        for id in candidates {
+            if max_results.is_some_and(|max| entries.len() > max) {
+                break;
+            }
+            let Some(entry) = self.read_header(id).await? else {
+                continue;
+            };
+            if !visible(&entry) {
+                continue;
+            }
+            let content = if uses_content {
+                self.read_content(entry.id).await?.unwrap_or_default()
+            } else {
+                String::new()
+            };
+            if filter.matches(&entry, &content, &context) {
+                entries.push(entry);
+            }
+        }
+
+        match order {
+            Some(order) => order.sort(&mut entries),
+            None => {
+                EntryOrder::CreatedDesc.sort(&mut entries);
+                entries = self.pinned_first(entries);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Candidate entry IDs for a filter from the available indexes,
+    /// or `None` if every entry has to be scanned
+    async fn plan_candidates(
+        &self,
+        filter: &EntryFilter,
+        context: &FilterContext,
+    ) -> Result<Option<Vec<u64>>, ViewError> {
+        let mut candidates: Option<Vec<u64>> = None;
+        let mut narrow = |ids: Vec<u64>| {
+            candidates = Some(match candidates.take() {
+                Some(current) => current.into_iter().filter(|id| ids.contains(id)).collect(),
+                None => ids,
+            });
+        };
+
+        if let Some(ids) = &filter.ids {
+            narrow(ids.clone());
+        }
+        if filter.created_after.is_some() || filter.created_before.is_some() {
+            narrow(
+                self.created_between(filter.created_after, filter.created_before)
+                    .await?,
+            );
+        }
+        for tag in filter.tags.iter().flatten() {
+            let tag = tag.trim_start_matches('#').to_lowercase();
+            narrow(self.tag_index.get(&tag).await?.unwrap_or_default());
+        }
+        if filter.pinned == Some(true) {
+            narrow(context.pinned.clone());
+        }
+        if filter.favorite == Some(true) {
+            narrow(context.favorites.clone());
+        }
+        for child in filter.and.iter().flatten() {
+            if let Some(ids) = Box::pin(self.plan_candidates(child, context)).await? {
+                narrow(ids);
+            }
+        }
+        if let Some(children) = &filter.or {
+            // A disjunction can only use indexes if every branch can
+            let mut union: Vec<u64> = Vec::new();
+            let mut indexed = true;
+            for child in children {
+                match Box::pin(self.plan_candidates(child, context)).await? {
+                    Some(ids) => {
+                        for id in ids {
+                            if !union.contains(&id) {
+                                union.push(id);
+                            }
+                        }
+                    }
+                    None => {
+                        indexed = false;
+                        break;
+                    }
+                }
+            }
+            if indexed {
+                narrow(union);
+            }
+        }
+
+        Ok(candidates)
+    }
+}
+
+/// Pinned and favorite IDs loaded once per query
+pub struct FilterContext {
+    pub pinned: Vec<u64>,
+    pub favorites: Vec<u64>,
+}
+
+/// Filter on entry metadata; all given conditions must match
+#[derive(Debug, Clone, Default, async_graphql::InputObject)]
+pub struct MetadataFilter {
+    pub mood: Option<Mood>,
+    pub min_rating: Option<u8>,
+    pub max_rating: Option<u8>,
+    pub energy: Option<EnergyLevel>,
+    /// Only entries that have this custom field
+    pub field_key: Option<String>,
+    /// Together with `field_key`, only entries where the field has this value
+    pub field_value: Option<String>,
+}
+
+impl MetadataFilter {
+    pub fn matches(&self, metadata: &EntryMetadata) -> bool {
+        if self.mood.is_some() && metadata.mood != self.mood {
+            return false;
+        }
+        if self.energy.is_some() && metadata.energy != self.energy {
+            return false;
+        }
+        if let Some(min) = self.min_rating {
+            if metadata.rating.is_none_or(|r| r < min) {
+                return false;
+            }
+        }
+        if let Some(max) = self.max_rating {
+            if metadata.rating.is_none_or(|r| r > max) {
+                return false;
+            }
+        }
+        if let Some(ref key) = self.field_key {
+            match metadata.custom_field(key) {
+                None => return false,
+                Some(field) => {
+                    if self.field_value.as_ref().is_some_and(|v| v != &field.value) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// A composable filter on entries. All given conditions must match;
+/// `and`, `or` and `not` combine nested filters.
+#[derive(Debug, Clone, Default, async_graphql::InputObject)]
+pub struct EntryFilter {
+    /// Only these entry IDs
+    pub ids: Option<Vec<u64>>,
+    /// Created at or after this timestamp (microseconds)
+    pub created_after: Option<u64>,
+    /// Created at or before this timestamp (microseconds)
+    pub created_before: Option<u64>,
+    /// Last written at or after this timestamp (microseconds)
+    pub updated_after: Option<u64>,
+    /// Last written at or before this timestamp (microseconds)
+    pub updated_before: Option<u64>,
+    /// Title contains this text (case-insensitive)
+    pub title_contains: Option<String>,
+    /// Content contains this text (case-insensitive)
+    pub content_contains: Option<String>,
+    /// Title or content contains this text (case-insensitive)
+    pub text_contains: Option<String>,
+    /// Uses all of these tags
+    pub tags: Option<Vec<String>>,
+    /// Metadata conditions
+    pub metadata: Option<MetadataFilter>,
+    pub pinned: Option<bool>,
+    pub favorite: Option<bool>,
+    pub has_attachments: Option<bool>,
+    /// All of these filters match
+    pub and: Option<Vec<EntryFilter>>,
+    /// At least one of these filters matches
+    pub or: Option<Vec<EntryFilter>>,
+    /// This filter does not match
+    pub not: Option<Box<EntryFilter>>,
+}
+
+impl EntryFilter {
+    /// Evaluate the filter on one entry; `content` is only read if `uses_content` is true
+    pub fn matches(&self, entry: &EntryHeader, content: &str, context: &FilterContext) -> bool {
+        let contains = |haystack: &str, needle: &str| {
+            haystack.to_lowercase().contains(&needle.to_lowercase())
+        };
+
+        if self.ids.as_ref().is_some_and(|ids| !ids.contains(&entry.id)) {
+            return false;
+        }
+        if self.created_after.is_some_and(|t| entry.created_at < t)
+            || self.created_before.is_some_and(|t| entry.created_at > t)
+            || self.updated_after.is_some_and(|t| entry.timestamp < t)
+            || self.updated_before.is_some_and(|t| entry.timestamp > t)
+        {
+            return false;
+        }
+        if self.title_contains.as_ref().is_some_and(|q| !contains(&entry.title, q))
+            || self.content_contains.as_ref().is_some_and(|q| !contains(content, q))
+            || self
+                .text_contains
+                .as_ref()
+                .is_some_and(|q| !contains(&entry.title, q) && !contains(content, q))
+        {
+            return false;
+        }
+        if let Some(tags) = &self.tags {
+            if !tags
+                .iter()
+                .all(|tag| entry.tags.contains(&tag.trim_start_matches('#').to_lowercase()))
+            {
+                return false;
+            }
+        }
+        if self.metadata.as_ref().is_some_and(|m| !m.matches(&entry.metadata)) {
+            return false;
+        }
+        if self.pinned.is_some_and(|p| p != context.pinned.contains(&entry.id))
+            || self.favorite.is_some_and(|f| f != context.favorites.contains(&entry.id))
+            || self.has_attachments.is_some_and(|a| a == entry.attachments.is_empty())
+        {
+            return false;
+        }
+        if self.and.iter().flatten().any(|f| !f.matches(entry, content, context)) {
+            return false;
+        }
+        if self
+            .or
+            .as_ref()
+            .is_some_and(|fs| !fs.iter().any(|f| f.matches(entry, content, context)))
+        {
+            return false;
+        }
+        if self.not.as_ref().is_some_and(|f| f.matches(entry, content, context)) {
+            return false;
+        }
+        true
+    }
+
+    /// Whether any part of the filter searches the content
+    fn uses_content(&self) -> bool {
+        self.content_contains.is_some()
+            || self.text_contains.is_some()
+            || self.and.iter().chain(self.or.iter()).flatten().any(EntryFilter::uses_content)
+            || self.not.as_ref().is_some_and(|f| f.uses_content())
+    }
+
+    /// Whether any part of the filter refers to favorites
+    fn uses_favorites(&self) -> bool {
+        self.favorite.is_some()
+            || self.and.iter().chain(self.or.iter()).flatten().any(EntryFilter::uses_favorites)
+            || self.not.as_ref().is_some_and(|f| f.uses_favorites())
+    }
+}
+
+/// Sort order for entry queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum EntryOrder {
+    /// Most recently written first
+    UpdatedDesc,
+    UpdatedAsc,
+    /// Most recently created first
+    CreatedDesc,
+    CreatedAsc,
+    TitleAsc,
+    TitleDesc,
+    /// Highest rated first, unrated entries last
+    RatingDesc,
+}
+
+impl EntryOrder {
+    pub fn sort(self, entries: &mut [EntryHeader]) {
+        match self {
+            EntryOrder::UpdatedDesc => {
+                entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(b.id.cmp(&a.id)))
+            }
+            EntryOrder::UpdatedAsc => {
+                entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.id.cmp(&b.id)))
+            }
+            EntryOrder::CreatedDesc => {
+                entries.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)))
+            }
+            EntryOrder::CreatedAsc => {
+                entries.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)))
+            }
+            EntryOrder::TitleAsc => {
+                entries.sort_by_key(|e| e.title.to_lowercase());
+            }
+            EntryOrder::TitleDesc => {
+                entries.sort_by_key(|e| std::cmp::Reverse(e.title.to_lowercase()));
+            }
+            EntryOrder::RatingDesc => {
+                entries.sort_by_key(|e| std::cmp::Reverse(e.metadata.rating));
+            }
+        }
+    }
 }
@@ -0,0 +1,235 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Versioned encodings of the values stored on-chain.
+//!
+//! Entries are stored in two parts: a [`StoredHeader`] with everything list queries need,
+//! and a [`StoredBody`] with the content, only read when the whole entry is. Headers are
+//! tagged with the layout they were written in, so fields can be added to [`EntryHeader`]
+//! without breaking the headers already stored. To change the header layout:
+//!
+//! 1. copy the current fields of `EntryHeader` into a frozen `HeaderV<n>` struct and make
+//!    `StoredHeader::V<n>` hold it instead of `EntryHeader`,
+//! 2. add a `V<n+1>(EntryHeader)` variant and convert `HeaderV<n>` in
+//!    [`StoredHeader::into_header`].
+//!
+//! Nested types such as [`EntryMetadata`] are shared between layouts until one of them
+//! changes, and are frozen the same way then. Bodies are tagged with their codec the same
+//! way: contents longer than [`COMPRESSION_THRESHOLD`] are compressed when written, and
+//! decompressed whenever they are read. Contents longer than [`CHUNK_SIZE`] are split into
+//! chunks stored apart, each one a body of its own, and the entry's body only lists them
+//! with their tags and links, so that edits read and rewrite the chunks they touch rather
+//! than the whole content.
+//!
+//! BCS encodes variants by position, so variants are never removed or reordered.
+//!
+//! Changes to the layout of the state itself, such as moving data between views, bump
+//! [`SCHEMA_VERSION`] and extend `DiaryState::migrate`, which the contract runs whenever it
+//! loads the state.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{text, DiaryEntry, EntryHeader, EntryMetadata, Visibility};
+
+/// Schema version of the state written by this version of the contract.
+///
+/// - 1: entries stored untagged, as [`EntryV1`] (state written before versioning)
+/// - 2: entries stored as a [`StoredHeader`] and a [`StoredBody`], with the default notebook
+///   and the indexes
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Contents longer than this many bytes are stored compressed
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Contents longer than this many bytes are split into chunks of at most this many bytes
+pub const CHUNK_SIZE: usize = 8 * 1024;
+
+/// An entry header together with the layout it was written in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StoredHeader {
+    V1(EntryHeader),
+}
+
+impl StoredHeader {
+    /// Layout version of the stored header
+    pub fn version(&self) -> u32 {
+        match self {
+            StoredHeader::V1(_) => 1,
+        }
+    }
+
+    /// The header in the current layout
+    pub fn into_header(self) -> EntryHeader {
+        match self {
+            StoredHeader::V1(header) => header,
+        }
+    }
+}
+
+impl From<EntryHeader> for StoredHeader {
+    fn from(header: EntryHeader) -> Self {
+        StoredHeader::V1(header)
+    }
+}
+
+/// The content of an entry together with its codec
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StoredBody {
+    /// UTF-8 text as written
+    Plain(String),
+    /// UTF-8 text compressed with LZ4, prefixed with its uncompressed size
+    Lz4(Vec<u8>),
+    /// Text split into chunks, in order; each chunk is stored apart as a `Plain` or `Lz4` body
+    Chunked(Vec<ChunkRef>),
+}
+
+/// One chunk of a chunked body, with what the indexes need to know of its text so that
+/// an edit only reads the chunks it changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRef {
+    /// Key of the chunk among the chunks of its entry
+    pub id: u32,
+    /// Length of the chunk's text in bytes
+    pub len: u32,
+    /// `#hashtag` tags used in the chunk
+    pub tags: Vec<String>,
+    /// Entry IDs referenced as `[[entry:42]]` in the chunk
+    pub links: Vec<u64>,
+}
+
+impl ChunkRef {
+    /// The reference to a chunk holding `text`
+    pub fn new(id: u32, text: &str) -> Self {
+        ChunkRef {
+            id,
+            len: text.len() as u32,
+            tags: text::extract_tags(text),
+            links: text::extract_entry_links(text),
+        }
+    }
+}
+
+impl StoredBody {
+    /// Number of bytes the body takes in storage, not counting the codec tag
+    pub fn stored_len(&self) -> usize {
+        match self {
+            StoredBody::Plain(content) => content.len(),
+            StoredBody::Lz4(bytes) => bytes.len(),
+            // The chunks themselves are stored apart
+            StoredBody::Chunked(chunks) => chunks
+                .iter()
+                .map(|chunk| {
+                    let tags: usize = chunk.tags.iter().map(String::len).sum();
+                    8 + tags + 8 * chunk.links.len()
+                })
+                .sum(),
+        }
+    }
+
+    /// Length of the content in bytes, without decompressing it or reading its chunks
+    pub fn content_len(&self) -> usize {
+        match self {
+            StoredBody::Plain(content) => content.len(),
+            // LZ4 bodies start with their uncompressed size
+            StoredBody::Lz4(bytes) => {
+                let mut size = [0; 4];
+                size.copy_from_slice(&bytes[..4]);
+                u32::from_le_bytes(size) as usize
+            }
+            StoredBody::Chunked(chunks) => chunks.iter().map(|chunk| chunk.len as usize).sum(),
+        }
+    }
+
+    /// The content as text. Chunked bodies are reassembled from their chunks by the state
+    /// instead, since the chunks are stored apart.
+    pub fn into_content(self) -> String {
+        match self {
+            StoredBody::Plain(content) => content,
+            StoredBody::Lz4(bytes) => {
+                let bytes = lz4_flex::decompress_size_prepended(&bytes)
+                    .expect("Compressed entry bodies are only written by the contract");
+                String::from_utf8(bytes)
+                    .expect("Compressed entry bodies are only written from text")
+            }
+            StoredBody::Chunked(_) => panic!("Chunked bodies are read chunk by chunk"),
+        }
+    }
+}
+
+/// Split `content` into chunks of at most [`CHUNK_SIZE`] bytes. Chunks end after
+/// whitespace and outside of `[[entry:42]]` links where possible, so that words, tags and
+/// links each lie within one chunk and the chunks can be indexed one by one. Text without
+/// whitespace for a whole chunk is split at a character boundary instead.
+pub fn split_chunks(content: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = content;
+    while rest.len() > CHUNK_SIZE {
+        let mut end = CHUNK_SIZE;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let head = &rest[..end];
+        // A link still open at the limit goes to the next chunk whole
+        let open = match head.rfind("[[") {
+            Some(open) if !head[open..].contains("]]") => open,
+            _ => end,
+        };
+        if let Some((index, c)) = head[..open]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+        {
+            end = index + c.len_utf8();
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+impl From<String> for StoredBody {
+    /// Compresses contents over [`COMPRESSION_THRESHOLD`], unless that does not save space
+    fn from(content: String) -> Self {
+        if content.len() <= COMPRESSION_THRESHOLD {
+            return StoredBody::Plain(content);
+        }
+        let compressed = lz4_flex::compress_prepend_size(content.as_bytes());
+        if compressed.len() < content.len() {
+            StoredBody::Lz4(compressed)
+        } else {
+            StoredBody::Plain(content)
+        }
+    }
+}
+
+/// Entry layout of schema version 1, stored without a version tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryV1 {
+    pub id: u64,
+    pub title: String,
+    pub content: String,
+    pub timestamp: u64,
+}
+
+impl From<EntryV1> for DiaryEntry {
+    fn from(entry: EntryV1) -> Self {
+        DiaryEntry {
+            id: entry.id,
+            title: entry.title,
+            content: entry.content,
+            timestamp: entry.timestamp,
+            // The last edit is the closest to the creation time that was kept
+            created_at: entry.timestamp,
+            // The default notebook, created by the migration
+            notebook_id: 0,
+            metadata: EntryMetadata::default(),
+            attachments: Vec::new(),
+            visibility: Visibility::default(),
+            revision: 0,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rendering of entry templates with `{{placeholder}}` substitution.
+
+use crate::calendar;
+
+/// Weekday names, starting on Monday
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Month names, starting in January
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Placeholders filled in from the current time by `builtin_values`
+pub const BUILTIN_PLACEHOLDERS: [&str; 6] = ["date", "weekday", "day", "month", "year", "time"];
+
+/// Values of the `BUILTIN_PLACEHOLDERS` at a time in the owner's timezone
+pub fn builtin_values(timestamp_micros: u64, utc_offset_minutes: i32) -> Vec<(String, String)> {
+    let day = calendar::local_day_of(timestamp_micros, utc_offset_minutes);
+    let (year, month, day_of_month) = calendar::civil_from_days(day);
+    let local_micros = timestamp_micros as i64 + i64::from(utc_offset_minutes) * 60_000_000;
+    let minutes = local_micros.rem_euclid(calendar::MICROS_PER_DAY as i64) / 60_000_000;
+
+    vec![
+        ("date".to_string(), calendar::format_date(day)),
+        (
+            "weekday".to_string(),
+            WEEKDAYS[calendar::weekday(day) as usize].to_string(),
+        ),
+        ("day".to_string(), day_of_month.to_string()),
+        ("month".to_string(), MONTHS[month as usize - 1].to_string()),
+        ("year".to_string(), year.to_string()),
+        (
+            "time".to_string(),
+            format!("{:02}:{:02}", minutes / 60, minutes % 60),
+        ),
+    ]
+}
+
+/// Reject caller values for built-in placeholders, which always come from the clock
+pub fn check_keys<'a>(keys: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+    match keys.into_iter().find(|key| BUILTIN_PLACEHOLDERS.contains(key)) {
+        Some(key) => Err(format!(
+            "'{}' is a built-in placeholder and cannot be given a value",
+            key
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The values to render a template with: the caller's followed by the built-in ones
+pub fn values_with_builtins(
+    values: Vec<(String, String)>,
+    timestamp_micros: u64,
+    utc_offset_minutes: i32,
+) -> Result<Vec<(String, String)>, String> {
+    check_keys(values.iter().map(|(key, _)| key.as_str()))?;
+
+    let mut values = values;
+    values.extend(builtin_values(timestamp_micros, utc_offset_minutes));
+    Ok(values)
+}
+
+/// Names of the placeholders used in a pattern, in order of first appearance
+pub fn placeholders(pattern: &str) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Unclosed placeholder in template".to_string())?;
+        let name = after[..end].trim();
+        if name.is_empty() {
+            return Err("Empty placeholder in template".to_string());
+        }
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+
+    Ok(names)
+}
+
+/// Replace every `{{name}}` in a pattern with the first value of that name
+pub fn render(pattern: &str, values: &[(String, String)]) -> Result<String, String> {
+    let mut output = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Unclosed placeholder in template".to_string())?;
+        let name = after[..end].trim();
+        let value = values
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("Missing value for placeholder '{}'", name))?;
+        output.push_str(value);
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
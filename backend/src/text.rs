@@ -0,0 +1,95 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Text helpers for word counts, frequent words, `#hashtag` tags and `[[entry:42]]` links.
+
+/// Common words left out of the most used words
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "all", "any", "can", "had", "her", "was",
+    "one", "our", "out", "has", "his", "how", "its", "may", "who", "did", "get", "him", "she",
+    "too", "use", "that", "with", "have", "this", "will", "your", "from", "they", "been", "were",
+    "what", "when", "then", "them", "than", "into", "just", "some", "there", "their", "about",
+    "would", "which", "could", "should",
+];
+
+/// Number of whitespace-separated words
+pub fn word_count(content: &str) -> u64 {
+    content.split_whitespace().count() as u64
+}
+
+/// The first words of the content, with whitespace collapsed, cut at a word boundary
+/// to at most `max_chars` characters and marked with `…` when cut
+pub fn excerpt(content: &str, max_chars: usize) -> String {
+    let collapsed = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= max_chars {
+        return collapsed;
+    }
+
+    // Leave room for the `…`
+    let cut: String = collapsed.chars().take(max_chars.saturating_sub(1)).collect();
+    let next = collapsed[cut.len()..].chars().next();
+    let mut excerpt = match (next, cut.rfind(' ')) {
+        // Cut in the middle of a word: drop it, unless it is the only one
+        (Some(c), Some(space)) if !c.is_whitespace() => cut[..space].to_string(),
+        _ => cut.trim_end().to_string(),
+    };
+    excerpt.push('…');
+    excerpt
+}
+
+/// Lowercased words worth counting for "most used words":
+/// alphanumeric, at least three characters and not a stop word
+pub fn significant_words(content: &str) -> Vec<String> {
+    content
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| word.chars().count() >= 3 && !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Distinct lowercased `#hashtag` tags in order of first appearance
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    for token in content.split_whitespace() {
+        let Some(rest) = token.strip_prefix('#') else {
+            continue;
+        };
+        let tag: String = rest
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+            .collect::<String>()
+            .to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    tags
+}
+
+/// Distinct entry IDs referenced as `[[entry:42]]` in order of first appearance
+pub fn extract_entry_links(content: &str) -> Vec<u64> {
+    let mut ids: Vec<u64> = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find("[[entry:") {
+        rest = &rest[start + "[[entry:".len()..];
+        let Some(end) = rest.find("]]") else {
+            break;
+        };
+        // A link left unclosed ends where the next one starts
+        if let Some(inner) = rest[..end].find("[[") {
+            rest = &rest[inner..];
+            continue;
+        }
+        if let Ok(id) = rest[..end].trim().parse::<u64>() {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        rest = &rest[end + 2..];
+    }
+
+    ids
+}
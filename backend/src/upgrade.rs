@@ -0,0 +1,93 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Moving a diary to a successor application built from newer bytecode.
+//!
+//! Messages are always delivered to the application that sent them, so the diary is
+//! copied through application calls instead, which requires both applications to be on
+//! the same chain:
+//!
+//! 1. publish the new bytecode and create the successor application on the diary's chain,
+//!    without initializing it,
+//! 2. run `RegisterPredecessor` on the successor with the old application's ID. The
+//!    successor only accepts `ImportFromPredecessor` calls from that application,
+//! 3. run `ExportToSuccessor` on the old application until its `exportProgress` is finished.
+//!    The first operation sends an [`ExportBatch::Header`] and every one sends up to
+//!    [`EXPORT_BATCH_SIZE`] entries, ending with [`ExportBatch::Done`],
+//! 4. point clients at the successor.
+//!
+//! The old diary is read-only from the first batch on, and the successor rejects writes
+//! until it received `Done`. Batches are BCS-encoded, so a successor with a newer
+//! [`ExportBatch`] layout can still decode the batches of its predecessor as long as it
+//! only appends variants and fields follow the rules of [`crate::storage`].
+
+use linera_sdk::linera_base_types::ApplicationId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    patch::RevisionEdits, DiaryEntry, Draft, Notebook, OwnerTransfer, OwnershipEvent, Template,
+};
+
+/// Maximum number of entries sent per `ExportToSuccessor` operation
+pub const EXPORT_BATCH_SIZE: u64 = 50;
+
+/// Part of a diary sent to the successor application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExportBatch {
+    /// Settings, notebooks, drafts, templates and owner transfers; always the first batch
+    Header(Box<DiaryHeader>),
+
+    /// Consecutive entries, in ID order
+    Entries(Vec<ExportedEntry>),
+
+    /// Everything was sent
+    Done,
+}
+
+/// Everything about a diary except its entries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiaryHeader {
+    pub secret_phrase_hash: String,
+    pub owner: String,
+    pub utc_offset_minutes: i32,
+    pub pinned: Vec<u64>,
+    /// Next entry ID, so that IDs are never reused
+    pub entry_count: u64,
+    pub notebooks: Vec<Notebook>,
+    pub notebook_count: u64,
+    pub drafts: Vec<Draft>,
+    pub draft_count: u64,
+    pub templates: Vec<Template>,
+    pub template_count: u64,
+    /// Hash of the phrase readers give to see `Members` entries
+    pub members_phrase_hash: Option<String>,
+    /// Owner transfer waiting to be accepted
+    pub pending_owner_transfer: Option<OwnerTransfer>,
+    pub ownership_log: Vec<OwnershipEvent>,
+}
+
+/// An entry with the state kept about it outside of [`DiaryEntry`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedEntry {
+    pub entry: DiaryEntry,
+    /// Entries linked from the content with `[[entry:42]]`
+    pub content_links: Vec<u64>,
+    /// Entries linked with `LinkEntries`
+    pub explicit_links: Vec<u64>,
+    pub favorite: bool,
+    /// Ranges changed by the latest revisions, so that patches made on them still merge
+    pub edit_history: Vec<RevisionEdits>,
+}
+
+/// Progress of `ExportToSuccessor`
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+pub struct ExportProgress {
+    /// The application the diary is exported to
+    pub successor: ApplicationId,
+
+    /// ID of the next entry to send
+    pub next_entry_id: u64,
+
+    /// Whether every batch was sent
+    pub finished: bool,
+}
@@ -0,0 +1,123 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the limits on query depth, complexity and result size.
+
+use diary_backend::{DiaryAbi, Operation, QueryLimits};
+use linera_sdk::test::TestValidator;
+
+/// Test that queries over the configured limits are rejected with a clear error
+#[tokio::test]
+async fn test_query_limits() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let limits = QueryLimits {
+        max_depth: 3,
+        max_complexity: 200,
+        max_results: 3,
+    };
+    let application_id = chain
+        .create_application(bytecode_id, Some(limits), (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            for i in 0..5 {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: format!("Day {}", i),
+                        content: "Quiet day".to_string(),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: None,
+                    },
+                );
+            }
+            // Four notebooks with the default one
+            for name in ["Work", "Travel", "Books"] {
+                block.with_operation(
+                    application_id,
+                    Operation::CreateNotebook {
+                        secret_phrase: secret_phrase.to_string(),
+                        name: name.to_string(),
+                        notebook_secret_phrase: None,
+                    },
+                );
+            }
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            queryLimits { maxDepth maxComplexity maxResults }
+            latestEntries(limit: 3) { id }
+            stats(top: 3) { heatmap { count } }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["queryLimits"]["maxResults"], 3);
+    assert_eq!(response["latestEntries"].as_array().unwrap().len(), 3);
+    // The heatmap keeps its default of a year whatever `maxResults` is
+    assert_eq!(response["stats"]["heatmap"].as_array().unwrap().len(), 365);
+
+    let rejected = [
+        // More entries than `maxResults`
+        ("query { entries { id } }", "more than the limit of 3"),
+        ("query { notebooks { id } }", "more than the limit of 3"),
+        ("query { latestEntries(limit: 4) { id } }", "Limit must be at most 3"),
+        ("query { stats(top: 4) { totalEntries } }", "Limit must be at most 3"),
+        ("query { stats(heatmapDays: 4) { totalEntries } }", "Limit must be at most 3"),
+        // Nested deeper than `maxDepth`
+        (
+            "query { entry(id: 0) { metadata { customFields { key } } } }",
+            "nested too deep",
+        ),
+        // Each alias of a full scan adds to the complexity
+        (
+            r#"query {
+                a: searchByContent(query: "day") { id }
+                b: searchByContent(query: "quiet") { id }
+                c: searchByContent(query: "night") { id }
+                d: searchByContent(query: "rain") { id }
+            }"#,
+            "too complex",
+        ),
+    ];
+    for (query, message) in rejected {
+        let error = chain
+            .try_graphql_query(application_id, query)
+            .await
+            .expect_err(query);
+        assert!(
+            format!("{:?}", error).contains(message),
+            "{}: {:?}",
+            query,
+            error
+        );
+    }
+}
+
+/// Test that invalid limits are rejected when the application is created
+#[tokio::test]
+#[should_panic]
+async fn test_invalid_query_limits() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let limits = QueryLimits {
+        max_results: 0,
+        ..QueryLimits::default()
+    };
+    chain
+        .create_application(bytecode_id, Some(limits), (), vec![])
+        .await;
+}
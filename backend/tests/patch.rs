@@ -0,0 +1,117 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for applying, rebasing and computing patches of entry content.
+
+use diary_backend::patch::{self, ContentEdit, EditSpan, RevisionEdits};
+
+fn edit(offset: u64, len: u64, text: &str) -> ContentEdit {
+    ContentEdit {
+        offset,
+        len,
+        text: text.to_string(),
+    }
+}
+
+/// Test that patches apply in base offsets and that invalid ones are rejected
+#[test]
+fn test_validate_and_apply() {
+    let content = "alpha beta gamma";
+    let edits = vec![edit(0, 5, "ALPHA"), edit(6, 0, "very "), edit(11, 5, "")];
+    patch::validate(&edits, content.len()).unwrap();
+    assert_eq!(patch::apply(content, &edits), "ALPHA very beta ");
+    assert_eq!(
+        patch::spans(&edits)[0],
+        EditSpan {
+            offset: 0,
+            len: 5,
+            new_len: 5
+        }
+    );
+
+    let invalid = [
+        (vec![], "no edits"),
+        (vec![edit(3, 0, "")], "changes nothing"),
+        (vec![edit(6, 4, "x"), edit(0, 1, "y")], "overlaps"),
+        (vec![edit(2, 4, "x"), edit(5, 1, "y")], "overlaps"),
+        (vec![edit(12, 5, "x")], "outside"),
+    ];
+    for (edits, message) in invalid {
+        let error = patch::validate(&edits, content.len()).unwrap_err();
+        assert!(error.contains(message), "{}", error);
+    }
+}
+
+/// Test that patches made on an earlier revision move past changes elsewhere
+/// and conflict with changes they touch
+#[test]
+fn test_rebase() {
+    // Revision 1 replaced "alpha" with "ALPHA!", revision 2 only changed metadata
+    let history = vec![
+        RevisionEdits {
+            revision: 1,
+            spans: vec![EditSpan {
+                offset: 0,
+                len: 5,
+                new_len: 6,
+            }],
+        },
+        RevisionEdits {
+            revision: 2,
+            spans: vec![],
+        },
+    ];
+
+    let rebased = patch::rebase(vec![edit(11, 5, "GAMMA")], 0, 2, &history).unwrap();
+    assert_eq!(rebased, vec![edit(12, 5, "GAMMA")]);
+    assert_eq!(
+        patch::apply("ALPHA! beta gamma", &rebased),
+        "ALPHA! beta GAMMA"
+    );
+
+    // Up to date: unchanged
+    let current = vec![edit(0, 1, "a")];
+    assert_eq!(
+        patch::rebase(current.clone(), 2, 2, &history).unwrap(),
+        current
+    );
+    assert_eq!(
+        patch::rebase(current.clone(), 1, 2, &history).unwrap(),
+        current
+    );
+
+    // Inside, overlapping or right next to the changed range
+    for conflicting in [edit(1, 2, "x"), edit(4, 3, "x"), edit(5, 0, "x")] {
+        let error = patch::rebase(vec![conflicting], 0, 2, &history).unwrap_err();
+        assert!(error.contains("conflicts with revision 1"), "{}", error);
+    }
+
+    // Revisions missing from the history, or not made yet
+    let error = patch::rebase(current.clone(), 0, 3, &history[1..]).unwrap_err();
+    assert!(error.contains("too old"), "{}", error);
+    let error = patch::rebase(current, 3, 2, &history).unwrap_err();
+    assert!(error.contains("does not exist yet"), "{}", error);
+}
+
+/// Test that a diff of two texts is the single edit between their common ends
+#[test]
+fn test_diff() {
+    assert!(patch::diff("same", "same").is_empty());
+    assert_eq!(
+        patch::diff(
+            "Dear diary, today was fine.",
+            "Dear diary, today was great!"
+        ),
+        vec![edit(22, 5, "great!")]
+    );
+    assert_eq!(patch::diff("abc", "abXc"), vec![edit(2, 0, "X")]);
+    assert_eq!(patch::diff("aaa", "aa"), vec![edit(2, 1, "")]);
+    assert_eq!(patch::diff("", "new"), vec![edit(0, 0, "new")]);
+
+    // Offsets stay on character boundaries
+    let old = "café au lait";
+    let new = "cafè au lait";
+    let diff = patch::diff(old, new);
+    assert_eq!(diff, vec![edit(3, 2, "è")]);
+    assert_eq!(patch::apply(old, &diff), new);
+}
@@ -0,0 +1,110 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests that keep the committed GraphQL schema in `schema.graphql` in sync with the service.
+//! After an intended API change, regenerate it with
+//! `UPDATE_SCHEMA=1 cargo test --test schema` and commit the result.
+
+use std::{env, fs};
+
+use diary_backend::{
+    schema::{breaking_changes, sdl_from_introspection, INTROSPECTION_QUERY},
+    DiaryAbi,
+};
+use linera_sdk::test::TestValidator;
+
+/// Path of the committed schema
+const SCHEMA_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema.graphql");
+
+/// Test that the service's schema matches the committed snapshot
+#[tokio::test]
+async fn test_schema_snapshot() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let introspection: serde_json::Value = chain
+        .graphql_query(application_id, INTROSPECTION_QUERY)
+        .await;
+    let sdl = sdl_from_introspection(&introspection).unwrap();
+
+    if env::var_os("UPDATE_SCHEMA").is_some() {
+        fs::write(SCHEMA_PATH, &sdl).unwrap();
+        return;
+    }
+
+    let committed = fs::read_to_string(SCHEMA_PATH).unwrap();
+    let breaking = breaking_changes(&committed, &sdl).unwrap();
+    assert!(
+        breaking.is_empty(),
+        "Breaking API changes:\n  {}",
+        breaking.join("\n  ")
+    );
+    assert!(
+        committed == sdl,
+        "schema.graphql is out of date. Run `UPDATE_SCHEMA=1 cargo test --test schema` \
+         and commit the result."
+    );
+}
+
+/// Test which schema changes are reported as breaking
+#[test]
+fn test_breaking_changes() {
+    let old = r#"
+        type QueryRoot {
+            entry(id: Int!, secretPhrase: String): DiaryEntry
+            entries: [DiaryEntry!]!
+            owner: String!
+        }
+        type DiaryEntry { id: Int! title: String! }
+        enum Mood { GREAT GOOD }
+        input EntryInput { title: String! content: String }
+    "#;
+
+    // Additions, optional arguments and safer nullability are compatible
+    let compatible = r#"
+        type QueryRoot {
+            entry(id: Int!, secretPhrase: String, revision: Int): DiaryEntry
+            entries(limit: Int = 10): [DiaryEntry!]!
+            owner: String!
+            version: Int!
+        }
+        type DiaryEntry { id: Int! title: String! wordCount: Int! }
+        enum Mood { GREAT GOOD OKAY }
+        input EntryInput { title: String content: String tags: [String!] }
+        type Stats { total: Int! }
+    "#;
+    assert_eq!(
+        breaking_changes(old, compatible).unwrap(),
+        Vec::<String>::new()
+    );
+
+    let breaking = r#"
+        type QueryRoot {
+            entry(id: String!): DiaryEntry
+            entries(limit: Int!): [DiaryEntry!]!
+            owner: String
+        }
+        type DiaryEntry { id: Int! }
+        enum Mood { GREAT }
+        input EntryInput { title: String! }
+    "#;
+    let mut changes = breaking_changes(old, breaking).unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            "DiaryEntry.title was removed",
+            "EntryInput(content) was removed",
+            "Mood.GOOD was removed",
+            "QueryRoot.entries(limit) is new and required",
+            "QueryRoot.entry(id) changed type from Int! to String!",
+            "QueryRoot.entry(secretPhrase) was removed",
+            "QueryRoot.owner changed type from String! to String",
+        ]
+    );
+
+    assert!(breaking_changes(old, "type QueryRoot {").is_err());
+}
@@ -4,8 +4,16 @@
 //! Integration tests for the diary application.
 //! These tests run the full application on a simulated chain.
 
-use diary_backend::{hash_secret_phrase, DiaryAbi, Operation, OperationResponse};
-use linera_sdk::test::{ActiveChain, TestValidator};
+use async_graphql::ErrorExtensions;
+use diary_backend::{
+    patch::ContentEdit, storage::CHUNK_SIZE, CustomField, DiaryAbi, EnergyLevel, EntryMetadata,
+    FieldType, Mood, Operation, RevisionConflict, TemplateValue, Visibility,
+    OWNER_TRANSFER_EXPIRY_MICROS,
+};
+use linera_sdk::{
+    linera_base_types::{AccountOwner, CryptoHash, DataBlobHash, TimeDelta},
+    test::TestValidator,
+};
 
 /// Test initializing a diary
 #[tokio::test]
@@ -13,17 +21,22 @@ async fn test_initialize_diary() {
     // Set up test environment
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
-    // Create a secret phrase and hash it
+    // Create a secret phrase
     let secret_phrase = "my-super-secret-phrase";
-    let secret_phrase_hash = hash_secret_phrase(secret_phrase);
 
     // Initialize the diary
     let response = chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash: secret_phrase_hash.clone(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await;
 
@@ -39,12 +52,14 @@ async fn test_initialize_diary() {
         query {
             isInitialized
             entryCount
+            schemaVersion
         }
     "#;
 
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     assert_eq!(response["isInitialized"], true);
     assert_eq!(response["entryCount"], 0);
+    assert_eq!(response["schemaVersion"], diary_backend::storage::SCHEMA_VERSION);
 }
 
 /// Test adding diary entries
@@ -52,16 +67,21 @@ async fn test_initialize_diary() {
 async fn test_add_entries() {
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
     let secret_phrase = "my-super-secret-phrase";
-    let secret_phrase_hash = hash_secret_phrase(secret_phrase);
 
     // Initialize
     chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash,
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await
         .unwrap();
@@ -69,11 +89,17 @@ async fn test_add_entries() {
     // Add first entry
     chain
         .add_block(|block| {
-            block.with_operation(Operation::AddEntry {
-                secret_phrase: secret_phrase.to_string(),
-                title: "My First Day".to_string(),
-                content: "Today was amazing! I started my journey...".to_string(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "My First Day".to_string(),
+                    content: "Today was amazing! I started my journey...".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
         })
         .await
         .unwrap();
@@ -81,30 +107,46 @@ async fn test_add_entries() {
     // Add second entry
     chain
         .add_block(|block| {
-            block.with_operation(Operation::AddEntry {
-                secret_phrase: secret_phrase.to_string(),
-                title: "Day Two".to_string(),
-                content: "Continuing the adventure...".to_string(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Day Two".to_string(),
+                    content: "Continuing the adventure...".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
         })
         .await
         .unwrap();
 
-    // Query entries
+    // Query entry headers, and the content of one entry
     let query = r#"
         query {
             entryCount
             entries {
                 id
                 title
+                excerpt
+                wordCount
+            }
+            entry(id: 0) {
                 content
             }
         }
     "#;
 
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     assert_eq!(response["entryCount"], 2);
     assert_eq!(response["entries"].as_array().unwrap().len(), 2);
+    assert_eq!(response["entries"][0]["excerpt"], "Continuing the adventure...");
+    assert_eq!(response["entries"][0]["wordCount"], 3);
+    assert_eq!(
+        response["entry"]["content"],
+        "Today was amazing! I started my journey..."
+    );
 }
 
 /// Test updating diary entries
@@ -112,16 +154,21 @@ async fn test_add_entries() {
 async fn test_update_entry() {
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
     let secret_phrase = "my-super-secret-phrase";
-    let secret_phrase_hash = hash_secret_phrase(secret_phrase);
 
     // Initialize
     chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash,
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await
         .unwrap();
@@ -129,11 +176,17 @@ async fn test_update_entry() {
     // Add entry
     let add_response = chain
         .add_block(|block| {
-            block.with_operation(Operation::AddEntry {
-                secret_phrase: secret_phrase.to_string(),
-                title: "Original Title".to_string(),
-                content: "Original content".to_string(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Original Title".to_string(),
+                    content: "Original content".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
         })
         .await
         .unwrap();
@@ -152,24 +205,30 @@ async fn test_update_entry() {
         }
     "#;
 
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     let entry_id = response["entries"][0]["id"].as_u64().unwrap();
 
     // Update the entry
     chain
         .add_block(|block| {
-            block.with_operation(Operation::UpdateEntry {
-                secret_phrase: secret_phrase.to_string(),
-                entry_id,
-                title: Some("Updated Title".to_string()),
-                content: Some("Updated content".to_string()),
-            });
+            block.with_operation(
+                application_id,
+                Operation::UpdateEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id,
+                    title: Some("Updated Title".to_string()),
+                    content: Some("Updated content".to_string()),
+                    metadata: None,
+                    visibility: None,
+                    expected_revision: None,
+                },
+            );
         })
         .await
         .unwrap();
 
     // Verify update
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     assert_eq!(response["entries"][0]["title"], "Updated Title");
 }
 
@@ -178,16 +237,21 @@ async fn test_update_entry() {
 async fn test_delete_entry() {
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
     let secret_phrase = "my-super-secret-phrase";
-    let secret_phrase_hash = hash_secret_phrase(secret_phrase);
 
     // Initialize
     chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash,
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await
         .unwrap();
@@ -195,22 +259,34 @@ async fn test_delete_entry() {
     // Add entries
     chain
         .add_block(|block| {
-            block.with_operation(Operation::AddEntry {
-                secret_phrase: secret_phrase.to_string(),
-                title: "Entry to Keep".to_string(),
-                content: "This stays".to_string(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Entry to Keep".to_string(),
+                    content: "This stays".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
         })
         .await
         .unwrap();
 
     chain
         .add_block(|block| {
-            block.with_operation(Operation::AddEntry {
-                secret_phrase: secret_phrase.to_string(),
-                title: "Entry to Delete".to_string(),
-                content: "This goes".to_string(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Entry to Delete".to_string(),
+                    content: "This goes".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
         })
         .await
         .unwrap();
@@ -226,7 +302,7 @@ async fn test_delete_entry() {
         }
     "#;
 
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     assert_eq!(response["entryCount"], 2);
 
     let entries = response["entries"].as_array().unwrap();
@@ -240,16 +316,20 @@ async fn test_delete_entry() {
     // Delete entry
     chain
         .add_block(|block| {
-            block.with_operation(Operation::DeleteEntry {
-                secret_phrase: secret_phrase.to_string(),
-                entry_id: entry_to_delete_id,
-            });
+            block.with_operation(
+                application_id,
+                Operation::DeleteEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: entry_to_delete_id,
+                    expected_revision: None,
+                },
+            );
         })
         .await
         .unwrap();
 
     // Verify deletion
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     assert_eq!(response["entryCount"], 1);
     assert_eq!(response["entries"][0]["title"], "Entry to Keep");
 }
@@ -259,16 +339,21 @@ async fn test_delete_entry() {
 async fn test_invalid_secret_phrase() {
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
     let secret_phrase = "correct-phrase";
-    let secret_phrase_hash = hash_secret_phrase(secret_phrase);
 
     // Initialize
     chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash,
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await
         .unwrap();
@@ -276,11 +361,17 @@ async fn test_invalid_secret_phrase() {
     // Try to add entry with wrong secret phrase
     let result = chain
         .add_block(|block| {
-            block.with_operation(Operation::AddEntry {
-                secret_phrase: "wrong-phrase".to_string(),
-                title: "Unauthorized Entry".to_string(),
-                content: "Should fail".to_string(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: "wrong-phrase".to_string(),
+                    title: "Unauthorized Entry".to_string(),
+                    content: "Should fail".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
         })
         .await;
 
@@ -298,16 +389,21 @@ async fn test_invalid_secret_phrase() {
 async fn test_graphql_queries() {
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
     let secret_phrase = "my-super-secret-phrase";
-    let secret_phrase_hash = hash_secret_phrase(secret_phrase);
 
     // Initialize and add entries
     chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash,
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await
         .unwrap();
@@ -316,11 +412,17 @@ async fn test_graphql_queries() {
     for i in 1..=5 {
         chain
             .add_block(|block| {
-                block.with_operation(Operation::AddEntry {
-                    secret_phrase: secret_phrase.to_string(),
-                    title: format!("Entry {}", i),
-                    content: format!("Content for entry {}", i),
-                });
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: format!("Entry {}", i),
+                        content: format!("Content for entry {}", i),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: None,
+                    },
+                );
             })
             .await
             .unwrap();
@@ -336,7 +438,7 @@ async fn test_graphql_queries() {
         }
     "#;
 
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     let latest = response["latestEntries"].as_array().unwrap();
     assert_eq!(latest.len(), 3);
 
@@ -349,7 +451,7 @@ async fn test_graphql_queries() {
         }
     "#;
 
-    let response: serde_json::Value = chain.graphql_query(query).await;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
     let results = response["searchByTitle"].as_array().unwrap();
     assert_eq!(results.len(), 1);
     assert_eq!(results[0]["title"], "Entry 3");
@@ -360,15 +462,21 @@ async fn test_graphql_queries() {
 async fn test_double_initialization() {
     let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
     let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
 
-    let secret_phrase_hash = hash_secret_phrase("secret");
+    let secret_phrase = "secret";
 
     // First initialization
     chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash: secret_phrase_hash.clone(),
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await
         .unwrap();
@@ -376,9 +484,12 @@ async fn test_double_initialization() {
     // Second initialization should fail
     let result = chain
         .add_block(|block| {
-            block.with_operation(Operation::Initialize {
-                secret_phrase_hash,
-            });
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
         })
         .await;
 
@@ -388,3 +499,2168 @@ async fn test_double_initialization() {
         "Should not allow double initialization"
     );
 }
+
+
+/// Test notebooks: scoping, protected notebooks and moving entries
+#[tokio::test]
+async fn test_notebooks() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    let dreams_phrase = "my-dreams-only-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Create a plain notebook and a protected one
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::CreateNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    name: "Work".to_string(),
+                    notebook_secret_phrase: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::CreateNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    name: "Dreams".to_string(),
+                    notebook_secret_phrase: Some(dreams_phrase.to_string()),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Standup".to_string(),
+                    content: "Shipped notebooks".to_string(),
+                    notebook_id: Some(1),
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: dreams_phrase.to_string(),
+                    title: "Flying".to_string(),
+                    content: "Over the sea".to_string(),
+                    notebook_id: Some(2),
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // The diary secret phrase does not open a protected notebook
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Intruder".to_string(),
+                    content: "Should fail".to_string(),
+                    notebook_id: Some(2),
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await;
+    assert!(
+        result.is_err() || !result.unwrap().is_success(),
+        "Protected notebooks should require their own secret phrase"
+    );
+
+    let query = r#"
+        query {
+            notebooks { id name isProtected }
+            work: entries(notebookId: 1) { title notebookId }
+            unscoped: entries { title }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["notebooks"].as_array().unwrap().len(), 3);
+    assert_eq!(response["notebooks"][2]["isProtected"], true);
+    assert_eq!(response["work"].as_array().unwrap().len(), 1);
+    assert_eq!(response["work"][0]["title"], "Standup");
+    // Entries from protected notebooks are not listed without their secret
+    let unscoped = response["unscoped"].as_array().unwrap();
+    assert_eq!(unscoped.len(), 1);
+
+    let query = r#"
+        query {
+            entries(notebookId: 2, secretPhrase: "my-dreams-only-phrase") { title }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entries"][0]["title"], "Flying");
+
+    // Move the work entry back to the default notebook and archive "Work"
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::MoveEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    notebook_id: 0,
+                    target_secret_phrase: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::ArchiveNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    notebook_id: 1,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            notebook(id: 1) { archived }
+            entries(notebookId: 0) { title }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["notebook"]["archived"], true);
+    assert_eq!(response["entries"][0]["title"], "Standup");
+
+    // Entries cannot be moved into or out of an archived notebook until it is unarchived
+    let move_entry = |notebook_id| Operation::MoveEntry {
+        secret_phrase: secret_phrase.to_string(),
+        entry_id: 0,
+        notebook_id,
+        target_secret_phrase: None,
+    };
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(application_id, move_entry(1));
+        })
+        .await;
+    assert!(result.is_err());
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::UnarchiveNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    notebook_id: 1,
+                },
+            );
+            block.with_operation(application_id, move_entry(1));
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            notebook(id: 1) { archived }
+            entries(notebookId: 1) { title }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["notebook"]["archived"], false);
+    assert_eq!(response["entries"][0]["title"], "Standup");
+}
+
+/// Test metadata on entries: validation, filtering and aggregation
+#[tokio::test]
+async fn test_entry_metadata() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let check_in = |mood, rating, hours: &str| EntryMetadata {
+        mood: Some(mood),
+        rating: Some(rating),
+        energy: Some(EnergyLevel::Medium),
+        custom_fields: vec![CustomField {
+            key: "sleep".to_string(),
+            field_type: FieldType::Number,
+            value: hours.to_string(),
+        }],
+    };
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Monday".to_string(),
+                    content: "Good start".to_string(),
+                    notebook_id: None,
+                    metadata: Some(check_in(Mood::Great, 5, "8")),
+                    visibility: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Tuesday".to_string(),
+                    content: "Rough night".to_string(),
+                    notebook_id: None,
+                    metadata: Some(check_in(Mood::Bad, 2, "5")),
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Ratings outside 1-5 are rejected
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Wednesday".to_string(),
+                    content: "Off the scale".to_string(),
+                    notebook_id: None,
+                    metadata: Some(check_in(Mood::Okay, 9, "7")),
+                    visibility: None,
+                },
+            );
+        })
+        .await;
+    assert!(
+        result.is_err() || !result.unwrap().is_success(),
+        "Should not allow ratings outside 1-5"
+    );
+
+    // Number fields must hold a finite number
+    for hours in ["NaN", "inf"] {
+        let result = chain
+            .add_block(|block| {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: "Wednesday".to_string(),
+                        content: "Lost count".to_string(),
+                        notebook_id: None,
+                        metadata: Some(check_in(Mood::Okay, 3, hours)),
+                        visibility: None,
+                    },
+                );
+            })
+            .await;
+        assert!(
+            result.is_err() || !result.unwrap().is_success(),
+            "Should not allow {} in a number field",
+            hours
+        );
+    }
+
+    let query = r#"
+        query {
+            entriesByMetadata(filter: { minRating: 4 }) { title }
+            metadataSummary {
+                ratedCount
+                averageRating
+                moods { mood count }
+                numericFields { key average }
+            }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let rated = response["entriesByMetadata"].as_array().unwrap();
+    assert_eq!(rated.len(), 1);
+    assert_eq!(rated[0]["title"], "Monday");
+
+    let summary = &response["metadataSummary"];
+    assert_eq!(summary["ratedCount"], 2);
+    assert_eq!(summary["averageRating"], 3.5);
+    assert_eq!(summary["moods"].as_array().unwrap().len(), 2);
+    assert_eq!(summary["numericFields"][0]["key"], "sleep");
+    assert_eq!(summary["numericFields"][0]["average"], 6.5);
+}
+
+/// Test the incrementally maintained statistics
+#[tokio::test]
+async fn test_stats() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Garden".to_string(),
+                    content: "Planted tomatoes today #garden #spring".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "More garden".to_string(),
+                    content: "Watered the tomatoes #garden".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            stats(top: 3, heatmapDays: 7) {
+                totalEntries
+                totalWords
+                currentStreak
+                longestStreak
+                perDay { period count }
+                topWords { term count }
+                topTags { term count }
+                heatmap { count level }
+            }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let stats = &response["stats"];
+    assert_eq!(stats["totalEntries"], 2);
+    assert_eq!(stats["totalWords"], 9);
+    assert_eq!(stats["currentStreak"], 1);
+    assert_eq!(stats["longestStreak"], 1);
+    assert_eq!(stats["perDay"][0]["count"], 2);
+    assert_eq!(stats["topWords"][0]["term"], "garden");
+    assert_eq!(stats["topTags"][0]["term"], "garden");
+    assert_eq!(stats["topTags"][0]["count"], 2);
+    assert_eq!(stats["heatmap"].as_array().unwrap().len(), 7);
+    assert_eq!(stats["heatmap"][6]["level"], 4);
+
+    // Deleting an entry takes it out of the statistics again
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::DeleteEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 1,
+                    expected_revision: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let stats = &response["stats"];
+    assert_eq!(stats["totalEntries"], 1);
+    assert_eq!(stats["totalWords"], 5);
+    assert_eq!(stats["topTags"][0]["count"], 1);
+
+    // Protected notebooks only count with their own secret phrase
+    let dreams_phrase = "dreams-phrase";
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::CreateNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    name: "Dreams".to_string(),
+                    notebook_secret_phrase: Some(dreams_phrase.to_string()),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: dreams_phrase.to_string(),
+                    title: "Flying".to_string(),
+                    content: "Flying over the sea #dream".to_string(),
+                    notebook_id: Some(1),
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let stats = &response["stats"];
+    assert_eq!(stats["totalEntries"], 1);
+    assert_eq!(stats["topTags"].as_array().unwrap().len(), 2);
+    assert_ne!(stats["topTags"][0]["term"], "dream");
+
+    let dreams_query = format!(
+        r#"query {{
+            stats(notebookId: 1, secretPhrase: "{}") {{
+                totalEntries
+                totalWords
+                topTags {{ term count }}
+            }}
+        }}"#,
+        dreams_phrase
+    );
+    let response: serde_json::Value = chain
+        .graphql_query(application_id, dreams_query.as_str())
+        .await;
+    let stats = &response["stats"];
+    assert_eq!(stats["totalEntries"], 1);
+    assert_eq!(stats["totalWords"], 5);
+    assert_eq!(stats["topTags"][0]["term"], "dream");
+
+    // Days follow the owner's timezone: 23:00 UTC is already the next day at UTC+2
+    validator.clock().add(TimeDelta::from_secs(23 * 60 * 60));
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Evening".to_string(),
+                    content: "A late walk #garden".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["stats"]["perDay"].as_array().unwrap().len(), 1);
+    assert_eq!(response["stats"]["perDay"][0]["count"], 2);
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::SetTimezone {
+                    secret_phrase: secret_phrase.to_string(),
+                    utc_offset_minutes: 120,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let stats = &response["stats"];
+    assert_eq!(stats["perDay"].as_array().unwrap().len(), 2);
+    assert_eq!(stats["currentStreak"], 2);
+}
+
+/// Test the timezone setting and "on this day" queries
+#[tokio::test]
+async fn test_timezone_and_on_this_day() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::SetTimezone {
+                    secret_phrase: secret_phrase.to_string(),
+                    utc_offset_minutes: 120,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Today".to_string(),
+                    content: "Written this year".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Offsets beyond UTC+14 are rejected
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::SetTimezone {
+                    secret_phrase: secret_phrase.to_string(),
+                    utc_offset_minutes: 15 * 60,
+                },
+            );
+        })
+        .await;
+    assert!(
+        result.is_err() || !result.unwrap().is_success(),
+        "Should not allow offsets outside UTC-12..UTC+14"
+    );
+
+    // So are offsets that are not whole quarter hours
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::SetTimezone {
+                    secret_phrase: secret_phrase.to_string(),
+                    utc_offset_minutes: 100,
+                },
+            );
+        })
+        .await;
+    assert!(
+        result.is_err() || !result.unwrap().is_success(),
+        "Should not allow offsets that are not whole quarter hours"
+    );
+
+    let query = r#"
+        query {
+            utcOffsetMinutes
+            onThisDay { yearsAgo }
+            thisYear: anniversaries(days: 365) { yearsAgo }
+            nextYear: anniversaries(days: 366) { yearsAgo }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["utcOffsetMinutes"], 120);
+    // An entry written today is not an anniversary yet; its first one is a year from today
+    assert_eq!(response["onThisDay"].as_array().unwrap().len(), 0);
+    assert_eq!(response["thisYear"].as_array().unwrap().len(), 0);
+    assert_eq!(response["nextYear"].as_array().unwrap().len(), 1);
+    assert_eq!(response["nextYear"][0]["yearsAgo"], 1);
+}
+
+/// Test attaching and detaching published files, and the checks on attachments
+#[tokio::test]
+async fn test_attachments() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    let dreams_phrase = "dreams-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::CreateNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    name: "Dreams".to_string(),
+                    notebook_secret_phrase: Some(dreams_phrase.to_string()),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Garden".to_string(),
+                    content: "Photos of the tomatoes".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: dreams_phrase.to_string(),
+                    title: "Flying".to_string(),
+                    content: "Recorded right after waking up".to_string(),
+                    notebook_id: Some(1),
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let photo = chain.publish_data_blob(b"not really a png".to_vec()).await;
+    let voice_note = chain.publish_data_blob(b"not really ogg".to_vec()).await;
+    let attach = |secret_phrase: &str, entry_id, blob_hash, mime_type: &str, filename: &str| {
+        Operation::AttachFile {
+            secret_phrase: secret_phrase.to_string(),
+            entry_id,
+            blob_hash,
+            mime_type: mime_type.to_string(),
+            filename: filename.to_string(),
+        }
+    };
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                attach(secret_phrase, 0, photo, "image/png", "tomatoes.png"),
+            );
+            block.with_operation(
+                application_id,
+                attach(secret_phrase, 0, voice_note, "audio/ogg", "note.ogg"),
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            attachments(entryId: 0) { mimeType size filename }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let attachments = response["attachments"].as_array().unwrap();
+    assert_eq!(attachments.len(), 2);
+    assert_eq!(attachments[0]["filename"], "tomatoes.png");
+    assert_eq!(attachments[0]["mimeType"], "image/png");
+    // The size is read from the blob, not taken from the caller
+    assert_eq!(attachments[0]["size"], 16);
+    assert_eq!(attachments[1]["mimeType"], "audio/ogg");
+
+    // Duplicates, unknown blobs, other file types and paths in filenames are rejected
+    let unknown = DataBlobHash(CryptoHash::test_hash("never published"));
+    let rejected = [
+        (attach(secret_phrase, 0, photo, "image/png", "again.png"), "duplicate"),
+        (attach(secret_phrase, 0, unknown, "image/png", "missing.png"), "unknown blob"),
+        (attach(secret_phrase, 0, photo, "text/html", "page.html"), "MIME type"),
+        (attach(secret_phrase, 0, photo, "image/png", "../tomatoes.png"), "filename"),
+        // Entries in a protected notebook need the notebook's own secret phrase
+        (attach(secret_phrase, 1, photo, "image/png", "dream.png"), "notebook secret"),
+    ];
+    for (operation, reason) in rejected {
+        let result = chain
+            .add_block(|block| {
+                block.with_operation(application_id, operation);
+            })
+            .await;
+        assert!(
+            result.is_err() || !result.unwrap().is_success(),
+            "Should not allow an attachment with a bad {}",
+            reason
+        );
+    }
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                attach(dreams_phrase, 1, voice_note, "audio/ogg", "dream.ogg"),
+            );
+            block.with_operation(
+                application_id,
+                Operation::DetachFile {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    blob_hash: photo,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let attachments = response["attachments"].as_array().unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0]["filename"], "note.ogg");
+
+    let response: serde_json::Value = chain
+        .graphql_query(
+            application_id,
+            format!(
+                r#"query {{ attachments(entryId: 1, secretPhrase: "{}") {{ filename }} }}"#,
+                dreams_phrase
+            )
+            .as_str(),
+        )
+        .await;
+    assert_eq!(response["attachments"][0]["filename"], "dream.ogg");
+
+    // Detaching a file that is not attached, or from a protected notebook without its
+    // secret phrase, is rejected
+    for (entry_id, blob_hash) in [(0, photo), (1, voice_note)] {
+        let result = chain
+            .add_block(|block| {
+                block.with_operation(
+                    application_id,
+                    Operation::DetachFile {
+                        secret_phrase: secret_phrase.to_string(),
+                        entry_id,
+                        blob_hash,
+                    },
+                );
+            })
+            .await;
+        assert!(
+            result.is_err() || !result.unwrap().is_success(),
+            "Should not detach {:?} from entry {}",
+            blob_hash,
+            entry_id
+        );
+    }
+}
+
+/// Test that drafts stay out of the entries until published
+#[tokio::test]
+async fn test_drafts() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::SaveDraft {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Half".to_string(),
+                    content: "Half-written thou".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::SaveDraft {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Scrap".to_string(),
+                    content: String::new(),
+                    notebook_id: None,
+                    metadata: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            entryCount
+            entries { title }
+            searchByContent(query: "thou") { title }
+            drafts { id title content }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entryCount"], 0);
+    assert_eq!(response["entries"].as_array().unwrap().len(), 0);
+    assert_eq!(response["searchByContent"].as_array().unwrap().len(), 0);
+    assert_eq!(response["drafts"].as_array().unwrap().len(), 2);
+
+    // Autosave overwrites, then publish one draft and discard the other
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::UpdateDraft {
+                    secret_phrase: secret_phrase.to_string(),
+                    draft_id: 0,
+                    title: "Whole".to_string(),
+                    content: "A whole thought".to_string(),
+                    metadata: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::PublishDraft {
+                    secret_phrase: secret_phrase.to_string(),
+                    draft_id: 0,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::DiscardDraft {
+                    secret_phrase: secret_phrase.to_string(),
+                    draft_id: 1,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entryCount"], 1);
+    assert_eq!(response["entries"][0]["title"], "Whole");
+    assert_eq!(response["searchByContent"][0]["title"], "Whole");
+    assert_eq!(response["drafts"].as_array().unwrap().len(), 0);
+}
+
+/// Test rendering entries from templates inside the contract
+#[tokio::test]
+async fn test_templates() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::CreateTemplate {
+                    secret_phrase: secret_phrase.to_string(),
+                    name: "Standup".to_string(),
+                    title_pattern: "Standup {{date}}".to_string(),
+                    content_skeleton: "{{weekday}}\nYesterday: {{yesterday}}\nToday: {{today}}"
+                        .to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntryFromTemplate {
+                    secret_phrase: secret_phrase.to_string(),
+                    template_id: 0,
+                    values: vec![
+                        TemplateValue {
+                            key: "yesterday".to_string(),
+                            value: "Templates".to_string(),
+                        },
+                        TemplateValue {
+                            key: "today".to_string(),
+                            value: "Tests".to_string(),
+                        },
+                    ],
+                    notebook_id: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Placeholders without a value are rejected
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntryFromTemplate {
+                    secret_phrase: secret_phrase.to_string(),
+                    template_id: 0,
+                    values: Vec::new(),
+                    notebook_id: None,
+                },
+            );
+        })
+        .await;
+    assert!(
+        result.is_err() || !result.unwrap().is_success(),
+        "Should not render templates with missing values"
+    );
+
+    // Built-in placeholders always come from the clock
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AddEntryFromTemplate {
+                    secret_phrase: secret_phrase.to_string(),
+                    template_id: 0,
+                    values: vec![
+                        TemplateValue {
+                            key: "date".to_string(),
+                            value: "someday".to_string(),
+                        },
+                        TemplateValue {
+                            key: "yesterday".to_string(),
+                            value: "Templates".to_string(),
+                        },
+                        TemplateValue {
+                            key: "today".to_string(),
+                            value: "Tests".to_string(),
+                        },
+                    ],
+                    notebook_id: None,
+                },
+            );
+        })
+        .await;
+    assert!(
+        result.is_err() || !result.unwrap().is_success(),
+        "Should not allow values for built-in placeholders"
+    );
+
+    let query = r#"
+        query {
+            templates { name placeholders }
+            entries { title }
+            entry(id: 0) { content }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(
+        response["templates"][0]["placeholders"],
+        serde_json::json!(["date", "weekday", "yesterday", "today"])
+    );
+    // The validator's clock starts at the Unix epoch
+    assert_eq!(response["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(response["entries"][0]["title"], "Standup 1970-01-01");
+    assert_eq!(
+        response["entry"]["content"],
+        "Thursday
+Yesterday: Templates
+Today: Tests"
+    );
+}
+
+/// Test content links, explicit links and backlinks between entries
+#[tokio::test]
+async fn test_entry_links() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            for (title, content) in [
+                ("Idea", "A graph of entries"),
+                ("Plan", "Build on [[entry:0]]"),
+                ("Notes", "See [[entry:0]] and [[entry:1]], not [[entry:99]]"),
+            ] {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: title.to_string(),
+                        content: content.to_string(),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: None,
+                    },
+                );
+            }
+            block.with_operation(
+                application_id,
+                Operation::LinkEntries {
+                    secret_phrase: secret_phrase.to_string(),
+                    from_entry_id: 0,
+                    to_entry_id: 2,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            backlinks(id: 0) { title }
+            linkedEntries(id: 2) { title }
+            ideaLinks: linkedEntries(id: 0) { title }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["backlinks"].as_array().unwrap().len(), 2);
+    // Links to entries that do not exist are ignored
+    assert_eq!(response["linkedEntries"].as_array().unwrap().len(), 2);
+    assert_eq!(response["ideaLinks"][0]["title"], "Notes");
+
+    // Editing the content drops its old links, deleting an entry drops its links
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::UpdateEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 1,
+                    title: None,
+                    content: Some("Standalone plan".to_string()),
+                    metadata: None,
+                    visibility: None,
+                    expected_revision: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::DeleteEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 2,
+                    expected_revision: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            backlinks(id: 0) { title }
+            linkedEntries(id: 0) { title }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["backlinks"].as_array().unwrap().len(), 0);
+    assert_eq!(response["linkedEntries"].as_array().unwrap().len(), 0);
+}
+
+/// Test pinned entries coming first and the favorites query
+#[tokio::test]
+async fn test_pinned_and_favorites() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    for i in 0..4 {
+        chain
+            .add_block(|block| {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: format!("Entry {}", i),
+                        content: format!("Content for entry {}", i),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: None,
+                    },
+                );
+            })
+            .await
+            .unwrap();
+    }
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::PinEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 1,
+                    position: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::PinEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    position: Some(0),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::Favorite {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 2,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            pinnedIds
+            entries { id }
+            latestEntries(limit: 3) { id }
+            favorites { id }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["pinnedIds"], serde_json::json!([0, 1]));
+    let ids: Vec<u64> = response["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ids, vec![0, 1, 3, 2]);
+    assert_eq!(response["latestEntries"][2]["id"], 3);
+    assert_eq!(response["favorites"], serde_json::json!([{ "id": 2 }]));
+
+    // Deleting a pinned entry unpins it
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::DeleteEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    expected_revision: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["pinnedIds"], serde_json::json!([1]));
+
+    // Pinned entries of a protected notebook only show with its secret phrase
+    let dreams_phrase = "dreams-phrase";
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::CreateNotebook {
+                    secret_phrase: secret_phrase.to_string(),
+                    name: "Dreams".to_string(),
+                    notebook_secret_phrase: Some(dreams_phrase.to_string()),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: dreams_phrase.to_string(),
+                    title: "Flying".to_string(),
+                    content: "Flying over the sea".to_string(),
+                    notebook_id: Some(1),
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::PinEntry {
+                    secret_phrase: dreams_phrase.to_string(),
+                    entry_id: 4,
+                    position: Some(0),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["pinnedIds"], serde_json::json!([1]));
+
+    let dreams_query = format!(
+        r#"query {{ pinnedIds(notebookId: 1, secretPhrase: "{}") }}"#,
+        dreams_phrase
+    );
+    let response: serde_json::Value = chain
+        .graphql_query(application_id, dreams_query.as_str())
+        .await;
+    assert_eq!(response["pinnedIds"], serde_json::json!([4]));
+}
+
+/// Test composing filters and orderings on the entries query
+#[tokio::test]
+async fn test_entry_filter() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            for (title, content, rating) in [
+                ("Alpha", "Sprint planning #work", 3),
+                ("Bravo", "Hiking trip #outdoors", 5),
+                ("Charlie", "Retro notes #work #team", 4),
+                ("Delta", "Quiet evening", 2),
+            ] {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: title.to_string(),
+                        content: content.to_string(),
+                        notebook_id: None,
+                        metadata: Some(EntryMetadata {
+                            rating: Some(rating),
+                            ..EntryMetadata::default()
+                        }),
+                        visibility: None,
+                    },
+                );
+            }
+            block.with_operation(
+                application_id,
+                Operation::Favorite {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 3,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            work: entries(filter: { tags: ["work"] }, orderBy: TITLE_ASC) { title }
+            workOrFavorite: entries(
+                filter: { or: [{ tags: ["team"] }, { favorite: true }] }
+                orderBy: TITLE_DESC
+            ) { title }
+            notWork: entries(
+                filter: { not: { tags: ["work"] }, metadata: { minRating: 3 } }
+            ) { title }
+            textSearch: entries(filter: { textContains: "NOTES" }) { title }
+            byRating: entries(orderBy: RATING_DESC) { title }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(
+        response["work"],
+        serde_json::json!([{ "title": "Alpha" }, { "title": "Charlie" }])
+    );
+    assert_eq!(
+        response["workOrFavorite"],
+        serde_json::json!([{ "title": "Delta" }, { "title": "Charlie" }])
+    );
+    assert_eq!(response["notWork"], serde_json::json!([{ "title": "Bravo" }]));
+    assert_eq!(response["textSearch"], serde_json::json!([{ "title": "Charlie" }]));
+    assert_eq!(response["byRating"][0]["title"], "Bravo");
+    assert_eq!(response["byRating"][3]["title"], "Delta");
+}
+
+/// Test per-entry visibility, the reader phrases of the regular queries and the public read API
+#[tokio::test]
+async fn test_visibility() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    // 30 bytes, enough for a nonce and an authentication tag
+    let envelope = format!("enc:v1:{}", "q83vEjRW".repeat(5));
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            for (title, content, visibility) in [
+                ("Release notes", "Shipped #devlog", Visibility::Public),
+                ("Team only", "Internal notes", Visibility::Members),
+                (envelope.as_str(), envelope.as_str(), Visibility::Private),
+            ] {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: title.to_string(),
+                        content: content.to_string(),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: Some(visibility),
+                    },
+                );
+            }
+        })
+        .await
+        .unwrap();
+
+    // Private entries must not hold plain text, nor envelopes too short to be encrypted
+    for (title, content) in [
+        ("Plain secret", "Not encrypted"),
+        ("enc:v1:dGl0bGU=", "enc:v1:Y29udGVudA=="),
+    ] {
+        let result = chain
+            .add_block(|block| {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: title.to_string(),
+                        content: content.to_string(),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: Some(Visibility::Private),
+                    },
+                );
+            })
+            .await;
+        assert!(
+            result.is_err() || !result.unwrap().is_success(),
+            "Should not store {} in private entries",
+            title
+        );
+    }
+
+    let query = r#"
+        query {
+            public {
+                entryCount
+                entries { id title tags }
+                hidden: entry(id: 1) { title }
+                shown: entry(id: 0) { title }
+            }
+            entries { id }
+            stats { totalEntries totalWords }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let public = &response["public"];
+    assert_eq!(public["entryCount"], 1);
+    assert_eq!(public["entries"][0]["title"], "Release notes");
+    assert_eq!(public["entries"][0]["tags"], serde_json::json!(["devlog"]));
+    assert!(public["hidden"].is_null());
+    assert_eq!(public["shown"]["title"], "Release notes");
+    // Without a members phrase anyone sees members entries, but private ones
+    // only show with the diary's secret phrase
+    assert_eq!(response["entries"], serde_json::json!([{ "id": 1 }, { "id": 0 }]));
+    // Private entries are left out of the statistics altogether
+    assert_eq!(response["stats"]["totalEntries"], 2);
+    assert_eq!(response["stats"]["totalWords"], 4);
+
+    let owner_query = format!(
+        r#"query {{
+            entries(readerPhrase: "{}") {{ id }}
+            entry(id: 2, readerPhrase: "{}") {{ title }}
+        }}"#,
+        secret_phrase, secret_phrase
+    );
+    let response: serde_json::Value = chain
+        .graphql_query(application_id, owner_query.as_str())
+        .await;
+    assert_eq!(
+        response["entries"],
+        serde_json::json!([{ "id": 2 }, { "id": 1 }, { "id": 0 }])
+    );
+    assert_eq!(response["entry"]["title"], envelope);
+
+    // With a members phrase, members entries need it or the secret phrase
+    let members_phrase = "team-phrase";
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::SetMembersPhrase {
+                    secret_phrase: secret_phrase.to_string(),
+                    members_phrase: Some(members_phrase.to_string()),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let members_query = format!(
+        r#"query {{
+            anyone: entries {{ id }}
+            members: entries(readerPhrase: "{}") {{ id }}
+            owner: entries(readerPhrase: "{}") {{ id }}
+            pinnedIds
+            stats(readerPhrase: "{}") {{ totalEntries }}
+        }}"#,
+        members_phrase, secret_phrase, members_phrase
+    );
+    let response: serde_json::Value = chain
+        .graphql_query(application_id, members_query.as_str())
+        .await;
+    assert_eq!(response["anyone"], serde_json::json!([{ "id": 0 }]));
+    assert_eq!(response["members"], serde_json::json!([{ "id": 1 }, { "id": 0 }]));
+    assert_eq!(response["owner"].as_array().unwrap().len(), 3);
+    assert_eq!(response["stats"]["totalEntries"], 2);
+}
+
+/// Test the Atom and RSS feeds of public entries
+#[tokio::test]
+async fn test_public_feed() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            for (title, visibility) in [
+                ("Shipping <v2> & more", Visibility::Public),
+                ("Team only", Visibility::Members),
+            ] {
+                block.with_operation(
+                    application_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: title.to_string(),
+                        content: "Release day #devlog".to_string(),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: Some(visibility),
+                    },
+                );
+            }
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            public {
+                atomFeed(link: "https://example.com/devlog", title: "Dev log")
+                rssFeed(link: "https://example.com/devlog")
+            }
+        }
+    "#;
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let guid = format!("urn:linera-diary:{}:entry:0", chain.id());
+
+    let atom = response["public"]["atomFeed"].as_str().unwrap();
+    assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+    assert!(atom.contains("<title>Dev log</title>"));
+    assert!(atom.contains(&format!("<id>{}</id>", guid)));
+    assert!(atom.contains("<title>Shipping &lt;v2&gt; &amp; more</title>"));
+    assert!(atom.contains("<category term=\"devlog\"/>"));
+    assert!(!atom.contains("Team only"));
+
+    let rss = response["public"]["rssFeed"].as_str().unwrap();
+    assert!(rss.contains("<rss version=\"2.0\">"));
+    assert!(rss.contains(&format!("<guid isPermaLink=\"false\">{}</guid>", guid)));
+    assert!(rss.contains("<link>https://example.com/devlog</link>"));
+    assert!(!rss.contains("Team only"));
+}
+
+/// Test appending to and editing a range of an entry long enough to be stored in chunks
+#[tokio::test]
+async fn test_append_and_replace_range() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    let chapter = "Another page of the long voyage. ".repeat(1000);
+    assert!(chapter.len() > CHUNK_SIZE * 3);
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Logbook".to_string(),
+                    content: chapter.clone(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::AppendToEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    text: "Land ahoy! #arrival".to_string(),
+                },
+            );
+            // "Another" -> "One more", in the second chunk
+            block.with_operation(
+                application_id,
+                Operation::ReplaceRange {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    offset: 33 * 300,
+                    len: "Another".len() as u64,
+                    text: "One more".to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let mut expected = format!("{}Land ahoy! #arrival", chapter);
+    expected.replace_range(33 * 300..33 * 300 + "Another".len(), "One more");
+
+    let query = r#"
+        query {
+            entry(id: 0) { content tags wordCount }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entry"]["content"], expected);
+    assert_eq!(response["entry"]["tags"], serde_json::json!(["arrival"]));
+    assert_eq!(response["entry"]["wordCount"], 6 * 1000 + 1 + 3);
+
+    // Edits at the start, across a chunk boundary and at the end keep the header and
+    // the statistics in step with the content. Each offset is in the content the
+    // previous edits leave.
+    let edits = [
+        (0, "Another".len(), "The first"),
+        (CHUNK_SIZE + 1, 1, ""),
+        (expected.rfind("#arrival").unwrap() + 1, "#arrival".len(), "#landfall"),
+    ];
+    assert_eq!(expected.as_bytes()[CHUNK_SIZE - 1], b' ');
+    chain
+        .add_block(|block| {
+            for (offset, len, text) in edits {
+                block.with_operation(
+                    application_id,
+                    Operation::ReplaceRange {
+                        secret_phrase: secret_phrase.to_string(),
+                        entry_id: 0,
+                        offset: offset as u64,
+                        len: len as u64,
+                        text: text.to_string(),
+                    },
+                );
+            }
+        })
+        .await
+        .unwrap();
+    for (offset, len, text) in edits {
+        expected.replace_range(offset..offset + len, text);
+    }
+
+    let query = r#"
+        query {
+            entry(id: 0) { content }
+            entries(filter: { tags: ["landfall"] }) { id tags wordCount excerpt }
+            arrivals: entries(filter: { tags: ["arrival"] }) { id }
+            stats(top: 1) { totalWords topTags { term count } }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let words = expected.split_whitespace().count();
+    assert_eq!(response["entry"]["content"], expected);
+    assert_eq!(response["entries"][0]["tags"], serde_json::json!(["landfall"]));
+    assert_eq!(response["entries"][0]["wordCount"], words);
+    assert!(response["entries"][0]["excerpt"]
+        .as_str()
+        .unwrap()
+        .starts_with("The first page of the long voyage."));
+    assert_eq!(response["arrivals"], serde_json::json!([]));
+    assert_eq!(response["stats"]["totalWords"], words);
+    assert_eq!(response["stats"]["topTags"][0]["term"], "landfall");
+
+    // A range past the end of the content is rejected
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::ReplaceRange {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    offset: expected.len() as u64 + 1,
+                    len: 0,
+                    text: "past the end".to_string(),
+                },
+            );
+        })
+        .await;
+    assert!(result.is_err());
+}
+
+/// Test patches on the current revision, merged over other changes, and conflicting
+#[tokio::test]
+async fn test_patch_entry() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    let edit = |offset: u64, len: u64, text: &str| ContentEdit {
+        offset,
+        len,
+        text: text.to_string(),
+    };
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Plans".to_string(),
+                    content: "Morning: run. Evening: read.".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Two devices edit revision 0: one the morning, the other the evening
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::PatchEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    base_revision: 0,
+                    patch: vec![edit(9, 3, "swim")],
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::PatchEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    base_revision: 0,
+                    patch: vec![edit(23, 4, "write")],
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = "query { entry(id: 0) { content revision } entries { revision } }";
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entry"]["content"], "Morning: swim. Evening: write.");
+    assert_eq!(response["entry"]["revision"], 2);
+    assert_eq!(response["entries"][0]["revision"], 2);
+
+    // A third edit of the morning on revision 0 touches what revision 1 changed
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::PatchEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    base_revision: 0,
+                    patch: vec![edit(9, 3, "walk")],
+                },
+            );
+        })
+        .await;
+    assert!(result.is_err());
+    // Several edits in one patch, each with offsets in the content before the patch
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::PatchEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 0,
+                    base_revision: 2,
+                    patch: vec![edit(0, 0, "#plans "), edit(30, 0, " #done")],
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = "query { entries { tags wordCount revision } stats { totalWords } }";
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entries"][0]["tags"], serde_json::json!(["plans", "done"]));
+    assert_eq!(response["entries"][0]["wordCount"], 6);
+    assert_eq!(response["entries"][0]["revision"], 3);
+    assert_eq!(response["stats"]["totalWords"], 6);
+}
+
+/// Test that updates and deletions expecting an older revision leave the entry unchanged
+#[tokio::test]
+async fn test_expected_revision() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    let update = |title: &str, expected_revision: Option<u64>| Operation::UpdateEntry {
+        secret_phrase: secret_phrase.to_string(),
+        entry_id: 0,
+        title: Some(title.to_string()),
+        content: None,
+        metadata: None,
+        visibility: None,
+        expected_revision,
+    };
+    let delete = |expected_revision: Option<u64>| Operation::DeleteEntry {
+        secret_phrase: secret_phrase.to_string(),
+        entry_id: 0,
+        expected_revision,
+    };
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::AddEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    title: "Draft".to_string(),
+                    content: "Notes".to_string(),
+                    notebook_id: None,
+                    metadata: None,
+                    visibility: None,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // The first device read revision 0 and saves
+    chain
+        .add_block(|block| {
+            block.with_operation(application_id, update("From the laptop", Some(0)));
+        })
+        .await
+        .unwrap();
+
+    // The second device also read revision 0, and would overwrite the first. The
+    // operations respond with the conflict instead of failing the block.
+    chain
+        .add_block(|block| {
+            block.with_operation(application_id, update("From the phone", Some(0)));
+            block.with_operation(application_id, delete(Some(0)));
+        })
+        .await
+        .unwrap();
+
+    let query = "query { entry(id: 0) { title revision } }";
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["entry"]["title"], "From the laptop");
+    assert_eq!(response["entry"]["revision"], 1);
+
+    // Without an expected revision, or with the current one, it goes through
+    chain
+        .add_block(|block| {
+            block.with_operation(application_id, update("From the phone", None));
+            block.with_operation(application_id, delete(Some(2)));
+        })
+        .await
+        .unwrap();
+    let response: serde_json::Value = chain
+        .graphql_query(application_id, "query { entryCount }")
+        .await;
+    assert_eq!(response["entryCount"], 0);
+
+    // An expected revision of a missing entry is not taken as a match
+    let mutation = format!(
+        r#"mutation {{
+            deleteEntry(secretPhrase: "{}", entryId: 0, expectedRevision: 2) {{ success }}
+        }}"#,
+        secret_phrase
+    );
+    let error = chain
+        .try_graphql_query(application_id, mutation.as_str())
+        .await
+        .expect_err("deleting a missing entry");
+    assert!(format!("{:?}", error).contains("Entry not found"));
+}
+
+/// Test the error GraphQL clients get for a revision conflict
+#[test]
+fn test_revision_conflict_error() {
+    let conflict = RevisionConflict {
+        entry_id: 3,
+        expected: 1,
+        actual: 2,
+    };
+    let error = conflict.extend();
+    assert_eq!(
+        error.message,
+        "Entry 3 is at revision 2, not the expected revision 1"
+    );
+
+    let extensions = serde_json::to_value(error.extensions.unwrap()).unwrap();
+    assert_eq!(
+        extensions,
+        serde_json::json!({ "code": "REVISION_CONFLICT", "entryId": 3, "expected": 1, "actual": 2 })
+    );
+    assert_eq!(
+        serde_json::from_value::<RevisionConflict>(extensions).unwrap(),
+        conflict
+    );
+}
+
+/// Test proposing and cancelling an owner transfer, and that only the proposed owner
+/// can accept it
+#[tokio::test]
+async fn test_owner_transfer() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+    let new_owner = AccountOwner::from(validator.new_chain().await.public_key());
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::ProposeOwnerTransfer {
+                    secret_phrase: secret_phrase.to_string(),
+                    new_owner,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            owner
+            pendingOwnerTransfer { newOwner proposedAt expiresAt }
+            ownershipLog { action owner newOwner }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let owner = response["owner"].as_str().unwrap().to_string();
+    let transfer = &response["pendingOwnerTransfer"];
+    assert_eq!(transfer["newOwner"], new_owner.to_string());
+    assert_eq!(
+        transfer["expiresAt"].as_u64().unwrap() - transfer["proposedAt"].as_u64().unwrap(),
+        OWNER_TRANSFER_EXPIRY_MICROS
+    );
+
+    // The current owner's key cannot accept a transfer to another key
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::AcceptOwnership);
+        })
+        .await;
+    assert!(result.is_err());
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::CancelOwnerTransfer {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["owner"], owner);
+    assert!(response["pendingOwnerTransfer"].is_null());
+    assert_eq!(
+        response["ownershipLog"],
+        serde_json::json!([
+            { "action": "PROPOSED", "owner": owner, "newOwner": new_owner.to_string() },
+            { "action": "CANCELLED", "owner": owner, "newOwner": new_owner.to_string() },
+        ])
+    );
+}
+
+/// Test that the proposed owner accepts from their own chain and key, and takes over the
+/// diary once its chain receives the acceptance
+#[tokio::test]
+async fn test_accept_ownership() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+    let new_owner_chain = validator.new_chain().await;
+    let new_owner = AccountOwner::from(new_owner_chain.public_key());
+
+    let secret_phrase = "my-super-secret-phrase";
+    let add_entry = || Operation::AddEntry {
+        secret_phrase: secret_phrase.to_string(),
+        title: "Handover".to_string(),
+        content: "Notes for the next maintainer".to_string(),
+        notebook_id: None,
+        metadata: None,
+        visibility: None,
+    };
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::ProposeOwnerTransfer {
+                    secret_phrase: secret_phrase.to_string(),
+                    new_owner,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = "query { owner }";
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    let owner = response["owner"].as_str().unwrap().to_string();
+
+    new_owner_chain
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::AcceptOwnership);
+        })
+        .await
+        .unwrap();
+    chain.handle_received_messages().await;
+
+    let query = r#"
+        query {
+            owner
+            pendingOwnerTransfer { newOwner }
+            ownershipLog { action owner newOwner }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["owner"], new_owner.to_string());
+    assert!(response["pendingOwnerTransfer"].is_null());
+    assert_eq!(
+        response["ownershipLog"],
+        serde_json::json!([
+            { "action": "PROPOSED", "owner": owner, "newOwner": new_owner.to_string() },
+            { "action": "ACCEPTED", "owner": owner, "newOwner": new_owner.to_string() },
+        ])
+    );
+
+    // The previous owner's key can no longer write, even with the secret phrase
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(application_id, add_entry());
+        })
+        .await;
+    assert!(result.is_err());
+}
+
+/// Test that a transfer can no longer be accepted once it expired
+#[tokio::test]
+async fn test_accept_expired_ownership() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let application_id = chain
+        .create_application(bytecode_id, None, (), vec![])
+        .await;
+    let new_owner_chain = validator.new_chain().await;
+    let new_owner = AccountOwner::from(new_owner_chain.public_key());
+
+    let secret_phrase = "my-super-secret-phrase";
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                application_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            block.with_operation(
+                application_id,
+                Operation::ProposeOwnerTransfer {
+                    secret_phrase: secret_phrase.to_string(),
+                    new_owner,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = r#"
+        query {
+            owner
+            pendingOwnerTransfer { newOwner }
+            ownershipLog { action }
+        }
+    "#;
+    let before: serde_json::Value = chain.graphql_query(application_id, query).await;
+
+    validator
+        .clock()
+        .add(TimeDelta::from_micros(OWNER_TRANSFER_EXPIRY_MICROS + 1));
+    new_owner_chain
+        .add_block(|block| {
+            block.with_operation(application_id, Operation::AcceptOwnership);
+        })
+        .await
+        .unwrap();
+    chain.handle_received_messages().await;
+
+    // The acceptance is rejected and the proposal stays until it is cancelled or replaced
+    let response: serde_json::Value = chain.graphql_query(application_id, query).await;
+    assert_eq!(response["owner"], before["owner"]);
+    assert_eq!(response["pendingOwnerTransfer"]["newOwner"], new_owner.to_string());
+    assert_eq!(
+        response["ownershipLog"],
+        serde_json::json!([{ "action": "PROPOSED" }])
+    );
+}
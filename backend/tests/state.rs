@@ -0,0 +1,249 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the application state on its own, in a mock key-value store.
+
+#[allow(dead_code)]
+#[path = "../src/state.rs"]
+mod state;
+
+use diary_backend::{
+    calendar::MICROS_PER_DAY,
+    storage::{EntryV1, SCHEMA_VERSION},
+    Visibility,
+};
+use linera_sdk::views::{KeyValueStore, View, ViewStorageContext};
+use state::{DiaryEntry, DiaryState, EntryFilter, DEFAULT_NOTEBOOK_ID};
+
+/// Load the state from an empty store, without a schema version
+async fn unversioned_state() -> DiaryState {
+    let context = ViewStorageContext::new_unsafe(KeyValueStore::mock().to_mut(), Vec::new(), ());
+    DiaryState::load(context)
+        .await
+        .expect("Failed to load state")
+}
+
+/// Load the state from an empty store, in the current layout
+async fn empty_state() -> DiaryState {
+    let mut state = unversioned_state().await;
+    state.schema_version.set(SCHEMA_VERSION);
+    state
+}
+
+/// An entry created at the start of a day since the epoch
+fn entry_on_day(id: u64, day: u64) -> DiaryEntry {
+    let timestamp = day * MICROS_PER_DAY;
+    DiaryEntry {
+        id,
+        title: format!("Day {}", day),
+        content: String::new(),
+        timestamp,
+        created_at: timestamp,
+        notebook_id: 0,
+        metadata: Default::default(),
+        attachments: Vec::new(),
+        visibility: Default::default(),
+        revision: 0,
+    }
+}
+
+/// IDs of the entries matching a filter, in ascending order
+async fn matching_ids(state: &DiaryState, filter: EntryFilter) -> Vec<u64> {
+    let mut ids: Vec<u64> = state
+        .query_entries(&filter, None, |_| true, None)
+        .await
+        .unwrap()
+        .iter()
+        .map(|entry| entry.id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// IDs of the entries created between two days, both included
+async fn created_between(state: &DiaryState, first: u64, last: u64) -> Vec<u64> {
+    let filter = EntryFilter {
+        created_after: Some(first * MICROS_PER_DAY),
+        created_before: Some(last * MICROS_PER_DAY),
+        ..EntryFilter::default()
+    };
+    matching_ids(state, filter).await
+}
+
+/// Test that removing the links of a deleted entry also drops the links to it
+#[tokio::test]
+async fn test_remove_links() {
+    let mut state = empty_state().await;
+
+    // Entries 1 and 2 link to entry 0 in their content, and entry 0 links to entry 2
+    state.set_links(1, Some(vec![0]), None).await.unwrap();
+    state.set_links(2, Some(vec![0, 1]), Some(vec![0])).await.unwrap();
+    state.set_links(0, None, Some(vec![2])).await.unwrap();
+    assert_eq!(state.get_backlink_ids(0).await, vec![1, 2]);
+
+    state.remove_links(0).await.unwrap();
+
+    assert!(state.get_linked_ids(0).await.is_empty());
+    assert!(state.get_backlink_ids(0).await.is_empty());
+    assert!(state.backlinks.get(&0).await.unwrap().is_none());
+    assert!(state.content_links.get(&1).await.unwrap().is_none());
+    assert_eq!(state.get_linked_ids(2).await, vec![1]);
+    assert!(state.explicit_links.get(&2).await.unwrap().is_none());
+    assert!(state.get_backlink_ids(2).await.is_empty());
+    assert_eq!(state.get_backlink_ids(1).await, vec![2]);
+}
+
+/// Test that entries are indexed by the day they were created, and that time ranges
+/// are planned from that index
+#[tokio::test]
+async fn test_creation_days() {
+    let mut state = empty_state().await;
+    for (id, day) in [(0, 10), (1, 10), (2, 40), (3, 400)] {
+        let entry = entry_on_day(id, day);
+        state.write_entry(entry.clone()).await.unwrap();
+        state.index_entry(&entry, true).await.unwrap();
+    }
+    state.entry_count.set(4);
+    assert_eq!(state.creation_days.get(&10).await.unwrap(), Some(vec![0, 1]));
+
+    // Indexing an entry twice lists it once
+    let mut entry = entry_on_day(4, 40);
+    state.write_entry(entry.clone()).await.unwrap();
+    state.index_entry(&entry, true).await.unwrap();
+    state.index_entry(&entry, true).await.unwrap();
+    state.entry_count.set(5);
+    assert_eq!(state.creation_days.get(&40).await.unwrap(), Some(vec![2, 4]));
+
+    // Short ranges read each day, long and open ones the list of days
+    assert_eq!(created_between(&state, 10, 40).await, vec![0, 1, 2, 4]);
+    assert_eq!(created_between(&state, 11, 400).await, vec![2, 3, 4]);
+    let filter = EntryFilter {
+        created_after: Some(41 * MICROS_PER_DAY),
+        ..EntryFilter::default()
+    };
+    assert_eq!(matching_ids(&state, filter).await, vec![3]);
+
+    // Removing an entry from the index drops the day once it is empty
+    state.index_entry(&entry, false).await.unwrap();
+    entry.id = 2;
+    state.index_entry(&entry, false).await.unwrap();
+    assert!(state.creation_days.get(&40).await.unwrap().is_none());
+}
+
+/// Test that a query stops reading entries once more than `max_results` of the visible
+/// ones match
+#[tokio::test]
+async fn test_query_stops_after_max_results() {
+    let mut state = empty_state().await;
+    for id in 0..6 {
+        let entry = entry_on_day(id, 10 + id);
+        state.write_entry(entry.clone()).await.unwrap();
+        state.index_entry(&entry, true).await.unwrap();
+    }
+    state.entry_count.set(6);
+
+    let filter = EntryFilter::default();
+    let odd = |entry: &state::EntryHeader| entry.id % 2 == 1;
+    let entries = state.query_entries(&filter, None, odd, None).await.unwrap();
+    assert_eq!(entries.len(), 3);
+    let entries = state.query_entries(&filter, None, odd, Some(1)).await.unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // Planned queries stop the same way
+    let filter = EntryFilter {
+        created_after: Some(10 * MICROS_PER_DAY),
+        created_before: Some(20 * MICROS_PER_DAY),
+        ..EntryFilter::default()
+    };
+    let entries = state.query_entries(&filter, None, |_| true, Some(3)).await.unwrap();
+    assert_eq!(entries.len(), 4);
+}
+
+/// Test that entries written before versioning, in the baseline layout, are read while
+/// they wait for the migration, join the default notebook, the indexes and the statistics
+/// once migrated, and are written in the current layout afterwards
+#[tokio::test]
+async fn test_migrate_baseline_entries() {
+    let mut state = unversioned_state().await;
+
+    // Written by the contract before versioning: an initialized diary without notebooks
+    state
+        .secret_phrase_hash
+        .set(diary_backend::hash_secret_phrase("secret"));
+    for id in 0..3 {
+        let entry = EntryV1 {
+            id,
+            title: format!("Entry {}", id),
+            content: format!("Written on day {} #old", 10 + id),
+            timestamp: (10 + id) * MICROS_PER_DAY,
+        };
+        state.legacy_entries.insert(&id, entry).unwrap();
+    }
+    state.entry_count.set(3);
+    assert_eq!(state.get_schema_version(), 1);
+    assert_eq!(state.get_entry(1).await.unwrap().title, "Entry 1");
+    assert_eq!(state.get_entry_count().await, 3);
+
+    // A small budget leaves the entries in both layouts, still readable and counted.
+    // Filters scan every entry while the indexes are incomplete.
+    assert!(!state.migrate(2, 5).await.unwrap());
+    assert_eq!(state.get_schema_version(), 1);
+    assert_eq!(state.get_entry(0).await.unwrap().title, "Entry 0");
+    assert_eq!(state.get_entry(2).await.unwrap().title, "Entry 2");
+    assert_eq!(state.get_entry_count().await, 3);
+    assert_eq!(created_between(&state, 10, 12).await, vec![0, 1, 2]);
+
+    // An entry about to be edited is migrated ahead of its batch
+    state.migrate_entry(2).await.unwrap();
+    assert!(state.legacy_entries.get(&2).await.unwrap().is_none());
+
+    assert!(state.migrate(100, 5).await.unwrap());
+    assert_eq!(state.get_schema_version(), SCHEMA_VERSION);
+    assert_eq!(state.legacy_entries.count().await.unwrap(), 0);
+    let notebook = state.get_notebook(DEFAULT_NOTEBOOK_ID).await.unwrap();
+    assert_eq!(notebook.created_at, 5);
+    assert_eq!(*state.notebook_count.get(), DEFAULT_NOTEBOOK_ID + 1);
+
+    for id in 0..3 {
+        let entry = state.get_entry(id).await.unwrap();
+        assert_eq!(entry.title, format!("Entry {}", id));
+        assert_eq!(entry.content, format!("Written on day {} #old", 10 + id));
+        assert_eq!(entry.timestamp, (10 + id) * MICROS_PER_DAY);
+        assert_eq!(entry.created_at, entry.timestamp);
+        assert_eq!(entry.notebook_id, DEFAULT_NOTEBOOK_ID);
+        assert_eq!(entry.visibility, Visibility::default());
+        assert_eq!(state.headers.get(&id).await.unwrap().unwrap().version(), 1);
+    }
+    assert_eq!(state.creation_days.get(&11).await.unwrap(), Some(vec![1]));
+    assert_eq!(
+        state.tag_index.get("old").await.unwrap(),
+        Some(vec![0, 1, 2])
+    );
+    assert_eq!(state.get_total_words(&[DEFAULT_NOTEBOOK_ID]).await, 15);
+
+    // A new entry and an edit, as the contract writes them
+    let entry = entry_on_day(3, 20);
+    state.write_entry(entry.clone()).await.unwrap();
+    state.index_entry(&entry, true).await.unwrap();
+    state.entry_count.set(4);
+
+    let mut edited = state.get_entry(1).await.unwrap();
+    state.index_entry(&edited, false).await.unwrap();
+    edited.content = "Rewritten #new".to_string();
+    state.index_entry(&edited, true).await.unwrap();
+    state.write_entry(edited).await.unwrap();
+
+    assert_eq!(state.get_header(1).await.unwrap().excerpt, "Rewritten #new");
+    assert_eq!(state.get_entry(1).await.unwrap().content, "Rewritten #new");
+    assert_eq!(state.get_entry(1).await.unwrap().created_at, 11 * MICROS_PER_DAY);
+    assert_eq!(state.tag_index.get("old").await.unwrap(), Some(vec![0, 2]));
+    assert_eq!(state.tag_index.get("new").await.unwrap(), Some(vec![1]));
+    assert_eq!(state.get_total_words(&[DEFAULT_NOTEBOOK_ID]).await, 12);
+    assert_eq!(state.get_entry_count().await, 4);
+    assert_eq!(created_between(&state, 10, 20).await, vec![0, 1, 2, 3]);
+
+    // Deleted entries leave the count but not the next entry ID
+    state.remove_entry(0).await.unwrap();
+    assert_eq!(state.get_entry_count().await, 3);
+    assert_eq!(*state.entry_count.get(), 4);
+}
@@ -0,0 +1,220 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the versioned encodings of stored entries.
+
+use diary_backend::{
+    storage::{
+        self, ChunkRef, EntryV1, StoredBody, StoredHeader, CHUNK_SIZE, COMPRESSION_THRESHOLD,
+    },
+    DiaryEntry, EntryHeader, EntryMetadata, Mood, Visibility, EXCERPT_LENGTH,
+};
+use linera_sdk::bcs;
+
+fn entry_v1() -> EntryV1 {
+    EntryV1 {
+        id: 3,
+        title: "Day one".to_string(),
+        content: "Hello #first".to_string(),
+        timestamp: 1_700_000_100_000_000,
+    }
+}
+
+/// Test that entries stored before versioning read back in the current layout
+#[test]
+fn test_read_v1_entry_as_current() {
+    // Untagged, as written by the contract before versioning
+    let bytes = bcs::to_bytes(&entry_v1()).unwrap();
+    let legacy: EntryV1 = bcs::from_bytes(&bytes).unwrap();
+
+    let entry = DiaryEntry::from(legacy);
+    assert_eq!(entry.id, 3);
+    assert_eq!(entry.title, "Day one");
+    assert_eq!(entry.content, "Hello #first");
+    assert_eq!(entry.created_at, 1_700_000_100_000_000);
+    assert_eq!(entry.notebook_id, 0);
+    assert_eq!(entry.metadata, EntryMetadata::default());
+    assert!(entry.attachments.is_empty());
+    assert_eq!(entry.visibility, Visibility::default());
+}
+
+/// Test that the version tag comes first and selects the layout, and that written
+/// headers use the current one
+#[test]
+fn test_stored_header_encoding() {
+    let mut entry = DiaryEntry::from(entry_v1());
+    entry.notebook_id = 1;
+    entry.metadata.mood = Some(Mood::Good);
+    entry.visibility = Visibility::Public;
+    let header = EntryHeader::from(&entry);
+
+    let bytes = bcs::to_bytes(&StoredHeader::from(header.clone())).unwrap();
+    assert_eq!(bytes[0], 0);
+    assert_eq!(bytes[1..], bcs::to_bytes(&header).unwrap()[..]);
+
+    let stored: StoredHeader = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(stored.version(), 1);
+    let decoded = stored.into_header();
+    assert_eq!(decoded.tags, vec!["first"]);
+    assert_eq!(decoded.notebook_id, 1);
+    assert_eq!(decoded.metadata.mood, Some(Mood::Good));
+    assert_eq!(decoded.visibility, Visibility::Public);
+}
+
+/// Test that an entry splits into a header and a body and reassembles unchanged
+#[test]
+fn test_entry_header_and_body() {
+    let mut entry = DiaryEntry::from(entry_v1());
+    entry.content = format!("Hello #first\n\n{}", "A long walk by the sea. ".repeat(20));
+    entry.metadata.rating = Some(4);
+
+    let header = EntryHeader::from(&entry);
+    assert_eq!(header.tags, vec!["first"]);
+    assert_eq!(header.word_count, 2 + 6 * 20);
+    assert!(header.excerpt.starts_with("Hello #first A long walk"));
+    assert!(header.excerpt.ends_with('…'));
+    assert!(header.excerpt.chars().count() <= EXCERPT_LENGTH);
+
+    let header_bytes = bcs::to_bytes(&StoredHeader::from(header)).unwrap();
+    let body_bytes = bcs::to_bytes(&StoredBody::from(entry.content.clone())).unwrap();
+    let header: StoredHeader = bcs::from_bytes(&header_bytes).unwrap();
+    let body: StoredBody = bcs::from_bytes(&body_bytes).unwrap();
+    assert_eq!(header.version(), 2);
+
+    let decoded = DiaryEntry::from_parts(header.into_header(), body.into_content());
+    assert_eq!(decoded.content, entry.content);
+    assert_eq!(decoded.created_at, entry.created_at);
+    assert_eq!(decoded.metadata.rating, Some(4));
+}
+
+/// A long-form Markdown entry, as written day to day
+fn markdown_entry(days: usize) -> String {
+    (1..=days)
+        .map(|day| {
+            format!(
+                "## Day {day}\n\n\
+                 Woke up early and went for a run along the river. The weather was \
+                 *cool and clear*, and the path was quiet for most of the way.\n\n\
+                 ### Work\n\n\
+                 - Reviewed the pull requests for the storage migration\n\
+                 - Fixed the flaky test in the sync module\n\
+                 - Wrote notes for the [[entry:{day}]] follow-up\n\n\
+                 ### Evening\n\n\
+                 Cooked dinner, read two chapters and went to bed before eleven. #running #work\n\n"
+            )
+        })
+        .collect()
+}
+
+/// Test that long bodies are compressed and read back unchanged, and short ones are not
+#[test]
+fn test_body_compression_round_trip() {
+    let short = "Hello #first".to_string();
+    assert!(matches!(StoredBody::from(short.clone()), StoredBody::Plain(_)));
+
+    let long = format!("Ünïcödé ✍️ {}", markdown_entry(10));
+    assert!(long.len() > COMPRESSION_THRESHOLD);
+    let body = StoredBody::from(long.clone());
+    assert!(matches!(body, StoredBody::Lz4(_)));
+
+    let bytes = bcs::to_bytes(&body).unwrap();
+    // The codec tag comes first
+    assert_eq!(bytes[0], 1);
+    let body: StoredBody = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(body.into_content(), long);
+
+    // Plain bodies written before compression still decode
+    let bytes = bcs::to_bytes(&StoredBody::Plain(long.clone())).unwrap();
+    assert_eq!(bytes[0], 0);
+    let body: StoredBody = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(body.into_content(), long);
+
+    // Text that does not compress is kept as written
+    let noise: String = (0..2000u32)
+        .map(|i| char::from_u32(0x4e00 + (i * 7919) % 20000).unwrap())
+        .collect();
+    assert!(matches!(StoredBody::from(noise.clone()), StoredBody::Plain(_)));
+    assert_eq!(StoredBody::from(noise.clone()).into_content(), noise);
+}
+
+/// Test the space saved on typical Markdown entries
+#[test]
+fn test_body_compression_savings() {
+    for days in [3, 7, 30] {
+        let content = markdown_entry(days);
+        let body = StoredBody::from(content.clone());
+        let ratio = body.stored_len() as f64 / content.len() as f64;
+        assert!(
+            ratio < 0.5,
+            "{} days: {} of {} bytes",
+            days,
+            body.stored_len(),
+            content.len()
+        );
+    }
+}
+
+/// Test that long contents split into chunks at character boundaries
+#[test]
+fn test_split_chunks() {
+    assert!(storage::split_chunks("").is_empty());
+    assert_eq!(storage::split_chunks("short"), vec!["short"]);
+
+    // Two-byte characters, so the limit falls in the middle of one
+    let content = "é".repeat(CHUNK_SIZE + 1);
+    let chunks = storage::split_chunks(&content);
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks.iter().all(|chunk| chunk.len() <= CHUNK_SIZE));
+    assert_eq!(chunks[0].len(), CHUNK_SIZE);
+    assert_eq!(chunks.concat(), content);
+
+    // Chunks are bodies of their own, compressed like any other
+    let content = markdown_entry(30);
+    let chunks = storage::split_chunks(&content);
+    assert!(chunks.len() > 1);
+    for chunk in chunks {
+        let body = StoredBody::from(chunk.to_string());
+        assert!(matches!(body, StoredBody::Lz4(_)));
+        assert_eq!(body.into_content(), chunk);
+    }
+}
+
+/// Test that chunks end after whitespace and outside of links, so that each chunk can be
+/// indexed on its own
+#[test]
+fn test_split_chunks_at_words() {
+    let content = "word #tag ".repeat(CHUNK_SIZE / 5);
+    let chunks = storage::split_chunks(&content);
+    assert!(chunks.len() > 1);
+    assert_eq!(chunks.concat(), content);
+    for chunk in &chunks {
+        assert!(chunk.len() <= CHUNK_SIZE);
+        assert!(chunk.ends_with(' '));
+        assert_eq!(ChunkRef::new(0, chunk).tags, vec!["tag"]);
+    }
+
+    // A link open at the limit moves to the next chunk whole
+    let padding = "a ".repeat((CHUNK_SIZE - 4) / 2);
+    let content = format!("{}[[entry:12]] after", padding);
+    let chunks = storage::split_chunks(&content);
+    assert_eq!(chunks, vec![padding.as_str(), "[[entry:12]] after"]);
+    assert_eq!(ChunkRef::new(1, chunks[1]).links, vec![12]);
+    assert!(ChunkRef::new(0, chunks[0]).links.is_empty());
+}
+
+/// Test that the length of a body's content is known without decompressing it
+#[test]
+fn test_body_content_len() {
+    let content = markdown_entry(7);
+    let body = StoredBody::from(content.clone());
+    assert!(matches!(body, StoredBody::Lz4(_)));
+    assert_eq!(body.content_len(), content.len());
+    assert_eq!(StoredBody::from("short".to_string()).content_len(), 5);
+
+    let chunks = storage::split_chunks(&content)
+        .into_iter()
+        .zip(0..)
+        .map(|(text, id)| ChunkRef::new(id, text))
+        .collect();
+    assert_eq!(StoredBody::Chunked(chunks).content_len(), content.len());
+}
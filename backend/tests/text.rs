@@ -0,0 +1,29 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the text helpers used to index and display entries.
+
+use diary_backend::text;
+
+/// Test which `[[entry:42]]` links are found in content
+#[test]
+fn test_extract_entry_links() {
+    assert_eq!(
+        text::extract_entry_links("See [[entry:3]], [[entry: 1 ]] and [[entry:3]] again"),
+        vec![3, 1]
+    );
+    assert_eq!(text::extract_entry_links("[[entry:x]] [[entry:2"), Vec::<u64>::new());
+    // An unclosed link does not swallow the next one
+    assert_eq!(text::extract_entry_links("[[entry: foo [[entry:3]]"), vec![3]);
+    assert_eq!(text::extract_entry_links("[[entry:[[entry:4]] [[entry:5]]"), vec![4, 5]);
+}
+
+/// Test where excerpts are cut
+#[test]
+fn test_excerpt() {
+    assert_eq!(text::excerpt("  Short\n\nand sweet ", 20), "Short and sweet");
+    assert_eq!(text::excerpt("one two three", 10), "one two…");
+    assert_eq!(text::excerpt("one two three", 8), "one two…");
+    assert_eq!(text::excerpt("supercalifragilistic", 6), "super…");
+    assert_eq!(text::excerpt("", 10), "");
+}
@@ -0,0 +1,307 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for moving a diary to a successor application on the same chain.
+
+use diary_backend::{
+    patch::ContentEdit, upgrade::EXPORT_BATCH_SIZE, DiaryAbi, Operation, Visibility,
+};
+use linera_sdk::{linera_base_types::AccountOwner, test::TestValidator};
+
+/// Test exporting a diary in batches and reading it from the successor
+#[tokio::test]
+async fn test_export_to_successor() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let predecessor_id = chain.create_application(bytecode_id, None, (), vec![]).await;
+    let successor_id = chain.create_application(bytecode_id, None, (), vec![]).await;
+    let new_owner = AccountOwner::from(validator.new_chain().await.public_key());
+
+    let secret_phrase = "my-super-secret-phrase";
+    let members_phrase = "team-phrase";
+    let entry_count = EXPORT_BATCH_SIZE + 10;
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                predecessor_id,
+                Operation::Initialize {
+                    secret_phrase: secret_phrase.to_string(),
+                },
+            );
+            for i in 0..entry_count {
+                block.with_operation(
+                    predecessor_id,
+                    Operation::AddEntry {
+                        secret_phrase: secret_phrase.to_string(),
+                        title: format!("Day {}", i),
+                        content: "Shipped it #devlog, see [[entry:0]]".to_string(),
+                        notebook_id: None,
+                        metadata: None,
+                        visibility: (i == 1).then_some(Visibility::Public),
+                    },
+                );
+            }
+            block.with_operation(
+                predecessor_id,
+                Operation::ReplaceRange {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 2,
+                    offset: 0,
+                    len: 7,
+                    text: "Planned".to_string(),
+                },
+            );
+            block.with_operation(
+                predecessor_id,
+                Operation::Favorite {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 3,
+                },
+            );
+            block.with_operation(
+                predecessor_id,
+                Operation::SetMembersPhrase {
+                    secret_phrase: secret_phrase.to_string(),
+                    members_phrase: Some(members_phrase.to_string()),
+                },
+            );
+            block.with_operation(
+                predecessor_id,
+                Operation::ProposeOwnerTransfer {
+                    secret_phrase: secret_phrase.to_string(),
+                    new_owner,
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                successor_id,
+                Operation::RegisterPredecessor {
+                    predecessor: predecessor_id.forget_abi(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Two batches of entries, the second one ends the export
+    for _ in 0..2 {
+        chain
+            .add_block(|block| {
+                block.with_operation(
+                    predecessor_id,
+                    Operation::ExportToSuccessor {
+                        secret_phrase: secret_phrase.to_string(),
+                        successor: successor_id.forget_abi(),
+                    },
+                );
+            })
+            .await
+            .unwrap();
+    }
+
+    let query = r#"
+        query {
+            exportProgress { nextEntryId finished }
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(predecessor_id, query).await;
+    assert_eq!(response["exportProgress"]["nextEntryId"], entry_count);
+    assert_eq!(response["exportProgress"]["finished"], true);
+
+    let query = format!(
+        r#"query {{
+            entryCount
+            importingFrom
+            entry(id: 3, readerPhrase: "{members_phrase}") {{ title }}
+            favorites(readerPhrase: "{members_phrase}") {{ id }}
+            backlinks(id: 0, readerPhrase: "{members_phrase}") {{ id }}
+            stats(top: 1) {{ topTags {{ term count }} }}
+            anyone: entries {{ id }}
+            public {{ entryCount }}
+            pendingOwnerTransfer {{ newOwner }}
+            ownershipLog {{ action newOwner }}
+        }}"#
+    );
+    let response: serde_json::Value = chain.graphql_query(successor_id, &query).await;
+    assert_eq!(response["entryCount"], entry_count);
+    assert_eq!(response["importingFrom"], serde_json::Value::Null);
+    assert_eq!(response["entry"]["title"], "Day 3");
+    assert_eq!(response["favorites"][0]["id"], 3);
+    assert_eq!(
+        response["backlinks"].as_array().unwrap().len() as u64,
+        entry_count - 1
+    );
+    assert_eq!(response["stats"]["topTags"][0]["count"], entry_count);
+    // The members phrase and the public entries moved along
+    assert_eq!(response["anyone"], serde_json::json!([{ "id": 1 }]));
+    assert_eq!(response["public"]["entryCount"], 1);
+    // So did the owner transfer and its log
+    assert_eq!(
+        response["pendingOwnerTransfer"]["newOwner"],
+        new_owner.to_string()
+    );
+    assert_eq!(
+        response["ownershipLog"],
+        serde_json::json!([{ "action": "PROPOSED", "newOwner": new_owner.to_string() }])
+    );
+
+    // The old diary is read-only, the successor takes new entries
+    let add_entry = || Operation::AddEntry {
+        secret_phrase: secret_phrase.to_string(),
+        title: "After the move".to_string(),
+        content: "Hello".to_string(),
+        notebook_id: None,
+        metadata: None,
+        visibility: None,
+    };
+    assert!(chain
+        .add_block(|block| {
+            block.with_operation(predecessor_id, add_entry());
+        })
+        .await
+        .is_err());
+    chain
+        .add_block(|block| {
+            block.with_operation(successor_id, add_entry());
+        })
+        .await
+        .unwrap();
+
+    // The edit history moved along, so a patch made before the move still merges
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                successor_id,
+                Operation::PatchEntry {
+                    secret_phrase: secret_phrase.to_string(),
+                    entry_id: 2,
+                    base_revision: 0,
+                    patch: vec![ContentEdit {
+                        offset: 8,
+                        len: 2,
+                        text: "all of it".to_string(),
+                    }],
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    let query = format!(
+        r#"query {{
+            entry(id: 2, readerPhrase: "{members_phrase}") {{ content revision }}
+        }}"#
+    );
+    let response: serde_json::Value = chain.graphql_query(successor_id, &query).await;
+    assert_eq!(
+        response["entry"]["content"],
+        "Planned all of it #devlog, see [[entry:0]]"
+    );
+    assert_eq!(response["entry"]["revision"], 2);
+}
+
+/// Test that imports are only accepted from the registered predecessor
+#[tokio::test]
+async fn test_import_requires_registered_predecessor() {
+    let (validator, bytecode_id) = TestValidator::with_current_bytecode::<DiaryAbi>().await;
+    let mut chain = validator.new_chain().await;
+    let predecessor_id = chain.create_application(bytecode_id, None, (), vec![]).await;
+    let other_id = chain.create_application(bytecode_id, None, (), vec![]).await;
+    let successor_id = chain.create_application(bytecode_id, None, (), vec![]).await;
+
+    let secret_phrase = "my-super-secret-phrase";
+    let export_to_successor = || Operation::ExportToSuccessor {
+        secret_phrase: secret_phrase.to_string(),
+        successor: successor_id.forget_abi(),
+    };
+
+    chain
+        .add_block(|block| {
+            for application_id in [predecessor_id, other_id] {
+                block.with_operation(
+                    application_id,
+                    Operation::Initialize {
+                        secret_phrase: secret_phrase.to_string(),
+                    },
+                );
+            }
+        })
+        .await
+        .unwrap();
+
+    // Nothing is accepted before a predecessor is registered
+    assert!(chain
+        .add_block(|block| {
+            block.with_operation(predecessor_id, export_to_successor());
+        })
+        .await
+        .is_err());
+
+    // Only the creator of the successor can register its predecessor
+    let mut other_chain = validator.new_chain().await;
+    let result = other_chain
+        .add_block(|block| {
+            block.with_operation(
+                successor_id,
+                Operation::RegisterPredecessor {
+                    predecessor: other_id.forget_abi(),
+                },
+            );
+        })
+        .await;
+    assert!(result.is_err());
+
+    chain
+        .add_block(|block| {
+            block.with_operation(
+                successor_id,
+                Operation::RegisterPredecessor {
+                    predecessor: predecessor_id.forget_abi(),
+                },
+            );
+        })
+        .await
+        .unwrap();
+
+    // Neither from another diary nor as an operation of its own
+    assert!(chain
+        .add_block(|block| {
+            block.with_operation(other_id, export_to_successor());
+        })
+        .await
+        .is_err());
+    let result = chain
+        .add_block(|block| {
+            block.with_operation(
+                successor_id,
+                Operation::ImportFromPredecessor { batch: vec![2] },
+            );
+        })
+        .await;
+    assert!(result.is_err());
+
+    let query = r#"
+        query {
+            isInitialized
+            importingFrom
+        }
+    "#;
+    let response: serde_json::Value = chain.graphql_query(successor_id, query).await;
+    assert_eq!(response["isInitialized"], false);
+    assert!(!response["importingFrom"].is_null());
+
+    chain
+        .add_block(|block| {
+            block.with_operation(predecessor_id, export_to_successor());
+        })
+        .await
+        .unwrap();
+    let response: serde_json::Value = chain.graphql_query(successor_id, query).await;
+    assert_eq!(response["isInitialized"], true);
+}
@@ -15,6 +15,9 @@ const state = {
   entries: [],
   currentEntry: null,
   loading: false,
+  // Kept in memory only: sent as the reader phrase and used to decrypt private entries
+  readerPhrase: null,
+  entryKey: null,
 };
 
 // ==========================================
@@ -69,6 +72,88 @@ function hashSecretPhrase(phrase) {
   return btoa(phrase);
 }
 
+// ==========================================
+// ENCRYPTION OF PRIVATE ENTRIES
+// ==========================================
+
+// AES-256-GCM with a key derived from the secret phrase by PBKDF2-SHA256, salted with
+// the chain ID, stored as "enc:v1:" + base64(nonce || ciphertext). The CLI uses the
+// same scheme, so private entries open in both.
+const ENCRYPTED_PREFIX = "enc:v1:";
+const KEY_ITERATIONS = 100000;
+const NONCE_LENGTH = 12;
+
+async function deriveEntryKey(secretPhrase) {
+  const encoder = new TextEncoder();
+  const material = await crypto.subtle.importKey(
+    "raw",
+    encoder.encode(secretPhrase),
+    "PBKDF2",
+    false,
+    ["deriveKey"]
+  );
+  return crypto.subtle.deriveKey(
+    {
+      name: "PBKDF2",
+      hash: "SHA-256",
+      salt: encoder.encode(config.chainId),
+      iterations: KEY_ITERATIONS,
+    },
+    material,
+    { name: "AES-GCM", length: 256 },
+    false,
+    ["encrypt", "decrypt"]
+  );
+}
+
+async function encryptText(key, text) {
+  const nonce = crypto.getRandomValues(new Uint8Array(NONCE_LENGTH));
+  const ciphertext = await crypto.subtle.encrypt(
+    { name: "AES-GCM", iv: nonce },
+    key,
+    new TextEncoder().encode(text)
+  );
+  const data = new Uint8Array(NONCE_LENGTH + ciphertext.byteLength);
+  data.set(nonce);
+  data.set(new Uint8Array(ciphertext), NONCE_LENGTH);
+  return ENCRYPTED_PREFIX + btoa(String.fromCharCode(...data));
+}
+
+async function decryptText(key, envelope) {
+  const data = Uint8Array.from(
+    atob(envelope.slice(ENCRYPTED_PREFIX.length)),
+    (c) => c.charCodeAt(0)
+  );
+  const text = await crypto.subtle.decrypt(
+    { name: "AES-GCM", iv: data.slice(0, NONCE_LENGTH) },
+    key,
+    data.slice(NONCE_LENGTH)
+  );
+  return new TextDecoder().decode(text);
+}
+
+// Decrypt the title, and the content if loaded, of a private entry in place
+async function openEntry(entry) {
+  if (entry.visibility !== "PRIVATE" || !state.entryKey) return entry;
+  try {
+    entry.title = await decryptText(state.entryKey, entry.title);
+    if (entry.content !== undefined) {
+      entry.content = await decryptText(state.entryKey, entry.content);
+    }
+  } catch (error) {
+    console.error("Error decrypting entry:", error);
+    entry.title = "🔒 Encrypted entry";
+    entry.content = entry.content !== undefined ? "" : undefined;
+  }
+  return entry;
+}
+
+// Remember the phrase for this page only, so private entries can be read and written
+async function rememberReader(secretPhrase) {
+  state.readerPhrase = secretPhrase;
+  state.entryKey = await deriveEntryKey(secretPhrase);
+}
+
 function formatDate(timestamp) {
   const date = new Date(parseInt(timestamp));
   const now = new Date();
@@ -92,11 +177,6 @@ function formatDate(timestamp) {
   }
 }
 
-function truncateText(text, maxLength = 150) {
-  if (text.length <= maxLength) return text;
-  return text.substring(0, maxLength) + "...";
-}
-
 function showStatus(message, type = "info") {
   const statusEl = document.getElementById("status");
   statusEl.textContent = message;
@@ -186,6 +266,7 @@ async function unlockDiary(secretPhrase) {
 
     if (data.unlock.success) {
       state.isUnlocked = true;
+      await rememberReader(secretPhrase);
       // Store hashed phrase for session (not the actual phrase)
       sessionStorage.setItem("diaryUnlocked", "true");
       showStatus("Diary unlocked! 🔓", "success");
@@ -206,20 +287,21 @@ async function unlockDiary(secretPhrase) {
 
 async function loadEntries() {
   const query = `
-        query {
-            entries {
+        query($readerPhrase: String) {
+            entries(readerPhrase: $readerPhrase) {
                 id
                 title
-                content
+                excerpt
                 timestamp
+                visibility
             }
         }
     `;
 
   try {
     setLoading(true);
-    const data = await client.query(query);
-    state.entries = data.entries || [];
+    const data = await client.query(query, { readerPhrase: state.readerPhrase });
+    state.entries = await Promise.all((data.entries || []).map(openEntry));
     renderEntries();
   } catch (error) {
     console.error("Error loading entries:", error);
@@ -229,10 +311,10 @@ async function loadEntries() {
   }
 }
 
-async function addEntry(title, content, secretPhrase) {
+async function addEntry(title, content, secretPhrase, isPrivate) {
   const mutation = `
-        mutation($title: String!, $content: String!, $secretPhrase: String!) {
-            addEntry(title: $title, content: $content, secretPhrase: $secretPhrase) {
+        mutation($title: String!, $content: String!, $secretPhrase: String!, $visibility: Visibility) {
+            addEntry(title: $title, content: $content, secretPhrase: $secretPhrase, visibility: $visibility) {
                 success
                 message
                 entryId
@@ -242,10 +324,18 @@ async function addEntry(title, content, secretPhrase) {
 
   try {
     setLoading(true);
+    let visibility = null;
+    if (isPrivate) {
+      const key = await deriveEntryKey(secretPhrase);
+      title = await encryptText(key, title);
+      content = await encryptText(key, content);
+      visibility = "PRIVATE";
+    }
     const data = await client.mutate(mutation, {
       title,
       content,
       secretPhrase,
+      visibility,
     });
 
     if (data.addEntry.success) {
@@ -277,6 +367,12 @@ async function updateEntry(id, title, content, secretPhrase) {
 
   try {
     setLoading(true);
+    // Private entries stay private: the edited text is encrypted again
+    if (state.currentEntry && state.currentEntry.visibility === "PRIVATE") {
+      const key = await deriveEntryKey(secretPhrase);
+      title = await encryptText(key, title);
+      content = await encryptText(key, content);
+    }
     const data = await client.mutate(mutation, {
       id: parseInt(id),
       title,
@@ -335,22 +431,56 @@ async function deleteEntry(id, secretPhrase) {
   }
 }
 
+// List queries only return entry headers; the content is loaded per entry
+async function loadEntry(id) {
+  const query = `
+        query($id: Int!, $readerPhrase: String) {
+            entry(id: $id, readerPhrase: $readerPhrase) {
+                id
+                title
+                content
+                timestamp
+                visibility
+            }
+        }
+    `;
+
+  try {
+    setLoading(true);
+    const data = await client.query(query, {
+      id: Number(id),
+      readerPhrase: state.readerPhrase,
+    });
+    return data.entry && (await openEntry(data.entry));
+  } catch (error) {
+    console.error("Error loading entry:", error);
+    showStatus("Failed to load entry", "error");
+    return null;
+  } finally {
+    setLoading(false);
+  }
+}
+
 async function searchEntries(query) {
   const searchQuery = `
-        query($query: String!) {
-            searchByTitle(query: $query) {
+        query($query: String!, $readerPhrase: String) {
+            entries(filter: { titleContains: $query }, readerPhrase: $readerPhrase) {
                 id
                 title
-                content
+                excerpt
                 timestamp
+                visibility
             }
         }
     `;
 
   try {
     setLoading(true);
-    const data = await client.query(searchQuery, { query });
-    state.entries = data.searchByTitle || [];
+    const data = await client.query(searchQuery, {
+      query,
+      readerPhrase: state.readerPhrase,
+    });
+    state.entries = await Promise.all((data.entries || []).map(openEntry));
     renderEntries();
   } catch (error) {
     console.error("Error searching entries:", error);
@@ -386,9 +516,7 @@ function renderEntries() {
                     <h3 class="entry-title">${escapeHtml(entry.title)}</h3>
                     <p class="entry-date">${formatDate(entry.timestamp)}</p>
                 </div>
-                <p class="entry-preview">${escapeHtml(
-                  truncateText(entry.content)
-                )}</p>
+                <p class="entry-preview">${escapeHtml(entry.excerpt)}</p>
                 <div class="entry-actions">
                     <button class="btn btn-sm btn-outline" onclick="viewEntry('${
                       entry.id
@@ -434,10 +562,8 @@ function closeModal(modalId) {
   document.body.style.overflow = "";
 }
 
-function viewEntry(entryId) {
-  const entry = state.entries.find(
-    (e) => e.id.toString() === entryId.toString()
-  );
+async function viewEntry(entryId) {
+  const entry = await loadEntry(entryId);
   if (!entry) return;
 
   document.getElementById("viewEntryTitle").textContent = entry.title;
@@ -449,10 +575,8 @@ function viewEntry(entryId) {
   showModal("viewEntryModal");
 }
 
-function editEntry(entryId) {
-  const entry = state.entries.find(
-    (e) => e.id.toString() === entryId.toString()
-  );
+async function editEntry(entryId) {
+  const entry = await loadEntry(entryId);
   if (!entry) return;
 
   state.currentEntry = entry;
@@ -496,6 +620,7 @@ async function handleInitialize(e) {
     document.getElementById("secretPhrase").value = "";
     // Automatically unlock after initialization
     state.isUnlocked = true;
+    await rememberReader(secretPhrase);
     sessionStorage.setItem("diaryUnlocked", "true");
     showStatus(
       "Diary created and unlocked! You can now write entries 🎉",
@@ -530,13 +655,14 @@ async function handleAddEntry(e) {
   const title = document.getElementById("newEntryTitle").value;
   const content = document.getElementById("newEntryContent").value;
   const secretPhrase = document.getElementById("newEntrySecret").value;
+  const isPrivate = document.getElementById("newEntryPrivate").checked;
 
   if (!title || !content || !secretPhrase) {
     showStatus("Please fill in all fields", "error");
     return;
   }
 
-  const success = await addEntry(title, content, secretPhrase);
+  const success = await addEntry(title, content, secretPhrase, isPrivate);
 
   if (success) {
     document.getElementById("newEntryForm").reset();
@@ -594,6 +720,8 @@ function handleSearch(e) {
 
 function handleLock() {
   state.isUnlocked = false;
+  state.readerPhrase = null;
+  state.entryKey = null;
   sessionStorage.removeItem("diaryUnlocked");
   state.entries = [];
   showStatus("Diary locked 🔒", "info");
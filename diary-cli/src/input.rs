@@ -0,0 +1,110 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reading entry text from stdin or `$EDITOR`, and parsing imports.
+
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    process,
+};
+
+use diary_backend::{EntryMetadata, Visibility};
+use diary_client::mutations::BatchEntry;
+use serde::Deserialize;
+
+use crate::CliError;
+
+/// Read text from stdin when it is piped in, otherwise open `$EDITOR`
+/// (or `$VISUAL`, falling back to `vi`) on a file holding `initial`
+pub fn read_text(initial: &str) -> Result<String, CliError> {
+    if !io::stdin().is_terminal() {
+        return Ok(io::read_to_string(io::stdin())?);
+    }
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| CliError::Usage("$EDITOR is empty".to_string()))?;
+
+    let file = tempfile::Builder::new()
+        .prefix("diary-")
+        .suffix(".md")
+        .tempfile()?;
+    fs::write(file.path(), initial)?;
+
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()?;
+    if !status.success() {
+        return Err(CliError::Usage(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+    Ok(fs::read_to_string(file.path())?)
+}
+
+/// Split text into a title (the first non-empty line, without a leading `#`)
+/// and content (the rest, trimmed)
+pub fn split_title(text: &str) -> Result<(String, String), CliError> {
+    let text = text.trim_start();
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let title = first.trim_start_matches('#').trim().to_string();
+    let content = rest.trim().to_string();
+
+    if title.is_empty() {
+        return Err(CliError::Usage("The entry needs a title".to_string()));
+    }
+    if content.is_empty() {
+        return Err(CliError::Usage("The entry needs some content".to_string()));
+    }
+    Ok((title, content))
+}
+
+/// The text shown in the editor for an existing entry; the inverse of [`split_title`]
+pub fn join_title(title: &str, content: &str) -> String {
+    format!("# {}\n\n{}\n", title, content)
+}
+
+/// An entry in an import file. Exports have more fields, which are ignored.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedEntry {
+    title: String,
+    content: String,
+    #[serde(default)]
+    metadata: Option<EntryMetadata>,
+    #[serde(default)]
+    notebook_id: Option<u64>,
+    #[serde(default)]
+    visibility: Option<Visibility>,
+}
+
+/// Parse a JSON array of entries, such as one written by `diary export`
+pub fn parse_import(json: &str) -> Result<Vec<BatchEntry>, CliError> {
+    let entries: Vec<ImportedEntry> = serde_json::from_str(json)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| BatchEntry {
+            title: entry.title,
+            content: entry.content,
+            metadata: entry.metadata,
+            notebook_id: entry.notebook_id,
+            visibility: entry.visibility,
+        })
+        .collect())
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+pub fn confirm(question: &str) -> Result<bool, CliError> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
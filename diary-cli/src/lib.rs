@@ -0,0 +1,663 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The `diary` command-line client.
+//!
+//! It talks to the diary application through a node service's GraphQL
+//! endpoint, using the queries and mutations of `DiaryService`.
+
+pub mod input;
+pub mod output;
+
+use std::{fs, io, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use diary_backend::{patch, schema, OperationResponse, Visibility};
+use diary_client::{
+    crypto::EntryKey,
+    mutations::{
+        AcceptOwnership, AddEntries, AddEntry, AppendToEntry, CancelOwnerTransfer, DeleteEntry,
+        Initialize, PatchEntry, ProposeOwnerTransfer, UpdateEntry,
+    },
+    queries::{
+        Entries, Entry, EntryFilter, EntryOrder, Owner, OwnershipLog, PendingOwnerTransfer,
+    },
+    ClientError, DiaryClient, DiaryEntry, EntryHeader, GraphQLOperation,
+};
+
+/// Number of entries sent per `addEntries` mutation when importing
+const IMPORT_BATCH_SIZE: usize = 50;
+
+/// Error the service returns for reads from a protected notebook without its secret phrase
+const NOTEBOOK_LOCKED: &str = "Notebook is locked";
+
+/// Errors reported by the `diary` command
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("The diary rejected the request: {0}")]
+    Rejected(String),
+
+    #[error("Invalid schema: {0}")]
+    Schema(String),
+}
+
+/// Write and read diary entries from the terminal
+#[derive(Debug, Parser)]
+#[command(name = "diary", version)]
+pub struct Cli {
+    /// GraphQL endpoint of the diary application. Overrides the node URL, chain and application
+    #[arg(long, env = "DIARY_ENDPOINT", global = true)]
+    pub endpoint: Option<String>,
+
+    /// URL of the node service
+    #[arg(
+        long,
+        env = "LINERA_NODE_URL",
+        default_value = "http://localhost:8080",
+        global = true
+    )]
+    pub node_url: String,
+
+    /// Chain the diary lives on
+    #[arg(long, env = "DIARY_CHAIN_ID", global = true)]
+    pub chain_id: Option<String>,
+
+    /// ID of the diary application
+    #[arg(long, env = "DIARY_APPLICATION_ID", global = true)]
+    pub application_id: Option<String>,
+
+    /// Secret phrase of the diary. Prompted for when needed and not set
+    #[arg(
+        long,
+        env = "DIARY_SECRET_PHRASE",
+        hide_env_values = true,
+        global = true
+    )]
+    pub secret_phrase: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Subcommands of `diary`
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Initialize the diary with a secret phrase
+    Init,
+
+    /// Add an entry, read from stdin or written in `$EDITOR`.
+    /// The first line is the title unless `--title` is given.
+    Add {
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long)]
+        notebook: Option<u64>,
+        /// List the entry on the public read API
+        #[arg(long)]
+        public: bool,
+        /// Encrypt the entry so that only the owner can read it. Needs `--chain-id`
+        #[arg(long, conflicts_with = "public")]
+        private: bool,
+    },
+
+    /// Edit an entry in `$EDITOR`, or replace it with text from stdin
+    Edit {
+        id: u64,
+        /// Secret phrase of a protected notebook. Prompted for when needed and not set
+        #[arg(long, env = "DIARY_NOTEBOOK_SECRET", hide_env_values = true)]
+        notebook_secret: Option<String>,
+    },
+
+    /// Add a paragraph to the end of an entry, read from stdin or written in `$EDITOR`.
+    /// Only the end of a long entry is rewritten.
+    Append {
+        id: u64,
+        /// Secret phrase of a protected notebook, used instead of the diary secret phrase
+        #[arg(long, env = "DIARY_NOTEBOOK_SECRET", hide_env_values = true)]
+        notebook_secret: Option<String>,
+    },
+
+    /// Delete an entry
+    Rm {
+        id: u64,
+        /// Do not ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// List entries, newest first
+    Ls {
+        #[arg(long)]
+        notebook: Option<u64>,
+        #[arg(long, short = 'n')]
+        limit: Option<usize>,
+        /// Secret phrase of a protected notebook
+        #[arg(long, env = "DIARY_NOTEBOOK_SECRET", hide_env_values = true)]
+        notebook_secret: Option<String>,
+    },
+
+    /// Show an entry
+    Show {
+        id: u64,
+        /// Secret phrase of a protected notebook
+        #[arg(long, env = "DIARY_NOTEBOOK_SECRET", hide_env_values = true)]
+        notebook_secret: Option<String>,
+    },
+
+    /// Search titles and content (case-insensitive).
+    /// The text of private entries is encrypted, so they are never found.
+    Search {
+        query: String,
+        #[arg(long)]
+        notebook: Option<u64>,
+        /// Secret phrase of a protected notebook
+        #[arg(long, env = "DIARY_NOTEBOOK_SECRET", hide_env_values = true)]
+        notebook_secret: Option<String>,
+    },
+
+    /// Export entries as JSON
+    Export {
+        /// File to write, stdout when not given
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[arg(long)]
+        notebook: Option<u64>,
+        /// Secret phrase of a protected notebook
+        #[arg(long, env = "DIARY_NOTEBOOK_SECRET", hide_env_values = true)]
+        notebook_secret: Option<String>,
+    },
+
+    /// Import entries from a JSON export. Entries keep their visibility and the notebook
+    /// they were exported from, which must exist.
+    Import {
+        /// File to read, stdin when not given
+        file: Option<PathBuf>,
+        /// Import every entry into this notebook instead
+        #[arg(long)]
+        notebook: Option<u64>,
+    },
+
+    /// Print the service's GraphQL schema as SDL
+    Schema {
+        /// File to write, stdout when not given
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show the owner of the diary, or hand it over to another key
+    Owner {
+        #[command(subcommand)]
+        command: Option<OwnerCommand>,
+    },
+}
+
+/// Subcommands of `diary owner`. Without one, the owner and any pending transfer are shown.
+#[derive(Debug, Subcommand)]
+pub enum OwnerCommand {
+    /// Offer the diary to another owner, who has 7 days to accept it
+    Transfer { new_owner: String },
+
+    /// Accept the diary offered to the key this node service signs with
+    Accept,
+
+    /// Withdraw the pending transfer
+    Cancel,
+
+    /// List every proposed, cancelled and accepted transfer
+    Log,
+}
+
+impl Cli {
+    /// The client for the configured endpoint
+    pub fn client(&self) -> Result<DiaryClient, CliError> {
+        if let Some(endpoint) = &self.endpoint {
+            return Ok(DiaryClient::new(endpoint.clone()));
+        }
+        match (&self.chain_id, &self.application_id) {
+            (Some(chain_id), Some(application_id)) => Ok(DiaryClient::for_application(
+                &self.node_url,
+                chain_id,
+                application_id,
+            )),
+            _ => Err(CliError::Usage(
+                "Set --endpoint, or both --chain-id and --application-id".to_string(),
+            )),
+        }
+    }
+
+    /// The diary secret phrase, prompting for it when not configured
+    fn secret_phrase(&self) -> Result<String, CliError> {
+        match &self.secret_phrase {
+            Some(phrase) => Ok(phrase.clone()),
+            None => Ok(rpassword::prompt_password("Secret phrase: ")?),
+        }
+    }
+
+    /// The key private entries are encrypted with, derived from the diary secret phrase
+    /// and the chain ID
+    fn entry_key(&self, secret_phrase: &str) -> Result<EntryKey, CliError> {
+        match &self.chain_id {
+            Some(chain_id) => Ok(EntryKey::derive(secret_phrase, chain_id)),
+            None => Err(CliError::Usage(
+                "Set --chain-id to encrypt and decrypt private entries".to_string(),
+            )),
+        }
+    }
+
+    /// Reads private entries when the diary secret phrase is configured.
+    /// Without it, read commands are left without private entries rather than prompting.
+    fn reader(&self) -> Reader {
+        Reader {
+            phrase: self.secret_phrase.clone(),
+            key: match (&self.secret_phrase, &self.chain_id) {
+                (Some(phrase), Some(chain_id)) => Some(EntryKey::derive(phrase, chain_id)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The reader phrase sent with queries, and the key that opens the private entries they return
+struct Reader {
+    phrase: Option<String>,
+    key: Option<EntryKey>,
+}
+
+impl Reader {
+    fn key(&self) -> Result<&EntryKey, CliError> {
+        self.key
+            .as_ref()
+            .ok_or_else(|| CliError::Usage("Set --chain-id to decrypt private entries".to_string()))
+    }
+
+    fn open_entry(&self, mut entry: DiaryEntry) -> Result<DiaryEntry, CliError> {
+        if entry.visibility == Visibility::Private {
+            self.key()?.open_entry(&mut entry)?;
+        }
+        Ok(entry)
+    }
+
+    fn open_headers(&self, headers: Vec<EntryHeader>) -> Result<Vec<EntryHeader>, CliError> {
+        headers
+            .into_iter()
+            .map(|mut header| {
+                if header.visibility == Visibility::Private {
+                    self.key()?.open_header(&mut header)?;
+                }
+                Ok(header)
+            })
+            .collect()
+    }
+}
+
+/// Run a parsed command line
+pub async fn run(cli: Cli) -> Result<(), CliError> {
+    let client = cli.client()?;
+
+    match &cli.command {
+        Command::Init => {
+            let secret_phrase = match &cli.secret_phrase {
+                Some(phrase) => phrase.clone(),
+                None => {
+                    let phrase = rpassword::prompt_password("New secret phrase: ")?;
+                    if rpassword::prompt_password("Repeat secret phrase: ")? != phrase {
+                        return Err(CliError::Usage("Secret phrases do not match".to_string()));
+                    }
+                    phrase
+                }
+            };
+            let response = mutate(&client, &Initialize::new(secret_phrase)).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Add {
+            title,
+            notebook,
+            public,
+            private,
+        } => {
+            let text = input::read_text("")?;
+            let (mut title, mut content) = match title {
+                Some(title) => (title.clone(), text.trim().to_string()),
+                None => input::split_title(&text)?,
+            };
+            let secret_phrase = cli.secret_phrase()?;
+            if *private {
+                let key = cli.entry_key(&secret_phrase)?;
+                title = key.encrypt(&title);
+                content = key.encrypt(&content);
+            }
+            let mut mutation = AddEntry::new(secret_phrase, title, content);
+            mutation.notebook_id = *notebook;
+            if *public {
+                mutation = mutation.visibility(Visibility::Public);
+            } else if *private {
+                mutation = mutation.visibility(Visibility::Private);
+            }
+            let response = mutate(&client, &mutation).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Edit {
+            id,
+            notebook_secret,
+        } => {
+            let secret_phrase = cli.secret_phrase()?;
+            let mut query = Entry::new(*id).reader_phrase(secret_phrase.clone());
+            query.secret_phrase = notebook_secret.clone();
+            let result = match client.execute(&query).await {
+                Err(ClientError::GraphQL(errors))
+                    if notebook_secret.is_none() && errors.iter().any(|e| e == NOTEBOOK_LOCKED) =>
+                {
+                    let phrase = rpassword::prompt_password("Notebook secret phrase: ")?;
+                    query.secret_phrase = Some(phrase);
+                    client.execute(&query).await
+                }
+                result => result,
+            };
+            let mut entry =
+                result?.ok_or_else(|| CliError::Usage(format!("Entry {} not found", id)))?;
+            // Writes to a protected notebook take the notebook secret phrase the entry was read
+            // with, entered or prompted for; the diary secret phrase still keys private entries
+            let write_phrase = query
+                .secret_phrase
+                .clone()
+                .unwrap_or_else(|| secret_phrase.clone());
+
+            // Private entries are decrypted for editing and sent back whole, newly encrypted,
+            // since the stored text is the ciphertext
+            if entry.visibility == Visibility::Private {
+                let key = cli.entry_key(&secret_phrase)?;
+                key.open_entry(&mut entry)?;
+                let text = input::read_text(&input::join_title(&entry.title, &entry.content))?;
+                let (title, content) = input::split_title(&text)?;
+                if title == entry.title && content == entry.content {
+                    println!("No changes");
+                    return Ok(());
+                }
+                let mutation = UpdateEntry::new(write_phrase, *id)
+                    .title(key.encrypt(&title))
+                    .content(key.encrypt(&content))
+                    .expected_revision(entry.revision);
+                let response = mutate(&client, &mutation).await?;
+                println!("{}", response.message);
+                return Ok(());
+            }
+
+            let text = input::read_text(&input::join_title(&entry.title, &entry.content))?;
+            let (title, content) = input::split_title(&text)?;
+
+            let (update, patch_entry) = edit_mutations(&entry, title, &content, &write_phrase);
+            if update.is_none() && patch_entry.is_none() {
+                println!("No changes");
+                return Ok(());
+            }
+            if let Some(mutation) = update {
+                let response = mutate(&client, &mutation).await?;
+                println!("{}", response.message);
+            }
+            if let Some(mutation) = patch_entry {
+                let response = mutate(&client, &mutation).await?;
+                println!("{}", response.message);
+            }
+        }
+
+        Command::Append {
+            id,
+            notebook_secret,
+        } => {
+            let text = input::read_text("")?;
+            if text.trim().is_empty() {
+                return Err(CliError::Usage("Nothing to append".to_string()));
+            }
+            let write_phrase = match notebook_secret {
+                Some(phrase) => phrase.clone(),
+                None => cli.secret_phrase()?,
+            };
+            let mutation = AppendToEntry::new(write_phrase, *id, format!("\n\n{}", text.trim()));
+            let response = mutate(&client, &mutation).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Rm { id, yes } => {
+            if !yes && !input::confirm(&format!("Delete entry {}?", id))? {
+                return Ok(());
+            }
+            let response = mutate(&client, &DeleteEntry::new(cli.secret_phrase()?, *id)).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Ls {
+            notebook,
+            limit,
+            notebook_secret,
+        } => {
+            let reader = cli.reader();
+            let mut entries = client
+                .execute(&scoped(*notebook, notebook_secret.clone(), &reader))
+                .await?;
+            if let Some(limit) = limit {
+                entries.truncate(*limit);
+            }
+            for entry in &reader.open_headers(entries)? {
+                println!("{}", output::entry_line(entry));
+            }
+        }
+
+        Command::Show {
+            id,
+            notebook_secret,
+        } => {
+            let reader = cli.reader();
+            let mut query = Entry::new(*id);
+            query.secret_phrase = notebook_secret.clone();
+            query.reader_phrase = reader.phrase.clone();
+            let entry = client
+                .execute(&query)
+                .await?
+                .ok_or_else(|| CliError::Usage(format!("Entry {} not found", id)))?;
+            print!("{}", output::entry_details(&reader.open_entry(entry)?));
+        }
+
+        Command::Search {
+            query,
+            notebook,
+            notebook_secret,
+        } => {
+            let mut search = Entries::new()
+                .filter(EntryFilter {
+                    text_contains: Some(query.clone()),
+                    ..EntryFilter::default()
+                })
+                .order_by(EntryOrder::UpdatedDesc);
+            search.notebook_id = *notebook;
+            search.secret_phrase = notebook_secret.clone();
+
+            let reader = cli.reader();
+            search.reader_phrase = reader.phrase.clone();
+            for entry in &reader.open_headers(client.execute(&search).await?)? {
+                println!("{}", output::entry_line(entry));
+            }
+        }
+
+        Command::Export {
+            output,
+            notebook,
+            notebook_secret,
+        } => {
+            // List queries only return headers, so each entry is fetched with its content.
+            // Private entries are exported decrypted: their key depends on the chain
+            let reader = cli.reader();
+            let headers = client
+                .execute(&scoped(*notebook, notebook_secret.clone(), &reader))
+                .await?;
+            let mut entries = Vec::with_capacity(headers.len());
+            for header in headers {
+                let mut query = Entry::new(header.id);
+                query.secret_phrase = notebook_secret.clone();
+                query.reader_phrase = reader.phrase.clone();
+                if let Some(entry) = client.execute(&query).await? {
+                    entries.push(reader.open_entry(entry)?);
+                }
+            }
+            let json = serde_json::to_string_pretty(&entries)?;
+            match output {
+                Some(path) => {
+                    fs::write(path, json + "\n")?;
+                    eprintln!("Exported {} entries to {}", entries.len(), path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+
+        Command::Import { file, notebook } => {
+            let json = match file {
+                Some(path) => fs::read_to_string(path)?,
+                None => io::read_to_string(io::stdin())?,
+            };
+            let mut entries = input::parse_import(&json)?;
+            if entries.is_empty() {
+                return Err(CliError::Usage("Nothing to import".to_string()));
+            }
+
+            let secret_phrase = cli.secret_phrase()?;
+            if notebook.is_some() {
+                for entry in &mut entries {
+                    entry.notebook_id = *notebook;
+                }
+            }
+            // Exports hold private entries decrypted, since their key depends on the chain
+            if entries
+                .iter()
+                .any(|entry| entry.visibility == Some(Visibility::Private))
+            {
+                let key = cli.entry_key(&secret_phrase)?;
+                for entry in &mut entries {
+                    if entry.visibility == Some(Visibility::Private) {
+                        entry.title = key.encrypt(&entry.title);
+                        entry.content = key.encrypt(&entry.content);
+                    }
+                }
+            }
+
+            let mut imported = 0;
+            for batch in entries.chunks(IMPORT_BATCH_SIZE) {
+                let mutation = AddEntries::new(secret_phrase.clone(), batch.to_vec());
+                for response in client.execute(&mutation).await? {
+                    if response.success {
+                        imported += 1;
+                    } else {
+                        eprintln!("Skipped: {}", response.message);
+                    }
+                }
+            }
+            println!("Scheduled {} of {} entries", imported, entries.len());
+        }
+
+        Command::Schema { output } => {
+            let introspection = client
+                .execute_raw(schema::INTROSPECTION_QUERY, serde_json::json!({}))
+                .await?;
+            let sdl = schema::sdl_from_introspection(&introspection).map_err(CliError::Schema)?;
+            match output {
+                Some(path) => fs::write(path, sdl)?,
+                None => print!("{}", sdl),
+            }
+        }
+
+        Command::Owner { command: None } => {
+            println!("Owner: {}", client.execute(&Owner::new()).await?);
+            if let Some(transfer) = client.execute(&PendingOwnerTransfer::new()).await? {
+                println!("{}", output::owner_transfer(&transfer));
+            }
+        }
+
+        Command::Owner {
+            command: Some(OwnerCommand::Transfer { new_owner }),
+        } => {
+            let mutation = ProposeOwnerTransfer::new(cli.secret_phrase()?, new_owner.clone());
+            let response = mutate(&client, &mutation).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Owner {
+            command: Some(OwnerCommand::Accept),
+        } => {
+            let response = mutate(&client, &AcceptOwnership::new()).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Owner {
+            command: Some(OwnerCommand::Cancel),
+        } => {
+            let response = mutate(&client, &CancelOwnerTransfer::new(cli.secret_phrase()?)).await?;
+            println!("{}", response.message);
+        }
+
+        Command::Owner {
+            command: Some(OwnerCommand::Log),
+        } => {
+            for event in client.execute(&OwnershipLog::new()).await? {
+                println!("{}", output::ownership_event(&event));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The mutations that save an edited title and content of a non-private entry, signed with
+/// `write_phrase`. Neither is needed when nothing changed.
+///
+/// The content is sent as a patch on the revision that was edited, so that changes made
+/// elsewhere in the meantime are merged rather than overwritten. A new title is only saved
+/// if nothing changed since.
+pub fn edit_mutations(
+    entry: &DiaryEntry,
+    title: String,
+    content: &str,
+    write_phrase: &str,
+) -> (Option<UpdateEntry>, Option<PatchEntry>) {
+    let update = (title != entry.title).then(|| {
+        UpdateEntry::new(write_phrase.to_string(), entry.id)
+            .title(title)
+            .expected_revision(entry.revision)
+    });
+    let edits = patch::diff(&entry.content, content);
+    let patch_entry = (!edits.is_empty())
+        .then(|| PatchEntry::new(write_phrase.to_string(), entry.id, entry.revision, edits));
+    (update, patch_entry)
+}
+
+/// The `entries` query for an optional notebook
+fn scoped(notebook: Option<u64>, notebook_secret: Option<String>, reader: &Reader) -> Entries {
+    let mut query = Entries::new();
+    query.notebook_id = notebook;
+    query.secret_phrase = notebook_secret;
+    query.reader_phrase = reader.phrase.clone();
+    query
+}
+
+/// Send a mutation and turn an unsuccessful response into an error
+async fn mutate<O>(client: &DiaryClient, mutation: &O) -> Result<OperationResponse, CliError>
+where
+    O: GraphQLOperation<Response = OperationResponse>,
+{
+    let response = client.execute(mutation).await?;
+    if !response.success {
+        return Err(CliError::Rejected(response.message));
+    }
+    Ok(response)
+}
@@ -0,0 +1,13 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::Parser;
+use diary_cli::Cli;
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = diary_cli::run(Cli::parse()).await {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Formatting entries for the terminal.
+
+use diary_backend::{
+    calendar, text, DiaryEntry, EntryHeader, OwnerTransfer, OwnershipAction, OwnershipEvent,
+    Visibility,
+};
+
+/// Format a timestamp in microseconds as `YYYY-MM-DD HH:MM` (UTC)
+pub fn format_timestamp(timestamp_micros: u64) -> String {
+    let minutes = (timestamp_micros % calendar::MICROS_PER_DAY) / 60_000_000;
+    format!(
+        "{} {:02}:{:02}",
+        calendar::format_date(calendar::day_of(timestamp_micros)),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// One line per entry for `ls` and `search`: ID, creation date and title
+pub fn entry_line(entry: &EntryHeader) -> String {
+    format!(
+        "{:>5}  {}  {}",
+        entry.id,
+        format_timestamp(entry.created_at),
+        entry.title
+    )
+}
+
+/// The full entry for `show`
+pub fn entry_details(entry: &DiaryEntry) -> String {
+    let mut details = format!("# {}\n\n", entry.title);
+    details.push_str(&format!("ID:       {}\n", entry.id));
+    details.push_str(&format!(
+        "Created:  {}\n",
+        format_timestamp(entry.created_at)
+    ));
+    details.push_str(&format!(
+        "Updated:  {}\n",
+        format_timestamp(entry.timestamp)
+    ));
+    details.push_str(&format!("Notebook: {}\n", entry.notebook_id));
+    if entry.visibility != Visibility::Members {
+        details.push_str(&format!("Visible:  {:?}\n", entry.visibility));
+    }
+    let tags = text::extract_tags(&entry.content);
+    if !tags.is_empty() {
+        details.push_str(&format!("Tags:     {}\n", tags.join(", ")));
+    }
+    for attachment in &entry.attachments {
+        details.push_str(&format!(
+            "File:     {} ({}, {} bytes)\n",
+            attachment.filename, attachment.mime_type, attachment.size
+        ));
+    }
+    details.push_str(&format!("\n{}\n", entry.content));
+    details
+}
+
+/// The pending transfer for `owner`
+pub fn owner_transfer(transfer: &OwnerTransfer) -> String {
+    format!(
+        "Offered to {} until {}",
+        transfer.new_owner,
+        format_timestamp(transfer.expires_at)
+    )
+}
+
+/// One line per event for `owner log`: date, action, and the owners before and after
+pub fn ownership_event(event: &OwnershipEvent) -> String {
+    let action = match event.action {
+        OwnershipAction::Proposed => "proposed",
+        OwnershipAction::Cancelled => "cancelled",
+        OwnershipAction::Accepted => "accepted",
+    };
+    format!(
+        "{}  {:<9}  {} -> {}",
+        format_timestamp(event.timestamp),
+        action,
+        event.owner,
+        event.new_owner
+    )
+}
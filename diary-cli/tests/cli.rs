@@ -0,0 +1,193 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for argument parsing, entry text handling and output formatting.
+
+use clap::Parser;
+use diary_backend::{DiaryEntry, EntryMetadata, OwnershipAction, OwnershipEvent, Visibility};
+use diary_cli::{edit_mutations, input, output, Cli, Command, OwnerCommand};
+
+/// Test that subcommands and global options parse
+#[test]
+fn test_parse_arguments() {
+    let cli = Cli::try_parse_from([
+        "diary",
+        "ls",
+        "--notebook",
+        "2",
+        "-n",
+        "5",
+        "--endpoint",
+        "http://localhost:8080/chains/e476/applications/a1b2",
+    ])
+    .unwrap();
+
+    assert!(matches!(
+        cli.command,
+        Command::Ls {
+            notebook: Some(2),
+            limit: Some(5),
+            ..
+        }
+    ));
+    assert_eq!(
+        cli.client().unwrap().endpoint(),
+        "http://localhost:8080/chains/e476/applications/a1b2"
+    );
+
+    let cli = Cli::try_parse_from([
+        "diary",
+        "--chain-id",
+        "e476",
+        "--application-id",
+        "a1b2",
+        "rm",
+        "7",
+        "--yes",
+    ])
+    .unwrap();
+    assert!(matches!(cli.command, Command::Rm { id: 7, yes: true }));
+    assert!(cli
+        .client()
+        .unwrap()
+        .endpoint()
+        .ends_with("/chains/e476/applications/a1b2"));
+
+    let cli = Cli::try_parse_from(["diary", "edit", "3", "--notebook-secret", "dreams"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Edit { id: 3, notebook_secret: Some(ref secret) } if secret == "dreams"
+    ));
+
+    let cli = Cli::try_parse_from(["diary", "add", "--private"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Add {
+            private: true,
+            public: false,
+            ..
+        }
+    ));
+    assert!(Cli::try_parse_from(["diary", "add", "--private", "--public"]).is_err());
+
+    let cli = Cli::try_parse_from(["diary", "append", "3"]).unwrap();
+    assert!(matches!(cli.command, Command::Append { id: 3, .. }));
+    let cli = Cli::try_parse_from(["diary", "append", "3", "--notebook-secret", "dreams"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Append { id: 3, notebook_secret: Some(ref secret) } if secret == "dreams"
+    ));
+
+    let cli = Cli::try_parse_from(["diary", "owner", "transfer", "0x5e1f"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Owner {
+            command: Some(OwnerCommand::Transfer { ref new_owner })
+        } if new_owner == "0x5e1f"
+    ));
+    let cli = Cli::try_parse_from(["diary", "owner"]).unwrap();
+    assert!(matches!(cli.command, Command::Owner { command: None }));
+
+    assert!(Cli::try_parse_from(["diary", "show", "not-a-number"]).is_err());
+}
+
+/// Test splitting editor text into a title and content
+#[test]
+fn test_split_title() {
+    let (title, content) = input::split_title("\n# Day one\n\nHello\nworld\n").unwrap();
+    assert_eq!(title, "Day one");
+    assert_eq!(content, "Hello\nworld");
+
+    let text = input::join_title(&title, &content);
+    assert_eq!(
+        input::split_title(&text).unwrap(),
+        (title.clone(), content.clone())
+    );
+
+    assert!(input::split_title("Only a title").is_err());
+    assert!(input::split_title("   \n\n").is_err());
+}
+
+/// Test reading back an export, ignoring fields that imports do not use
+#[test]
+fn test_parse_import() {
+    let json = r#"[
+        {
+            "id": 3,
+            "title": "Day one",
+            "content": "Hello",
+            "createdAt": 0,
+            "notebookId": 2,
+            "visibility": "PRIVATE",
+            "metadata": { "mood": "GREAT", "rating": 5, "energy": null, "customFields": [] }
+        },
+        { "title": "Day two", "content": "Again" }
+    ]"#;
+
+    let entries = input::parse_import(json).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].title, "Day one");
+    assert_eq!(entries[0].metadata.as_ref().unwrap().rating, Some(5));
+    assert_eq!(entries[0].notebook_id, Some(2));
+    assert_eq!(entries[0].visibility, Some(Visibility::Private));
+    assert!(entries[1].metadata.is_none());
+    assert_eq!(entries[1].notebook_id, None);
+    assert_eq!(entries[1].visibility, None);
+
+    assert!(input::parse_import(r#"{ "title": "Not a list" }"#).is_err());
+}
+
+/// Test timestamp formatting
+#[test]
+fn test_format_timestamp() {
+    // 2024-03-05 14:30:00 UTC
+    assert_eq!(
+        output::format_timestamp(1_709_649_000_000_000),
+        "2024-03-05 14:30"
+    );
+}
+
+/// Test formatting the ownership log
+#[test]
+fn test_ownership_event() {
+    let event = OwnershipEvent {
+        action: OwnershipAction::Accepted,
+        owner: "0xa11c".to_string(),
+        new_owner: "0x5e1f".to_string(),
+        timestamp: 1_709_649_000_000_000,
+    };
+    assert_eq!(
+        output::ownership_event(&event),
+        "2024-03-05 14:30  accepted   0xa11c -> 0x5e1f"
+    );
+}
+
+/// Test that edits of an entry in a protected notebook are signed with the notebook's
+/// secret phrase
+#[test]
+fn test_edit_protected_notebook() {
+    let entry = DiaryEntry {
+        id: 3,
+        title: "Day one".to_string(),
+        content: "Hello".to_string(),
+        timestamp: 0,
+        created_at: 0,
+        notebook_id: 2,
+        metadata: EntryMetadata::default(),
+        attachments: Vec::new(),
+        visibility: Visibility::Members,
+        revision: 4,
+    };
+
+    let (update, patch) = edit_mutations(&entry, "Day two".to_string(), "Hello world", "dreams");
+    let update = update.unwrap();
+    assert_eq!(update.secret_phrase, "dreams");
+    assert_eq!(update.title.as_deref(), Some("Day two"));
+    assert_eq!(update.expected_revision, Some(4));
+    let patch = patch.unwrap();
+    assert_eq!(patch.secret_phrase, "dreams");
+    assert_eq!(patch.base_revision, 4);
+
+    let (update, patch) = edit_mutations(&entry, "Day one".to_string(), "Hello", "dreams");
+    assert!(update.is_none() && patch.is_none());
+}
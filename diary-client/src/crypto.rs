@@ -0,0 +1,103 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Encryption of private entries.
+//!
+//! Private entries are stored as envelopes: [`ENCRYPTED_PREFIX`] followed by
+//! `base64(nonce || ciphertext)`, sealed with AES-256-GCM. The key is derived from
+//! the diary's secret phrase with PBKDF2-SHA256, using the chain ID as salt, so the
+//! same phrase gives a different key on every chain.
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use diary_backend::{DiaryEntry, EntryHeader, Visibility, ENCRYPTED_PREFIX};
+use sha2::Sha256;
+
+use crate::ClientError;
+
+/// PBKDF2 iterations used to derive the key from the secret phrase
+pub const KEY_ITERATIONS: u32 = 100_000;
+
+/// Length of the AES-GCM nonce at the start of an envelope
+const NONCE_LENGTH: usize = 12;
+
+/// The key that private entries of one diary are encrypted with
+#[derive(Clone)]
+pub struct EntryKey {
+    cipher: Aes256Gcm,
+}
+
+impl EntryKey {
+    /// Derive the key from the diary's secret phrase and the ID of its chain
+    pub fn derive(secret_phrase: &str, chain_id: &str) -> Self {
+        let mut key = Key::<Aes256Gcm>::default();
+        pbkdf2::pbkdf2_hmac::<Sha256>(
+            secret_phrase.as_bytes(),
+            chain_id.as_bytes(),
+            KEY_ITERATIONS,
+            &mut key,
+        );
+        EntryKey {
+            cipher: Aes256Gcm::new(&key),
+        }
+    }
+
+    /// Seal a text into an envelope, with a fresh random nonce
+    pub fn encrypt(&self, text: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, text.as_bytes())
+            .expect("AES-GCM encryption of an in-memory text cannot fail");
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(data))
+    }
+
+    /// Open an envelope sealed with this key
+    pub fn decrypt(&self, envelope: &str) -> Result<String, ClientError> {
+        let data = envelope
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .ok_or(ClientError::Decrypt("not an encrypted envelope"))?;
+        let data = STANDARD
+            .decode(data)
+            .map_err(|_| ClientError::Decrypt("invalid base64"))?;
+        if data.len() < NONCE_LENGTH {
+            return Err(ClientError::Decrypt("envelope too short"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        let text = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| ClientError::Decrypt("wrong key or corrupted text"))?;
+        String::from_utf8(text).map_err(|_| ClientError::Decrypt("text is not UTF-8"))
+    }
+
+    /// Decrypt the title and content of a private entry in place.
+    /// Other entries are left as they are.
+    pub fn open_entry(&self, entry: &mut DiaryEntry) -> Result<(), ClientError> {
+        if entry.visibility == Visibility::Private {
+            entry.title = self.decrypt(&entry.title)?;
+            entry.content = self.decrypt(&entry.content)?;
+        }
+        Ok(())
+    }
+
+    /// Decrypt the title of a private entry's header in place. Headers of private entries
+    /// have no excerpt and no tags, as the service cannot read their content.
+    pub fn open_header(&self, header: &mut EntryHeader) -> Result<(), ClientError> {
+        if header.visibility == Visibility::Private {
+            header.title = self.decrypt(&header.title)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for EntryKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EntryKey(..)")
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed client for the diary GraphQL API served by a Linera node service.
+//!
+//! Every field of the service's `QueryRoot`, `MutationRoot` and `public` read API has a
+//! builder in [`queries`] or [`mutations`]; a test checks them against
+//! `backend/schema.graphql`. Required arguments go to `new`, optional ones have setters,
+//! and responses decode into the backend's own types, or into [`responses`] for the
+//! objects only the service defines:
+//!
+//! ```no_run
+//! use diary_client::{mutations::AddEntry, queries::Entries, DiaryClient};
+//!
+//! # async fn run() -> Result<(), diary_client::ClientError> {
+//! let client = DiaryClient::for_application("http://localhost:8080", "e476…", "a1b2…");
+//! client
+//!     .execute(&AddEntry::new("secret".into(), "Day one".into(), "Hello".into()))
+//!     .await?;
+//! let entries = client.execute(&Entries::new().notebook_id(1)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Private entries are encrypted on the client; [`crypto::EntryKey`] seals and opens them.
+
+#[macro_use]
+mod operation;
+pub mod crypto;
+pub mod mutations;
+pub mod queries;
+pub mod responses;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+pub use diary_backend::{
+    patch::ContentEdit, Attachment, CustomField, DiaryEntry, EnergyLevel, EntryHeader,
+    EntryMetadata, FieldType, Mood, OperationResponse, OwnerTransfer, OwnershipAction,
+    OwnershipEvent, RevisionConflict, Visibility,
+};
+pub use operation::GraphQLOperation;
+
+/// Errors returned by [`DiaryClient`]
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("GraphQL error: {}", .0.join("; "))]
+    GraphQL(Vec<String>),
+
+    #[error(transparent)]
+    RevisionConflict(RevisionConflict),
+
+    #[error("Response has no data for field '{0}'")]
+    MissingData(&'static str),
+
+    #[error("Failed to decode response: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    #[error("Failed to decrypt private entry: {0}")]
+    Decrypt(&'static str),
+}
+
+/// A client for one diary application on one chain
+#[derive(Clone, Debug)]
+pub struct DiaryClient {
+    endpoint: String,
+    http: reqwest::Client,
+}
+
+impl DiaryClient {
+    /// Create a client for a GraphQL endpoint URL
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        DiaryClient {
+            endpoint: endpoint.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Create a client for an application served by `linera service`,
+    /// e.g. `http://localhost:8080`
+    pub fn for_application(node_url: &str, chain_id: &str, application_id: &str) -> Self {
+        Self::new(application_endpoint(node_url, chain_id, application_id))
+    }
+
+    /// The GraphQL endpoint URL
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Send a query or mutation and decode its result
+    pub async fn execute<O: GraphQLOperation>(
+        &self,
+        operation: &O,
+    ) -> Result<O::Response, ClientError> {
+        let data = self
+            .execute_raw(&operation.document(), operation.variables())
+            .await?;
+        O::decode(data)
+    }
+
+    /// Send a GraphQL document with variables and return its `data` object
+    pub async fn execute_raw(
+        &self,
+        document: &str,
+        variables: Value,
+    ) -> Result<Value, ClientError> {
+        let response: GraphQLResponse = self
+            .http
+            .post(&self.endpoint)
+            .json(&serde_json::json!({ "query": document, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(conflict) = response
+            .errors
+            .iter()
+            .find_map(GraphQLError::revision_conflict)
+        {
+            return Err(ClientError::RevisionConflict(conflict));
+        }
+        if !response.errors.is_empty() {
+            return Err(ClientError::GraphQL(
+                response.errors.into_iter().map(|e| e.message).collect(),
+            ));
+        }
+        Ok(response.data.unwrap_or(Value::Null))
+    }
+}
+
+/// URL of an application's GraphQL endpoint on a node service
+pub fn application_endpoint(node_url: &str, chain_id: &str, application_id: &str) -> String {
+    format!(
+        "{}/chains/{}/applications/{}",
+        node_url.trim_end_matches('/'),
+        chain_id,
+        application_id
+    )
+}
+
+/// The body of a GraphQL response
+#[derive(Deserialize)]
+struct GraphQLResponse {
+    data: Option<Value>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLError {
+    message: String,
+    #[serde(default)]
+    extensions: Option<Value>,
+}
+
+impl GraphQLError {
+    /// The conflict reported by an error with the `REVISION_CONFLICT` code
+    fn revision_conflict(&self) -> Option<RevisionConflict> {
+        let extensions = self.extensions.as_ref()?;
+        if extensions.get("code")?.as_str()? != RevisionConflict::CODE {
+            return None;
+        }
+        serde_json::from_value(extensions.clone()).ok()
+    }
+}
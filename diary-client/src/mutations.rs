@@ -0,0 +1,419 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builders for the fields of `MutationRoot`.
+
+use diary_backend::{
+    patch::ContentEdit, EntryMetadata, OperationResponse, TemplateValue, Visibility,
+};
+use serde::Serialize;
+
+use crate::operation::RESPONSE_FIELDS;
+
+/// One entry of an `addEntries` batch
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchEntry {
+    pub title: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<EntryMetadata>,
+    /// Notebook of this entry, instead of the batch's `notebook_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notebook_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+}
+
+operation! {
+    /// Initialize the diary with a secret phrase
+    "mutation" Initialize => "initialize" -> OperationResponse { RESPONSE_FIELDS }
+    required { secret_phrase: String => "String!" }
+    optional {}
+}
+
+operation! {
+    /// Add a new diary entry
+    "mutation" AddEntry => "addEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        title: String => "String!",
+        content: String => "String!",
+    }
+    optional {
+        notebook_id: u64 => "Int",
+        metadata: EntryMetadata => "EntryMetadataInput",
+        visibility: Visibility => "Visibility",
+    }
+}
+
+operation! {
+    /// Update an existing diary entry
+    "mutation" UpdateEntry => "updateEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+    }
+    optional {
+        title: String => "String",
+        content: String => "String",
+        metadata: EntryMetadata => "EntryMetadataInput",
+        visibility: Visibility => "Visibility",
+        expected_revision: u64 => "Int",
+    }
+}
+
+operation! {
+    /// Append text to the end of an entry's content
+    "mutation" AppendToEntry => "appendToEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+        text: String => "String!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Replace `len` bytes of an entry's content at byte `offset` with `text`
+    "mutation" ReplaceRange => "replaceRange" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+        offset: u64 => "Int!",
+        len: u64 => "Int!",
+        text: String => "String!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Apply edits made on revision `base_revision` of an entry's content, merging them
+    /// over the revisions made since when they do not overlap
+    "mutation" PatchEntry => "patchEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+        base_revision: u64 => "Int!",
+        patch: Vec<ContentEdit> => "[ContentEditInput!]!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Delete a diary entry
+    "mutation" DeleteEntry => "deleteEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+    }
+    optional {
+        expected_revision: u64 => "Int",
+    }
+}
+
+operation! {
+    /// Add several entries at once
+    "mutation" AddEntries => "addEntries" -> Vec<OperationResponse> { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entries: Vec<BatchEntry> => "[BatchEntryInput!]!",
+    }
+    optional { notebook_id: u64 => "Int" }
+}
+
+operation! {
+    /// Create a new notebook, optionally protected by its own secret phrase
+    "mutation" CreateNotebook => "createNotebook" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        name: String => "String!",
+    }
+    optional { notebook_secret_phrase: String => "String" }
+}
+
+operation! {
+    /// Rename a notebook
+    "mutation" RenameNotebook => "renameNotebook" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        notebook_id: u64 => "Int!",
+        name: String => "String!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Archive a notebook, making it read-only
+    "mutation" ArchiveNotebook => "archiveNotebook" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        notebook_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Make an archived notebook writable again
+    "mutation" UnarchiveNotebook => "unarchiveNotebook" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        notebook_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Move an entry to another notebook
+    "mutation" MoveEntry => "moveEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+        notebook_id: u64 => "Int!",
+    }
+    optional { target_secret_phrase: String => "String" }
+}
+
+operation! {
+    /// Save a new draft. Drafts are not listed with the entries until published.
+    "mutation" SaveDraft => "saveDraft" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        title: String => "String!",
+        content: String => "String!",
+    }
+    optional {
+        notebook_id: u64 => "Int",
+        metadata: EntryMetadata => "EntryMetadataInput",
+    }
+}
+
+operation! {
+    /// Overwrite a draft with its latest title, content and metadata
+    "mutation" UpdateDraft => "updateDraft" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        draft_id: u64 => "Int!",
+        title: String => "String!",
+        content: String => "String!",
+    }
+    optional { metadata: EntryMetadata => "EntryMetadataInput" }
+}
+
+operation! {
+    /// Publish a draft as a regular entry
+    "mutation" PublishDraft => "publishDraft" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        draft_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Discard a draft
+    "mutation" DiscardDraft => "discardDraft" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        draft_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Create a template with a title pattern and a content skeleton
+    "mutation" CreateTemplate => "createTemplate" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        name: String => "String!",
+        title_pattern: String => "String!",
+        content_skeleton: String => "String!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Update a template
+    "mutation" UpdateTemplate => "updateTemplate" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        template_id: u64 => "Int!",
+    }
+    optional {
+        name: String => "String",
+        title_pattern: String => "String",
+        content_skeleton: String => "String",
+    }
+}
+
+operation! {
+    /// Delete a template
+    "mutation" DeleteTemplate => "deleteTemplate" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        template_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Add an entry rendered from a template
+    "mutation" AddEntryFromTemplate => "addEntryFromTemplate" -> OperationResponse {
+        RESPONSE_FIELDS
+    }
+    required {
+        secret_phrase: String => "String!",
+        template_id: u64 => "Int!",
+        values: Vec<TemplateValue> => "[TemplateValue!]!",
+    }
+    optional { notebook_id: u64 => "Int" }
+}
+
+operation! {
+    /// Attach a file, published as a data blob beforehand, to an entry
+    "mutation" AttachFile => "attachFile" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+        blob_hash: String => "DataBlobHash!",
+        mime_type: String => "String!",
+        filename: String => "String!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Remove an attachment from an entry
+    "mutation" DetachFile => "detachFile" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+        blob_hash: String => "DataBlobHash!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Link one entry to another
+    "mutation" LinkEntries => "linkEntries" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        from_entry_id: u64 => "Int!",
+        to_entry_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Remove an explicit link between two entries
+    "mutation" UnlinkEntries => "unlinkEntries" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        from_entry_id: u64 => "Int!",
+        to_entry_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Pin an entry, at the end of the pinned list or at `position`
+    "mutation" PinEntry => "pinEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+    }
+    optional { position: u32 => "Int" }
+}
+
+operation! {
+    /// Unpin an entry
+    "mutation" UnpinEntry => "unpinEntry" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Mark an entry as a favorite
+    "mutation" Favorite => "favorite" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Remove an entry from the favorites
+    "mutation" Unfavorite => "unfavorite" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        entry_id: u64 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Set the owner's timezone as an offset from UTC in minutes (e.g. 120 for UTC+2)
+    "mutation" SetTimezone => "setTimezone" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        utc_offset_minutes: i32 => "Int!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Set the phrase members read `MEMBERS` entries with, or remove it to let anyone read them
+    "mutation" SetMembersPhrase => "setMembersPhrase" -> OperationResponse { RESPONSE_FIELDS }
+    required { secret_phrase: String => "String!" }
+    optional { members_phrase: String => "String" }
+}
+
+operation! {
+    /// Send the next batch of the diary to a successor application on the same chain.
+    /// Repeat until the export progress is finished.
+    "mutation" ExportToSuccessor => "exportToSuccessor" -> OperationResponse { RESPONSE_FIELDS }
+    required {
+        secret_phrase: String => "String!",
+        successor: String => "ApplicationId!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Accept the export of `predecessor` into this diary, before it is initialized
+    "mutation" RegisterPredecessor => "registerPredecessor" -> OperationResponse {
+        RESPONSE_FIELDS
+    }
+    required { predecessor: String => "ApplicationId!" }
+    optional {}
+}
+
+operation! {
+    /// Offer the diary to `new_owner`, who can accept it within 7 days
+    "mutation" ProposeOwnerTransfer => "proposeOwnerTransfer" -> OperationResponse {
+        RESPONSE_FIELDS
+    }
+    required {
+        secret_phrase: String => "String!",
+        new_owner: String => "AccountOwner!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Take over the diary proposed to the signer of the block
+    "mutation" AcceptOwnership => "acceptOwnership" -> OperationResponse { RESPONSE_FIELDS }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Withdraw a pending owner transfer
+    "mutation" CancelOwnerTransfer => "cancelOwnerTransfer" -> OperationResponse { RESPONSE_FIELDS }
+    required { secret_phrase: String => "String!" }
+    optional {}
+}
@@ -0,0 +1,219 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The [`GraphQLOperation`] trait and the `operation!` macro that
+//! declares a builder for each field of `QueryRoot`, `PublicQuery` and `MutationRoot`.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::ClientError;
+
+/// Fields selected for every `DiaryEntry` in a response
+pub const ENTRY_FIELDS: &str = "id title content timestamp createdAt notebookId revision \
+    metadata { mood rating energy customFields { key fieldType value } } \
+    attachments { blobHash mimeType size filename attachedAt } visibility";
+
+/// Fields selected for every `EntryHeader` in a response
+pub const HEADER_FIELDS: &str = "id title timestamp createdAt notebookId tags excerpt wordCount \
+    revision metadata { mood rating energy customFields { key fieldType value } } \
+    attachments { blobHash mimeType size filename attachedAt } visibility";
+
+/// Fields selected for every `OperationResponse` in a response
+pub const RESPONSE_FIELDS: &str = "success message";
+
+/// Fields selected for every `PublicEntry` in a response
+pub const PUBLIC_ENTRY_FIELDS: &str = "id title content createdAt updatedAt tags";
+
+/// Fields selected for every `Notebook` in a response
+pub const NOTEBOOK_FIELDS: &str = "id name archived createdAt isProtected";
+
+/// Fields selected for every `Draft` in a response
+pub const DRAFT_FIELDS: &str = "id title content notebookId createdAt updatedAt \
+    metadata { mood rating energy customFields { key fieldType value } }";
+
+/// Fields selected for every `Template` in a response
+pub const TEMPLATE_FIELDS: &str = "id name titlePattern contentSkeleton createdAt placeholders";
+
+/// Fields selected for every `Anniversary` in a response
+pub fn anniversary_fields() -> String {
+    format!("date writtenOn yearsAgo entry {{ {} }}", HEADER_FIELDS)
+}
+
+/// A query or mutation with typed variables and a typed result
+pub trait GraphQLOperation {
+    /// What the root field decodes into
+    type Response: DeserializeOwned;
+
+    /// `query` or `mutation`
+    const KIND: &'static str;
+
+    /// Name of the field in the schema
+    const FIELD: &'static str;
+
+    /// The root field that [`FIELD`](Self::FIELD) is nested in, e.g. `public`
+    const PARENT: Option<&'static str> = None;
+
+    /// Rust names and GraphQL types of the field's arguments, required ones first
+    const ARGUMENTS: &'static [(&'static str, &'static str)];
+
+    /// The GraphQL document sent to the service
+    fn document(&self) -> String;
+
+    /// The variables sent along with the document
+    fn variables(&self) -> Value;
+
+    /// Decode the field from the `data` object of a response
+    fn decode(data: Value) -> Result<Self::Response, ClientError>
+    where
+        Self: Sized,
+    {
+        decode_field::<Self>(data)
+    }
+
+    /// GraphQL names and types of the field's arguments
+    fn arguments() -> Vec<(String, &'static str)>
+    where
+        Self: Sized,
+    {
+        Self::ARGUMENTS
+            .iter()
+            .map(|(argument, graphql_type)| (camel_case(argument), *graphql_type))
+            .collect()
+    }
+}
+
+/// Build `kind Name($arg: Type, …) { field(arg: $arg, …) { selection } }`,
+/// with the field wrapped in `parent { … }` for nested fields
+pub fn build_document(
+    kind: &str,
+    name: &str,
+    parent: Option<&str>,
+    field: &str,
+    arguments: &[(&str, &str)],
+    selection: &str,
+) -> String {
+    let mut document = format!("{} {}", kind, name);
+    let mut call = field.to_string();
+
+    if !arguments.is_empty() {
+        let definitions: Vec<String> = arguments
+            .iter()
+            .map(|(argument, graphql_type)| format!("${}: {}", camel_case(argument), graphql_type))
+            .collect();
+        let uses: Vec<String> = arguments
+            .iter()
+            .map(|(argument, _)| format!("{0}: ${0}", camel_case(argument)))
+            .collect();
+        document.push_str(&format!("({})", definitions.join(", ")));
+        call.push_str(&format!("({})", uses.join(", ")));
+    }
+
+    if !selection.is_empty() {
+        call = format!("{} {{ {} }}", call, selection);
+    }
+    if let Some(parent) = parent {
+        call = format!("{} {{ {} }}", parent, call);
+    }
+    format!("{} {{ {} }}", document, call)
+}
+
+/// Convert a Rust argument name into the name GraphQL uses, e.g. `secret_phrase` to `secretPhrase`
+pub fn camel_case(name: &str) -> String {
+    let mut output = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            output.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// Decode the field of an operation from the `data` object
+pub fn decode_field<O: GraphQLOperation>(mut data: Value) -> Result<O::Response, ClientError> {
+    if let Some(parent) = O::PARENT {
+        data = data
+            .get_mut(parent)
+            .map(Value::take)
+            .ok_or(ClientError::MissingData(parent))?;
+    }
+    let value = data
+        .get_mut(O::FIELD)
+        .map(Value::take)
+        .ok_or(ClientError::MissingData(O::FIELD))?;
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Declare a builder for one field, optionally nested `in` a root field. The argument
+/// lists mirror the resolver's signature in `DiaryService`, with the GraphQL type of each
+/// argument; `tests/schema.rs` checks them against `backend/schema.graphql`.
+macro_rules! operation {
+    (
+        $(#[$doc:meta])*
+        $kind:literal $name:ident => $field:literal $(in $parent:literal)? -> $response:ty {
+            $selection:expr
+        }
+        required { $($required:ident: $required_type:ty => $required_graphql:literal),* $(,)? }
+        optional { $($optional:ident: $optional_type:ty => $optional_graphql:literal),* $(,)? }
+    ) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq, serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct $name {
+            $(pub $required: $required_type,)*
+            $(
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub $optional: Option<$optional_type>,
+            )*
+        }
+
+        impl $name {
+            #[allow(clippy::new_without_default)]
+            pub fn new($($required: $required_type),*) -> Self {
+                $name {
+                    $($required,)*
+                    $($optional: None,)*
+                }
+            }
+
+            $(
+                pub fn $optional(mut self, $optional: $optional_type) -> Self {
+                    self.$optional = Some($optional);
+                    self
+                }
+            )*
+        }
+
+        impl $crate::operation::GraphQLOperation for $name {
+            type Response = $response;
+            const KIND: &'static str = $kind;
+            const FIELD: &'static str = $field;
+            $(const PARENT: Option<&'static str> = Some($parent);)?
+            const ARGUMENTS: &'static [(&'static str, &'static str)] = &[
+                $((stringify!($required), $required_graphql),)*
+                $((stringify!($optional), $optional_graphql),)*
+            ];
+
+            fn document(&self) -> String {
+                $crate::operation::build_document(
+                    $kind,
+                    stringify!($name),
+                    Self::PARENT,
+                    $field,
+                    Self::ARGUMENTS,
+                    $selection,
+                )
+            }
+
+            fn variables(&self) -> serde_json::Value {
+                serde_json::to_value(self).expect("Variables are always valid JSON")
+            }
+        }
+    };
+}
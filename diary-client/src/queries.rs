@@ -0,0 +1,474 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builders for the fields of `QueryRoot` and of the `public` read API.
+
+use diary_backend::{
+    Attachment, DiaryEntry, EnergyLevel, EntryHeader, Mood, OwnerTransfer, OwnershipEvent,
+    TemplateValue,
+};
+use serde::Serialize;
+
+use crate::{
+    operation::{
+        anniversary_fields, DRAFT_FIELDS, ENTRY_FIELDS, HEADER_FIELDS, NOTEBOOK_FIELDS,
+        PUBLIC_ENTRY_FIELDS, TEMPLATE_FIELDS,
+    },
+    responses,
+};
+
+/// A filter on entries for [`Entries`]. All given conditions must match;
+/// `and`, `or` and `not` combine nested filters.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_before: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_contains: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_contains: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_contains: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MetadataFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favorite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_attachments: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub and: Option<Vec<EntryFilter>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub or: Option<Vec<EntryFilter>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<EntryFilter>>,
+}
+
+/// A filter on entry metadata; all given conditions must match
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mood: Option<Mood>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_rating: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_rating: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub energy: Option<EnergyLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_value: Option<String>,
+}
+
+/// Sort order for [`Entries`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EntryOrder {
+    UpdatedDesc,
+    UpdatedAsc,
+    CreatedDesc,
+    CreatedAsc,
+    TitleAsc,
+    TitleDesc,
+    RatingDesc,
+}
+
+operation! {
+    /// Number of public entries
+    "query" PublicEntryCount => "entryCount" in "public" -> u64 { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Public entries, newest first
+    "query" PublicEntries => "entries" in "public" -> Vec<responses::PublicEntry> {
+        PUBLIC_ENTRY_FIELDS
+    }
+    required {}
+    optional { limit: i32 => "Int" }
+}
+
+operation! {
+    /// A public entry by ID; `None` for entries that are not public
+    "query" PublicEntry => "entry" in "public" -> Option<responses::PublicEntry> {
+        PUBLIC_ENTRY_FIELDS
+    }
+    required { id: u64 => "Int!" }
+    optional {}
+}
+
+operation! {
+    /// Public entries as an Atom 1.0 document, newest first
+    "query" AtomFeed => "atomFeed" in "public" -> String { "" }
+    required { link: String => "String!" }
+    optional {
+        title: String => "String",
+        description: String => "String",
+        limit: i32 => "Int",
+    }
+}
+
+operation! {
+    /// Public entries as an RSS 2.0 document, newest first
+    "query" RssFeed => "rssFeed" in "public" -> String { "" }
+    required { link: String => "String!" }
+    optional {
+        title: String => "String",
+        description: String => "String",
+        limit: i32 => "Int",
+    }
+}
+
+operation! {
+    /// Check if the diary has been initialized
+    "query" IsInitialized => "isInitialized" -> bool { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Get the diary owner
+    "query" Owner => "owner" -> String { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// The owner transfer waiting to be accepted, if any
+    "query" PendingOwnerTransfer => "pendingOwnerTransfer" -> Option<OwnerTransfer> {
+        "newOwner proposedAt expiresAt"
+    }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Every proposed, cancelled and accepted owner transfer, oldest first
+    "query" OwnershipLog => "ownershipLog" -> Vec<OwnershipEvent> {
+        "action owner newOwner timestamp"
+    }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Get the number of entries, not counting deleted ones
+    "query" EntryCount => "entryCount" -> u64 { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Schema version of the stored state
+    "query" SchemaVersion => "schemaVersion" -> u32 { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// The limits queries to this service must stay within
+    "query" QueryLimits => "queryLimits" -> diary_backend::QueryLimits {
+        "maxDepth maxComplexity maxResults"
+    }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Get all notebooks
+    "query" Notebooks => "notebooks" -> Vec<responses::Notebook> { NOTEBOOK_FIELDS }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Get a specific notebook by ID
+    "query" Notebook => "notebook" -> Option<responses::Notebook> { NOTEBOOK_FIELDS }
+    required { id: u64 => "Int!" }
+    optional {}
+}
+
+operation! {
+    /// Get diary entry headers matching a filter. Without an order: pinned first,
+    /// then newest first. Without a notebook, entries of protected notebooks are left out,
+    /// and without a reader phrase, entries only members or the owner may read.
+    "query" Entries => "entries" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required {}
+    optional {
+        filter: EntryFilter => "EntryFilter",
+        order_by: EntryOrder => "EntryOrder",
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get the IDs of the pinned entries readable in the requested scope, in display order
+    "query" PinnedIds => "pinnedIds" -> Vec<u64> { "" }
+    required {}
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get the favorite entries (newest first)
+    "query" Favorites => "favorites" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required {}
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get a specific diary entry by ID, with its content
+    "query" Entry => "entry" -> Option<DiaryEntry> { ENTRY_FIELDS }
+    required { id: u64 => "Int!" }
+    optional {
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get unpublished drafts (most recently saved first)
+    "query" Drafts => "drafts" -> Vec<responses::Draft> { DRAFT_FIELDS }
+    required {}
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get a specific draft by ID
+    "query" Draft => "draft" -> Option<responses::Draft> { DRAFT_FIELDS }
+    required { id: u64 => "Int!" }
+    optional { secret_phrase: String => "String" }
+}
+
+operation! {
+    /// Get all entry templates
+    "query" Templates => "templates" -> Vec<responses::Template> { TEMPLATE_FIELDS }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Get a specific template by ID
+    "query" Template => "template" -> Option<responses::Template> { TEMPLATE_FIELDS }
+    required { id: u64 => "Int!" }
+    optional {}
+}
+
+operation! {
+    /// Render a template as `addEntryFromTemplate` would right now, without adding an entry
+    "query" PreviewTemplate => "previewTemplate" -> responses::RenderedTemplate {
+        "title content"
+    }
+    required {
+        template_id: u64 => "Int!",
+        values: Vec<TemplateValue> => "[TemplateValue!]!",
+    }
+    optional {}
+}
+
+operation! {
+    /// Get the entries an entry links to
+    "query" LinkedEntries => "linkedEntries" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required { id: u64 => "Int!" }
+    optional {
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get the entries linking to an entry
+    "query" Backlinks => "backlinks" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required { id: u64 => "Int!" }
+    optional {
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get the files attached to an entry
+    "query" Attachments => "attachments" -> Vec<Attachment> {
+        "blobHash mimeType size filename attachedAt"
+    }
+    required { entry_id: u64 => "Int!" }
+    optional {
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get the latest N entries. Deprecated: use [`Entries`] without an order.
+    "query" LatestEntries => "latestEntries" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required { limit: i32 => "Int!" }
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get entries created within a time range (timestamps in microseconds).
+    /// Deprecated: use [`Entries`] with `created_after` and `created_before`.
+    "query" EntriesInRange => "entriesInRange" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required {
+        start_timestamp: u64 => "Int!",
+        end_timestamp: u64 => "Int!",
+    }
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Search entries by title (case-insensitive).
+    /// Deprecated: use [`Entries`] with `title_contains`.
+    "query" SearchByTitle => "searchByTitle" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required { query: String => "String!" }
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Search entries by content (case-insensitive).
+    /// Deprecated: use [`Entries`] with `content_contains`.
+    "query" SearchByContent => "searchByContent" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required { query: String => "String!" }
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Get entries whose metadata matches a filter.
+    /// Deprecated: use [`Entries`] with `metadata`.
+    "query" EntriesByMetadata => "entriesByMetadata" -> Vec<EntryHeader> { HEADER_FIELDS }
+    required { filter: MetadataFilter => "MetadataFilter!" }
+    optional {
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Aggregate mood, rating, energy and numeric custom fields,
+    /// optionally over the entries matching a filter
+    "query" MetadataSummary => "metadataSummary" -> responses::MetadataSummary {
+        "entryCount ratedCount averageRating moods { mood count } \
+         energyLevels { energy count } numericFields { key count average }"
+    }
+    required {}
+    optional {
+        filter: MetadataFilter => "MetadataFilter",
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// The owner's timezone as an offset from UTC in minutes
+    "query" UtcOffsetMinutes => "utcOffsetMinutes" -> i32 { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Progress of the export to a successor application, if one was started
+    "query" ExportProgress => "exportProgress" -> Option<responses::ExportProgress> {
+        "successor nextEntryId finished"
+    }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// The predecessor application while its export is being imported
+    "query" ImportingFrom => "importingFrom" -> Option<String> { "" }
+    required {}
+    optional {}
+}
+
+operation! {
+    /// Entries written on the same calendar day in previous years.
+    /// `date` is `YYYY-MM-DD` in the owner's timezone and defaults to today.
+    "query" OnThisDay => "onThisDay" -> Vec<responses::Anniversary> { &anniversary_fields() }
+    required {}
+    optional {
+        date: String => "String",
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Anniversaries of entries falling within `days` days starting at `date`
+    /// (default: the next 7 days starting today), soonest first
+    "query" Anniversaries => "anniversaries" -> Vec<responses::Anniversary> {
+        &anniversary_fields()
+    }
+    required {}
+    optional {
+        date: String => "String",
+        days: i32 => "Int",
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
+
+operation! {
+    /// Writing statistics. Without a notebook, protected notebooks are left out;
+    /// private entries are never counted.
+    "query" Stats => "stats" -> responses::DiaryStats {
+        "totalEntries totalWords averageWords currentStreak longestStreak \
+         perDay { period count } perWeek { period count } perMonth { period count } \
+         topWords { term count } topTags { term count } heatmap { date count level }"
+    }
+    required {}
+    optional {
+        top: i32 => "Int",
+        heatmap_days: i32 => "Int",
+        notebook_id: u64 => "Int",
+        secret_phrase: String => "String",
+        reader_phrase: String => "String",
+    }
+}
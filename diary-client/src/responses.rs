@@ -0,0 +1,158 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Response types for the objects the service returns in a shape of their own.
+//! Objects that are backend types, such as `DiaryEntry`, decode into those instead.
+
+use diary_backend::{EnergyLevel, EntryHeader, EntryMetadata, Mood};
+use serde::Deserialize;
+
+/// A named journal grouping entries within the diary
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notebook {
+    pub id: u64,
+    pub name: String,
+    pub archived: bool,
+    pub created_at: u64,
+    pub is_protected: bool,
+}
+
+/// A draft that becomes an entry once published
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Draft {
+    pub id: u64,
+    pub title: String,
+    pub content: String,
+    pub notebook_id: u64,
+    pub metadata: EntryMetadata,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// A reusable structure for recurring entries
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub id: u64,
+    pub name: String,
+    pub title_pattern: String,
+    pub content_skeleton: String,
+    pub created_at: u64,
+    pub placeholders: Vec<String>,
+}
+
+/// A template rendered with the current time and the given values
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RenderedTemplate {
+    pub title: String,
+    pub content: String,
+}
+
+/// Progress of the export to a successor application
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub successor: String,
+    pub next_entry_id: u64,
+    pub finished: bool,
+}
+
+/// The publicly visible part of an entry
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicEntry {
+    pub id: u64,
+    pub title: String,
+    pub content: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub tags: Vec<String>,
+}
+
+/// An entry remembered on the same calendar day of a later year
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Anniversary {
+    /// The day of the anniversary (`YYYY-MM-DD`)
+    pub date: String,
+    /// The day the entry was written (`YYYY-MM-DD`)
+    pub written_on: String,
+    pub years_ago: u32,
+    pub entry: EntryHeader,
+}
+
+/// Number of entries with a given mood
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct MoodCount {
+    pub mood: Mood,
+    pub count: u64,
+}
+
+/// Number of entries with a given energy level
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct EnergyCount {
+    pub energy: EnergyLevel,
+    pub count: u64,
+}
+
+/// Average of a numeric custom field
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct FieldAverage {
+    pub key: String,
+    pub count: u64,
+    pub average: f64,
+}
+
+/// Aggregated metadata over a set of entries
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataSummary {
+    pub entry_count: u64,
+    pub rated_count: u64,
+    pub average_rating: Option<f64>,
+    pub moods: Vec<MoodCount>,
+    pub energy_levels: Vec<EnergyCount>,
+    pub numeric_fields: Vec<FieldAverage>,
+}
+
+/// Number of entries in a day, week (labelled by its Monday) or month
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct PeriodCount {
+    pub period: String,
+    pub count: u64,
+}
+
+/// How often a word or tag is used
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TermCount {
+    pub term: String,
+    pub count: u64,
+}
+
+/// One cell of the calendar heatmap
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct HeatmapDay {
+    pub date: String,
+    pub count: u64,
+    /// Intensity from 0 (no entries) to 4 (busiest days)
+    pub level: u8,
+}
+
+/// Writing statistics for the diary
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiaryStats {
+    pub total_entries: u64,
+    pub total_words: u64,
+    pub average_words: f64,
+    pub current_streak: u64,
+    pub longest_streak: u64,
+    pub per_day: Vec<PeriodCount>,
+    pub per_week: Vec<PeriodCount>,
+    pub per_month: Vec<PeriodCount>,
+    pub top_words: Vec<TermCount>,
+    pub top_tags: Vec<TermCount>,
+    pub heatmap: Vec<HeatmapDay>,
+}
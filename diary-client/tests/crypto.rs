@@ -0,0 +1,77 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the encryption of private entries.
+
+use diary_backend::{is_encrypted, ENCRYPTED_PREFIX};
+use diary_client::{crypto::EntryKey, ClientError, DiaryEntry, EntryHeader, Visibility};
+
+const CHAIN_ID: &str = "e476187f6ddfeb9d588c7b45d3df334d5501d6499b3f9ad5595cae86cce16a65";
+
+/// Test that envelopes open with the key they were sealed with and pass the contract's check
+#[test]
+fn test_encrypt_and_decrypt() {
+    let key = EntryKey::derive("secret", CHAIN_ID);
+    let envelope = key.encrypt("Dear diary, ünïcödé");
+
+    assert!(envelope.starts_with(ENCRYPTED_PREFIX));
+    assert!(is_encrypted(&envelope));
+    assert!(is_encrypted(&key.encrypt("")));
+    assert_eq!(key.decrypt(&envelope).unwrap(), "Dear diary, ünïcödé");
+
+    // Every envelope has its own nonce
+    assert_ne!(key.encrypt("same"), key.encrypt("same"));
+}
+
+/// Test that the phrase and the chain both go into the key
+#[test]
+fn test_wrong_key() {
+    let envelope = EntryKey::derive("secret", CHAIN_ID).encrypt("Dear diary");
+
+    for key in [
+        EntryKey::derive("other", CHAIN_ID),
+        EntryKey::derive("secret", "a1b2"),
+    ] {
+        assert!(matches!(
+            key.decrypt(&envelope),
+            Err(ClientError::Decrypt(_))
+        ));
+    }
+    let key = EntryKey::derive("secret", CHAIN_ID);
+    assert!(key.decrypt("Dear diary").is_err());
+    assert!(key.decrypt("enc:v1:not base64!").is_err());
+    assert!(key.decrypt("enc:v1:q83v").is_err());
+}
+
+/// Test that only private entries and their headers are decrypted
+#[test]
+fn test_open_entry() {
+    let key = EntryKey::derive("secret", CHAIN_ID);
+    let mut entry: DiaryEntry = serde_json::from_value(serde_json::json!({
+        "id": 3,
+        "title": key.encrypt("Plans"),
+        "content": key.encrypt("Quit my job"),
+        "timestamp": 0,
+        "createdAt": 0,
+        "notebookId": 0,
+        "metadata": { "mood": null, "rating": null, "energy": null, "customFields": [] },
+        "attachments": [],
+        "visibility": "PRIVATE",
+        "revision": 0,
+    }))
+    .unwrap();
+
+    let mut shared = entry.clone();
+    shared.visibility = Visibility::Members;
+    key.open_entry(&mut shared).unwrap();
+    assert!(is_encrypted(&shared.title));
+
+    let mut header = EntryHeader::from(&entry);
+    assert!(header.excerpt.is_empty());
+    key.open_header(&mut header).unwrap();
+    assert_eq!(header.title, "Plans");
+
+    key.open_entry(&mut entry).unwrap();
+    assert_eq!(entry.title, "Plans");
+    assert_eq!(entry.content, "Quit my job");
+}
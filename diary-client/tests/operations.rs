@@ -0,0 +1,298 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the documents and variables built for each operation,
+//! and for decoding responses into the backend types.
+
+use diary_client::{
+    application_endpoint,
+    mutations::{
+        AcceptOwnership, AddEntries, AddEntry, BatchEntry, PatchEntry, ProposeOwnerTransfer,
+        ReplaceRange, UpdateEntry,
+    },
+    queries::{
+        Entries, Entry, EntryFilter, EntryOrder, IsInitialized, LatestEntries, MetadataFilter,
+        OwnershipLog, PublicEntries, SearchByTitle,
+    },
+    ContentEdit, DiaryEntry, EntryHeader, EntryMetadata, GraphQLOperation, Mood, OwnershipAction,
+    OwnershipEvent, Visibility,
+};
+use serde_json::json;
+
+/// Test the endpoint of an application on a node service
+#[test]
+fn test_application_endpoint() {
+    assert_eq!(
+        application_endpoint("http://localhost:8080/", "e476", "a1b2"),
+        "http://localhost:8080/chains/e476/applications/a1b2"
+    );
+}
+
+/// Test operations without arguments
+#[test]
+fn test_document_without_arguments() {
+    assert_eq!(
+        IsInitialized::new().document(),
+        "query IsInitialized { isInitialized }"
+    );
+    assert_eq!(IsInitialized::new().variables(), json!({}));
+}
+
+/// Test that arguments use GraphQL names and that unset optional arguments are left out
+#[test]
+fn test_query_arguments() {
+    let query = SearchByTitle::new("standup".to_string()).notebook_id(2);
+    let document = query.document();
+
+    assert!(document.starts_with(
+        "query SearchByTitle($query: String!, $notebookId: Int, $secretPhrase: String, \
+         $readerPhrase: String) { searchByTitle(query: $query, notebookId: $notebookId, \
+         secretPhrase: $secretPhrase, readerPhrase: $readerPhrase) {"
+    ));
+    assert!(document.contains("createdAt"));
+    assert_eq!(
+        query.variables(),
+        json!({ "query": "standup", "notebookId": 2 })
+    );
+}
+
+/// Test that fields of the `public` read API are sent and decoded inside `public`
+#[test]
+fn test_nested_field() {
+    let query = PublicEntries::new().limit(2);
+    assert_eq!(
+        query.document(),
+        "query PublicEntries($limit: Int) { public { entries(limit: $limit) \
+         { id title content createdAt updatedAt tags } } }"
+    );
+
+    let data = json!({ "public": { "entries": [{
+        "id": 4,
+        "title": "Launch",
+        "content": "We shipped #release",
+        "createdAt": 1,
+        "updatedAt": 2,
+        "tags": ["release"],
+    }] } });
+    let entries = PublicEntries::decode(data).unwrap();
+    assert_eq!(entries[0].id, 4);
+    assert_eq!(entries[0].tags, vec!["release"]);
+}
+
+/// Test that entry filters serialize to the `EntryFilter` input, leaving out unset conditions
+#[test]
+fn test_entry_filter_variables() {
+    let query = Entries::new()
+        .filter(EntryFilter {
+            created_after: Some(1_700_000_000_000_000),
+            or: Some(vec![
+                EntryFilter {
+                    tags: Some(vec!["work".to_string()]),
+                    ..EntryFilter::default()
+                },
+                EntryFilter {
+                    metadata: Some(MetadataFilter {
+                        mood: Some(Mood::Great),
+                        ..MetadataFilter::default()
+                    }),
+                    ..EntryFilter::default()
+                },
+            ]),
+            not: Some(Box::new(EntryFilter {
+                text_contains: Some("draft".to_string()),
+                ..EntryFilter::default()
+            })),
+            ..EntryFilter::default()
+        })
+        .order_by(EntryOrder::CreatedDesc);
+
+    assert!(query
+        .document()
+        .starts_with("query Entries($filter: EntryFilter, $orderBy: EntryOrder,"));
+    assert_eq!(
+        query.variables(),
+        json!({
+            "filter": {
+                "createdAfter": 1_700_000_000_000_000u64,
+                "or": [{ "tags": ["work"] }, { "metadata": { "mood": "GREAT" } }],
+                "not": { "textContains": "draft" },
+            },
+            "orderBy": "CREATED_DESC",
+        })
+    );
+}
+
+/// Test that mutation inputs serialize the way the schema expects
+#[test]
+fn test_mutation_variables() {
+    let mutation = AddEntry::new(
+        "secret".to_string(),
+        "Day one".to_string(),
+        "Hello".to_string(),
+    )
+    .metadata(EntryMetadata {
+        mood: Some(Mood::Great),
+        rating: Some(5),
+        ..EntryMetadata::default()
+    })
+    .visibility(Visibility::Public);
+
+    assert_eq!(
+        mutation.variables(),
+        json!({
+            "secretPhrase": "secret",
+            "title": "Day one",
+            "content": "Hello",
+            "metadata": { "mood": "GREAT", "rating": 5, "energy": null, "customFields": [] },
+            "visibility": "PUBLIC",
+        })
+    );
+    assert!(mutation
+        .document()
+        .contains("$metadata: EntryMetadataInput, $visibility: Visibility"));
+
+    let update = UpdateEntry::new("secret".to_string(), 7)
+        .title("Renamed".to_string())
+        .expected_revision(2);
+    assert!(update.document().contains("$expectedRevision: Int"));
+    assert_eq!(
+        update.variables(),
+        json!({ "secretPhrase": "secret", "entryId": 7, "title": "Renamed", "expectedRevision": 2 })
+    );
+
+    let edit = ReplaceRange::new("secret".to_string(), 7, 12, 3, "new".to_string());
+    assert!(edit
+        .document()
+        .contains("replaceRange(secretPhrase: $secretPhrase, entryId: $entryId, offset: $offset, len: $len, text: $text)"));
+    assert_eq!(
+        edit.variables(),
+        json!({ "secretPhrase": "secret", "entryId": 7, "offset": 12, "len": 3, "text": "new" })
+    );
+
+    let patch = PatchEntry::new(
+        "secret".to_string(),
+        7,
+        2,
+        vec![ContentEdit {
+            offset: 12,
+            len: 3,
+            text: "new".to_string(),
+        }],
+    );
+    assert!(patch
+        .document()
+        .contains("$baseRevision: Int!, $patch: [ContentEditInput!]!"));
+    assert_eq!(
+        patch.variables(),
+        json!({
+            "secretPhrase": "secret",
+            "entryId": 7,
+            "baseRevision": 2,
+            "patch": [{ "offset": 12, "len": 3, "text": "new" }],
+        })
+    );
+
+    let batch = AddEntries::new(
+        "secret".to_string(),
+        vec![BatchEntry {
+            title: "A".to_string(),
+            content: "B".to_string(),
+            metadata: None,
+            notebook_id: Some(2),
+            visibility: Some(Visibility::Public),
+        }],
+    );
+    assert!(batch.document().contains("$entries: [BatchEntryInput!]!"));
+    assert_eq!(
+        batch.variables()["entries"],
+        json!([{ "title": "A", "content": "B", "notebookId": 2, "visibility": "PUBLIC" }])
+    );
+}
+
+/// Test decoding an entry as returned by the service
+#[test]
+fn test_decode_entry() {
+    let value = json!({
+        "id": 3,
+        "title": "Day one",
+        "content": "Hello #first",
+        "timestamp": 1_700_000_000_000_000u64,
+        "createdAt": 1_700_000_000_000_000u64,
+        "notebookId": 0,
+        "revision": 4,
+        "metadata": {
+            "mood": "GOOD",
+            "rating": 4,
+            "energy": null,
+            "customFields": [{ "key": "sleep", "fieldType": "NUMBER", "value": "7.5" }],
+        },
+        "attachments": [],
+        "visibility": "MEMBERS",
+    });
+
+    let entry: Option<DiaryEntry> = serde_json::from_value(value).unwrap();
+    let entry = entry.unwrap();
+    assert_eq!(entry.id, 3);
+    assert_eq!(entry.revision, 4);
+    assert_eq!(entry.metadata.mood, Some(Mood::Good));
+    assert_eq!(entry.metadata.custom_fields[0].key, "sleep");
+    assert_eq!(entry.visibility, Visibility::Members);
+
+    assert_eq!(<Entry as GraphQLOperation>::FIELD, "entry");
+}
+
+/// Test that list queries select and decode entry headers, without the content
+#[test]
+fn test_decode_entry_headers() {
+    let document = LatestEntries::new(5).document();
+    assert!(document.contains("excerpt wordCount"));
+    assert!(!document.contains("content"));
+
+    let value = json!([{
+        "id": 3,
+        "title": "Day one",
+        "timestamp": 1_700_000_000_000_000u64,
+        "createdAt": 1_700_000_000_000_000u64,
+        "notebookId": 0,
+        "tags": ["first"],
+        "excerpt": "Hello #first",
+        "wordCount": 2,
+        "metadata": { "mood": null, "rating": null, "energy": null, "customFields": [] },
+        "attachments": [],
+        "visibility": "PUBLIC",
+        "revision": 0,
+    }]);
+
+    let headers: Vec<EntryHeader> = serde_json::from_value(value).unwrap();
+    assert_eq!(headers[0].tags, vec!["first"]);
+    assert_eq!(headers[0].word_count, 2);
+    assert_eq!(headers[0].visibility, Visibility::Public);
+}
+
+/// Test the owner transfer mutations and decoding the ownership log
+#[test]
+fn test_owner_transfer() {
+    let propose = ProposeOwnerTransfer::new("secret".to_string(), "0x5e1f".to_string());
+    assert!(propose
+        .document()
+        .contains("$secretPhrase: String!, $newOwner: AccountOwner!"));
+    assert_eq!(
+        propose.variables(),
+        json!({ "secretPhrase": "secret", "newOwner": "0x5e1f" })
+    );
+    assert_eq!(
+        AcceptOwnership::new().document(),
+        "mutation AcceptOwnership { acceptOwnership { success message } }"
+    );
+
+    let value = json!([{
+        "action": "ACCEPTED",
+        "owner": "0xa11c",
+        "newOwner": "0x5e1f",
+        "timestamp": 1_700_000_000_000_000u64,
+    }]);
+    let log: Vec<OwnershipEvent> = serde_json::from_value(value).unwrap();
+    assert_eq!(log[0].action, OwnershipAction::Accepted);
+    assert_eq!(log[0].new_owner, "0x5e1f");
+    assert_eq!(<OwnershipLog as GraphQLOperation>::FIELD, "ownershipLog");
+}
@@ -0,0 +1,187 @@
+// Copyright (c) Linera.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Test that the builders cover the service's schema: one builder for every field of
+//! `QueryRoot`, `PublicQuery` and `MutationRoot`, with the same arguments and types.
+
+use std::collections::BTreeMap;
+
+use diary_client::{mutations::*, queries::*, GraphQLOperation};
+
+/// The SDL committed next to the service, checked against it by the backend's tests
+const SCHEMA: &str = include_str!("../../backend/schema.graphql");
+
+/// Operation kind, parent field and field name
+type Field = (&'static str, Option<String>, String);
+
+/// Arguments by GraphQL name, with their types
+type Arguments = BTreeMap<String, String>;
+
+fn builder<O: GraphQLOperation>() -> (Field, Arguments) {
+    let field = (O::KIND, O::PARENT.map(str::to_string), O::FIELD.to_string());
+    let arguments = O::arguments()
+        .into_iter()
+        .map(|(name, graphql_type)| (name, graphql_type.to_string()))
+        .collect();
+    (field, arguments)
+}
+
+macro_rules! builders {
+    ($($name:ty),* $(,)?) => {
+        vec![$(builder::<$name>()),*]
+    };
+}
+
+/// The fields of an object type in the SDL, with their arguments
+fn schema_fields(type_name: &str) -> Vec<(String, Arguments)> {
+    let start = SCHEMA
+        .find(&format!("type {} {{", type_name))
+        .unwrap_or_else(|| panic!("No type {} in the schema", type_name));
+    let body = &SCHEMA[start..];
+    let body = &body[body.find('{').unwrap() + 1..body.find("\n}").unwrap()];
+
+    let mut fields = Vec::new();
+    let mut in_description = false;
+    for line in body.lines().map(str::trim) {
+        if line.starts_with("\"\"\"") {
+            in_description = !in_description;
+            continue;
+        }
+        if in_description || line.is_empty() {
+            continue;
+        }
+        let name_end = line.find(['(', ':']).unwrap();
+        let mut arguments = Arguments::new();
+        if line[name_end..].starts_with('(') {
+            let list = &line[name_end + 1..line.find(')').unwrap()];
+            for argument in list.split(", ") {
+                let (name, graphql_type) = argument.split_once(": ").unwrap();
+                arguments.insert(name.to_string(), graphql_type.to_string());
+            }
+        }
+        fields.push((line[..name_end].to_string(), arguments));
+    }
+    fields
+}
+
+/// Test that every field has exactly one builder, and that its arguments match
+#[test]
+fn test_builders_match_schema() {
+    let builders = builders![
+        // QueryRoot
+        IsInitialized,
+        Owner,
+        PendingOwnerTransfer,
+        OwnershipLog,
+        EntryCount,
+        SchemaVersion,
+        QueryLimits,
+        Notebooks,
+        Notebook,
+        Entries,
+        PinnedIds,
+        Favorites,
+        Entry,
+        Drafts,
+        Draft,
+        Templates,
+        Template,
+        PreviewTemplate,
+        LinkedEntries,
+        Backlinks,
+        Attachments,
+        LatestEntries,
+        EntriesInRange,
+        SearchByTitle,
+        SearchByContent,
+        EntriesByMetadata,
+        MetadataSummary,
+        UtcOffsetMinutes,
+        ExportProgress,
+        ImportingFrom,
+        OnThisDay,
+        Anniversaries,
+        Stats,
+        // PublicQuery
+        PublicEntryCount,
+        PublicEntries,
+        PublicEntry,
+        AtomFeed,
+        RssFeed,
+        // MutationRoot
+        Initialize,
+        AddEntry,
+        UpdateEntry,
+        AppendToEntry,
+        ReplaceRange,
+        PatchEntry,
+        DeleteEntry,
+        AddEntries,
+        CreateNotebook,
+        RenameNotebook,
+        ArchiveNotebook,
+        UnarchiveNotebook,
+        MoveEntry,
+        SaveDraft,
+        UpdateDraft,
+        PublishDraft,
+        DiscardDraft,
+        CreateTemplate,
+        UpdateTemplate,
+        DeleteTemplate,
+        AddEntryFromTemplate,
+        AttachFile,
+        DetachFile,
+        LinkEntries,
+        UnlinkEntries,
+        PinEntry,
+        UnpinEntry,
+        Favorite,
+        Unfavorite,
+        SetTimezone,
+        SetMembersPhrase,
+        ExportToSuccessor,
+        RegisterPredecessor,
+        ProposeOwnerTransfer,
+        AcceptOwnership,
+        CancelOwnerTransfer,
+    ];
+
+    let mut expected: BTreeMap<Field, Arguments> = BTreeMap::new();
+    for (kind, parent, type_name) in [
+        ("query", None, "QueryRoot"),
+        ("query", Some("public"), "PublicQuery"),
+        ("mutation", None, "MutationRoot"),
+    ] {
+        for (name, arguments) in schema_fields(type_name) {
+            // `public` itself is covered by the builders of its fields
+            if type_name == "QueryRoot" && name == "public" {
+                continue;
+            }
+            expected.insert((kind, parent.map(str::to_string), name), arguments);
+        }
+    }
+
+    let mut covered: BTreeMap<Field, Arguments> = BTreeMap::new();
+    for (field, arguments) in builders {
+        let schema_arguments = expected
+            .get(&field)
+            .unwrap_or_else(|| panic!("Builder for {:?}, which is not in the schema", field));
+        assert_eq!(&arguments, schema_arguments, "Arguments of {:?}", field);
+        assert!(
+            covered.insert(field.clone(), arguments).is_none(),
+            "Two builders for {:?}",
+            field
+        );
+    }
+
+    let missing: Vec<&Field> = expected
+        .keys()
+        .filter(|f| !covered.contains_key(*f))
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "Fields without a builder: {:?}",
+        missing
+    );
+}